@@ -2,7 +2,7 @@ use handlebars::{Context, Handlebars, Helper, Output, RenderContext, RenderError
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fs::{self, File},
     io::Write,
 };
@@ -129,6 +129,36 @@ struct AssistantCharacteristic {
     pub out_values: Option<HashMap<String, Value>>,
 }
 
+/// Hand-written schema of the TLV8 characteristic payloads, read from `codegen/gen/tlv8.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Tlv8Schema {
+    /// Maps characteristic keys to the name of the type of their payload.
+    pub characteristics: HashMap<String, String>,
+    pub types: BTreeMap<String, Tlv8Type>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Tlv8Type {
+    pub description: String,
+    #[serde(default)]
+    pub tuple: bool,
+    pub fields: Vec<Tlv8Field>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Tlv8Field {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub tlv_type: String,
+    pub format: String,
+    /// One of `item`, `optional`, `list` or `entries`.
+    #[serde(default = "default_tlv8_kind")]
+    pub kind: String,
+    pub description: String,
+}
+
+fn default_tlv8_kind() -> String { "item".to_string() }
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct RenderMetadata {
     pub categories: HashMap<String, HomeKitCategory>,
@@ -526,84 +556,46 @@ fn out_values_helper(
     Ok(())
 }
 
-fn in_values_enum_helper(
-    h: &Helper,
-    _: &Handlebars,
-    _: &Context,
-    _: &mut RenderContext,
-    out: &mut dyn Output,
-) -> Result<(), RenderError> {
-    let param = h.param(0).unwrap().value().as_object().unwrap();
-    let mut values = param
-        .into_iter()
-        .map(|(key, val)| (key.clone(), val.clone().to_string().replace("\"", "")))
-        .collect::<Vec<(String, String)>>();
-    values.sort_by(|a, b| a.1.cmp(&b.1));
-
-    let mut output = String::from("\npub enum Value {\n");
-    for (key, val) in values {
-        let key = key
-            .to_lowercase()
-            .split("_")
-            .into_iter()
-            .map(|word| {
-                let mut c = word.chars().collect::<Vec<char>>();
-
-                if c.len() == 1 && c[0].is_numeric() {
-                    format!("Num{}", c[0])
-                } else {
-                    c[0] = c[0].to_uppercase().nth(0).unwrap();
-                    c.into_iter().collect::<String>()
-                }
-            })
-            .collect::<String>();
-
-        output.push_str(&format!("\t{} = {},\n", key, val));
-    }
-    output.push_str("}\n");
-    out.write(&output)?;
-
-    Ok(())
-}
+/// Collects the variants of the enum of a characteristic with enumerated values, sorted by their wire value.
+fn enum_values(
+    in_values: Option<&HashMap<String, Value>>,
+    out_values: Option<&HashMap<String, Value>>,
+) -> Option<Vec<Value>> {
+    let mut values = match (in_values, out_values) {
+        (Some(in_values), _) => in_values
+            .iter()
+            .map(|(key, val)| (key.clone(), val.to_string().replace("\"", "")))
+            .collect::<Vec<(String, String)>>(),
+        (None, Some(out_values)) => out_values
+            .iter()
+            .map(|(key, val)| (val.to_string().replace("\"", ""), key.clone()))
+            .collect::<Vec<(String, String)>>(),
+        (None, None) => return None,
+    };
+    values.sort_by_key(|(_, val)| val.parse::<i64>().unwrap());
 
-fn out_values_enum_helper(
-    h: &Helper,
-    _: &Handlebars,
-    _: &Context,
-    _: &mut RenderContext,
-    out: &mut dyn Output,
-) -> Result<(), RenderError> {
-    let param = h.param(0).unwrap().value().as_object().unwrap();
-    let mut values = param
+    let values = values
         .into_iter()
-        .map(|(key, val)| (val.clone().to_string().replace("\"", ""), key.clone()))
-        .collect::<Vec<(String, String)>>();
-    values.sort_by(|a, b| a.1.cmp(&b.1));
-
-    let mut output = String::from("\npub enum Value {\n");
-    for (key, val) in values {
-        let key = key
-            .to_lowercase()
-            .split("_")
-            .into_iter()
-            .map(|word| {
-                let mut c = word.chars().collect::<Vec<char>>();
-
-                if c.len() == 1 && c[0].is_numeric() {
-                    format!("Num{}", c[0])
-                } else {
-                    c[0] = c[0].to_uppercase().nth(0).unwrap();
-                    c.into_iter().collect::<String>()
-                }
-            })
-            .collect::<String>();
+        .map(|(key, val)| {
+            let name = key
+                .to_lowercase()
+                .split("_")
+                .map(|word| {
+                    let mut c = word.chars().collect::<Vec<char>>();
+                    if c[0].is_numeric() {
+                        format!("Num{}", word)
+                    } else {
+                        c[0] = c[0].to_uppercase().nth(0).unwrap();
+                        c.into_iter().collect::<String>()
+                    }
+                })
+                .collect::<String>();
 
-        output.push_str(&format!("\t{} = {},\n", key, val));
-    }
-    output.push_str("}\n");
-    out.write(&output)?;
+            json!({ "name": name, "value": val })
+        })
+        .collect();
 
-    Ok(())
+    Some(values)
 }
 
 fn perms_helper(
@@ -666,6 +658,46 @@ fn array_length_helper(
 
 fn shorten_uuid(id: &str) -> String { id.trim_start_matches('0').to_owned() }
 
+fn tlv8_type_helper(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> Result<(), RenderError> {
+    let format = h.param(0).unwrap().value().as_str().unwrap();
+    let kind = h.param(1).unwrap().value().as_str().unwrap();
+    let item_type = match format {
+        "string" => "String",
+        "bytes" => "Vec<u8>",
+        // integer & float formats are named like the Rust types, anything else is a nested payload type
+        _ => format,
+    };
+    let field_type = match kind {
+        "optional" => format!("Option<{}>", item_type),
+        "list" | "entries" => format!("Vec<{}>", item_type),
+        _ => item_type.to_string(),
+    };
+    out.write(&field_type)?;
+    Ok(())
+}
+
+fn tlv8_kind_helper(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> Result<(), RenderError> {
+    // optional fields are detected by the derive macro, lists and inline entries need to be marked
+    match h.param(0).unwrap().value().as_str().unwrap() {
+        "list" => out.write(", list")?,
+        "entries" => out.write(", entries")?,
+        _ => {},
+    }
+    Ok(())
+}
+
 fn snake_case_helper(
     h: &Helper,
     _: &Handlebars,
@@ -690,9 +722,14 @@ fn pascal_case_helper(
     _: &mut RenderContext,
     out: &mut dyn Output,
 ) -> Result<(), RenderError> {
-    let param = h.param(0).unwrap().value().as_str().unwrap().to_owned();
-    let param = param.replace("-", " ");
-    let name = param
+    let param = h.param(0).unwrap().value().as_str().unwrap();
+    out.write(&pascal_case(param))?;
+    Ok(())
+}
+
+fn pascal_case(s: &str) -> String {
+    let name = s
+        .replace("-", " ")
         .to_lowercase()
         .split(" ")
         .into_iter()
@@ -702,9 +739,7 @@ fn pascal_case_helper(
             c.into_iter().collect::<String>()
         })
         .collect::<String>();
-    let name = name.replace(" ", "").replace(".", "_");
-    out.write(&name)?;
-    Ok(())
+    name.replace(" ", "").replace(".", "_")
 }
 
 static CATEGORIES: &'static str = "// this file is auto-generated by hap-codegen\n
@@ -802,12 +837,15 @@ impl Serialize for HapType {
 
 static CHARACTERISTIC: &'static str = "// this file is auto-generated by hap-codegen\n
 use async_trait::async_trait;
-use serde::Serialize;
-use serde_json::json;
+{{#if enum_values}}use serde::{ser::Serializer, Serialize};
+{{else}}use serde::Serialize;
+{{/if}}use serde_json::json;
+use std::time::Duration;
 
 use crate::{
     characteristic::{
-        AsyncCharacteristicCallbacks,
+{{#if tlv8_value}}        tlv8::{{tlv8_value}},
+{{/if}}        AsyncCharacteristicCallbacks,
         Characteristic,
         CharacteristicCallbacks,
         Format,
@@ -818,18 +856,56 @@ use crate::{
         OnReadFuture,
         OnUpdateFn,
         OnUpdateFuture,
+        OnUpdateResponseFn,
+        OnUpdateResponseFuture,
         Perm,
-        Unit,
+{{#if enum_values}}        TypedValue,
+{{/if}}        Unit,
     },
     pointer,
-    Result,
+{{#if enum_values}}    Error,
+{{/if}}    Result,
 };
 
 // TODO - re-check MaximumDataLength
 /// {{characteristic.DefaultDescription}} characteristic.
 #[derive(Debug, Default, Serialize)]
 pub struct {{pascal_case characteristic.DefaultDescription}}Characteristic(Characteristic<{{type characteristic.Format}}>);
-{{#if in_values includeZero=true}}{{in_values_enum in_values}}{{/if}}{{#if out_values includeZero=true}}{{out_values_enum out_values}}{{/if}}
+{{#if enum_values}}
+/// Valid values of the {{characteristic.DefaultDescription}} characteristic.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum {{typed_value}} {
+{{#each enum_values}}{{#if @first}}    #[default]
+{{/if}}    {{name}} = {{value}},
+{{/each}}}
+
+impl From<{{typed_value}}> for {{type characteristic.Format}} {
+    fn from(val: {{typed_value}}) -> Self { val as {{type characteristic.Format}} }
+}
+
+impl TryFrom<{{type characteristic.Format}}> for {{typed_value}} {
+    type Error = Error;
+
+    fn try_from(val: {{type characteristic.Format}}) -> Result<Self> {
+        match val {
+{{#each enum_values}}            {{value}} => Ok(Self::{{name}}),
+{{/each}}            _ => Err(Error::ValueNotInValidValues),
+        }
+    }
+}
+
+impl TypedValue<{{type characteristic.Format}}> for {{typed_value}} {
+    fn from_value(value: &{{type characteristic.Format}}) -> Result<Self> { Self::try_from(*value) }
+
+    fn to_value(&self) -> {{type characteristic.Format}} { (*self).into() }
+}
+
+impl Serialize for {{typed_value}} {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        {{type characteristic.Format}}::from(*self).serialize(serializer)
+    }
+}
+{{/if}}
 impl {{pascal_case characteristic.DefaultDescription}}Characteristic {
     /// Creates a new {{characteristic.DefaultDescription}} characteristic.
     pub fn new(id: u64, accessory_id: u64) -> Self {
@@ -861,7 +937,31 @@ impl {{pascal_case characteristic.DefaultDescription}}Characteristic {
         c
     }
 }
+{{#if typed_value}}
+impl {{pascal_case characteristic.DefaultDescription}}Characteristic {
+    /// Returns the value of the characteristic converted to a [`{{typed_value}}`].
+    pub async fn get_typed_value(&mut self) -> Result<{{typed_value}}> {
+        self.0.get_typed_value().await
+    }
 
+    /// Sets the value of the characteristic from a [`{{typed_value}}`].
+    pub async fn set_typed_value(&mut self, val: {{typed_value}}) -> Result<()> {
+        self.0.set_typed_value(val).await
+    }
+
+    /// Sets a callback function on the characteristic that is called every time a controller attempts to read its
+    /// value, working with the typed [`{{typed_value}}`].
+    pub fn on_read_typed(&mut self, f: Option<impl OnReadFn<{{typed_value}}>>) {
+        self.0.on_read_typed(f)
+    }
+
+    /// Sets a callback function on the characteristic that is called every time a controller attempts to update its
+    /// value, working with the typed [`{{typed_value}}`].
+    pub fn on_update_typed(&mut self, f: Option<impl OnUpdateFn<{{typed_value}}>>) {
+        self.0.on_update_typed(f)
+    }
+}
+{{/if}}
 #[async_trait]
 impl HapCharacteristic for {{pascal_case characteristic.DefaultDescription}}Characteristic {
     fn get_id(&self) -> u64 { HapCharacteristic::get_id(&self.0) }
@@ -892,12 +992,22 @@ impl HapCharacteristic for {{pascal_case characteristic.DefaultDescription}}Char
         HapCharacteristic::set_event_notifications(&mut self.0, event_notifications)
     }
 
+    fn get_min_event_interval(&self) -> Option<Duration> { HapCharacteristic::get_min_event_interval(&self.0) }
+
+    fn set_min_event_interval(&mut self, min_event_interval: Option<Duration>) {
+        HapCharacteristic::set_min_event_interval(&mut self.0, min_event_interval)
+    }
+
     async fn get_value(&mut self) -> Result<serde_json::Value> { HapCharacteristic::get_value(&mut self.0).await }
 
     async fn set_value(&mut self, value: serde_json::Value) -> Result<()> {
         HapCharacteristic::set_value(&mut self.0, value).await
     }
 
+    async fn set_value_with_response(&mut self, value: serde_json::Value) -> Result<serde_json::Value> {
+        HapCharacteristic::set_value_with_response(&mut self.0, value).await
+    }
+
     fn get_unit(&self) -> Option<Unit> { HapCharacteristic::get_unit(&self.0) }
 
     fn set_unit(&mut self, unit: Option<Unit>) { HapCharacteristic::set_unit(&mut self.0, unit) }
@@ -965,6 +1075,10 @@ impl CharacteristicCallbacks<{{type characteristic.Format}}> for {{pascal_case c
     fn on_read(&mut self, f: Option<impl OnReadFn<{{type characteristic.Format}}>>) { CharacteristicCallbacks::on_read(&mut self.0, f) }
 
     fn on_update(&mut self, f: Option<impl OnUpdateFn<{{type characteristic.Format}}>>) { CharacteristicCallbacks::on_update(&mut self.0, f) }
+
+    fn on_update_with_response(&mut self, f: Option<impl OnUpdateResponseFn<{{type characteristic.Format}}>>) {
+        CharacteristicCallbacks::on_update_with_response(&mut self.0, f)
+    }
 }
 
 impl AsyncCharacteristicCallbacks<{{type characteristic.Format}}> for {{pascal_case characteristic.DefaultDescription}}Characteristic {
@@ -975,6 +1089,10 @@ impl AsyncCharacteristicCallbacks<{{type characteristic.Format}}> for {{pascal_c
     fn on_update_async(&mut self, f: Option<impl OnUpdateFuture<{{type characteristic.Format}}>>) {
         AsyncCharacteristicCallbacks::on_update_async(&mut self.0, f)
     }
+
+    fn on_update_with_response_async(&mut self, f: Option<impl OnUpdateResponseFuture<{{type characteristic.Format}}>>) {
+        AsyncCharacteristicCallbacks::on_update_with_response_async(&mut self.0, f)
+    }
 }
 ";
 
@@ -982,6 +1100,21 @@ static CHARACTERISTIC_MOD: &'static str = "// this file is auto-generated by hap
 {{#each characteristics as |c|}}\n/// {{c.name}} characteristic definition.\npub mod {{c.file_name}};{{/each}}
 ";
 
+static TLV8: &'static str = "// this file is auto-generated by hap-codegen\n
+use serde::Serialize;
+
+use crate::characteristic::tlv8::Tlv8;
+{{#each types as |t|}}
+/// {{{t.description}}}
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+{{#if t.tuple}}pub struct {{t.name}}({{#each t.fields as |f|}}#[tlv(type = {{f.type}}{{tlv8_kind f.kind}})] pub {{tlv8_type f.format f.kind}}{{/each}});
+{{else}}pub struct {{t.name}} {
+{{#each t.fields as |f|}}    /// {{{f.description}}}
+    #[tlv(type = {{f.type}}{{tlv8_kind f.kind}})]
+    pub {{f.name}}: {{tlv8_type f.format f.kind}},
+{{/each}}}
+{{/if}}{{/each}}";
+
 static SERVICE: &'static str = "// this file is auto-generated by hap-codegen\n
 use serde::ser::{Serialize, SerializeStruct, Serializer};
 
@@ -1368,7 +1501,7 @@ const MANUALLY_GENERATED_EXAMPLES: &'static [&'static str] = &[
     "television.rs",
 ];
 
-// TODO - manual overrides for valve type & media state values
+// TODO - manual overrides for media state values
 
 fn main() {
     let metadata_file = File::open("codegen/gen/system.json").unwrap();
@@ -1376,6 +1509,13 @@ fn main() {
     let metadata: SystemMetadata = serde_json::from_reader(&metadata_file).unwrap();
     let metadata = RenderMetadata::from(metadata);
 
+    let tlv8_schema_file = File::open("codegen/gen/tlv8.json").unwrap();
+    let tlv8_schema: Tlv8Schema = serde_json::from_reader(&tlv8_schema_file).unwrap();
+
+    let value_overrides_file = File::open("codegen/gen/values.json").unwrap();
+    let value_overrides: HashMap<String, HashMap<String, Value>> =
+        serde_json::from_reader(&value_overrides_file).unwrap();
+
     let mut handlebars = Handlebars::new();
     handlebars.register_helper("if_eq", Box::new(if_eq_helper));
     handlebars.register_helper("trim", Box::new(trim_helper));
@@ -1387,13 +1527,13 @@ fn main() {
     handlebars.register_helper("uuid", Box::new(uuid_helper));
     handlebars.register_helper("in_values", Box::new(in_values_helper));
     handlebars.register_helper("out_values", Box::new(out_values_helper));
-    handlebars.register_helper("in_values_enum", Box::new(in_values_enum_helper));
-    handlebars.register_helper("out_values_enum", Box::new(out_values_enum_helper));
     handlebars.register_helper("perms", Box::new(perms_helper));
     handlebars.register_helper("float", Box::new(float_helper));
     handlebars.register_helper("array_length", Box::new(array_length_helper));
     handlebars.register_helper("snake_case", Box::new(snake_case_helper));
     handlebars.register_helper("pascal_case", Box::new(pascal_case_helper));
+    handlebars.register_helper("tlv8_type", Box::new(tlv8_type_helper));
+    handlebars.register_helper("tlv8_kind", Box::new(tlv8_kind_helper));
     handlebars.register_template_string("categories", CATEGORIES).unwrap();
     handlebars.register_template_string("hap_type", HAP_TYPE).unwrap(); // PascalCase camelCase
     handlebars
@@ -1402,6 +1542,7 @@ fn main() {
     handlebars
         .register_template_string("characteristic_mod", CHARACTERISTIC_MOD)
         .unwrap();
+    handlebars.register_template_string("tlv8", TLV8).unwrap();
     handlebars.register_template_string("service", SERVICE).unwrap();
     handlebars.register_template_string("service_mod", SERVICE_MOD).unwrap();
     handlebars.register_template_string("accessory", ACCESSORY).unwrap();
@@ -1427,13 +1568,43 @@ fn main() {
     fs::create_dir_all(&characteristic_base_path).unwrap();
     let mut characteristic_names = vec![];
     for (c_name, c) in &metadata.characteristics {
-        let in_values = metadata.characteristic_in_values.get(c_name);
-        let out_values = metadata.characteristic_out_values.get(c_name);
+        // values missing from or mislabeled in the metadata are taken from the manual overrides
+        let (in_values, out_values) = match value_overrides.get(c_name) {
+            Some(values) => (Some(values), None),
+            None => (
+                metadata.characteristic_in_values.get(c_name),
+                metadata.characteristic_out_values.get(c_name),
+            ),
+        };
+        let enum_values = enum_values(in_values, out_values);
+
+        // TLV8 characteristics without a documented payload structure are typed as their raw items
+        let tlv8_value = match c.format.as_str() {
+            "tlv8" => Some(
+                tlv8_schema
+                    .characteristics
+                    .get(c_name)
+                    .cloned()
+                    .unwrap_or_else(|| "Tlv8Items".to_string()),
+            ),
+            _ => None,
+        };
+        let typed_value = match enum_values {
+            Some(_) => Some(pascal_case(&c.name)),
+            None => tlv8_value.clone(),
+        };
 
         let characteristic = handlebars
             .render(
                 "characteristic",
-                &json!({ "characteristic": c, "in_values": in_values, "out_values": out_values }),
+                &json!({
+                    "characteristic": c,
+                    "in_values": in_values,
+                    "out_values": out_values,
+                    "tlv8_value": tlv8_value,
+                    "typed_value": typed_value,
+                    "enum_values": enum_values,
+                }),
             )
             .unwrap();
 
@@ -1468,6 +1639,19 @@ fn main() {
         .write_all(characteristic_mod.as_bytes())
         .unwrap();
 
+    let tlv8_types = tlv8_schema
+        .types
+        .iter()
+        .map(|(name, t)| {
+            let mut t = serde_json::to_value(t).unwrap();
+            t.as_object_mut().unwrap().insert("name".to_string(), json!(name));
+            t
+        })
+        .collect::<Vec<Value>>();
+    let tlv8 = handlebars.render("tlv8", &json!({ "types": tlv8_types })).unwrap();
+    let mut tlv8_file = File::create("src/characteristic/tlv8/generated.rs").unwrap();
+    tlv8_file.write_all(tlv8.as_bytes()).unwrap();
+
     let service_base_path = "src/service/generated/";
     let accessory_base_path = "src/accessory/generated/";
     if std::path::Path::new(&service_base_path).exists() {
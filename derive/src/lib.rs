@@ -0,0 +1,187 @@
+//! Derive macros for the [`hap`](https://docs.rs/hap) crate.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput, Error, Expr, Lit, Member, Type};
+
+/// Derives `hap::characteristic::tlv8::Tlv8Value` for a struct, encoding every field as a TLV8 item.
+///
+/// Every field needs a `#[tlv(type = ...)]` attribute holding its TLV type, either as an integer literal or as a
+/// fieldless enum variant that can be cast to `u8`. Fields can be of any type implementing
+/// `hap::characteristic::tlv8::Tlv8Item`, i.e. integers (encoded little-endian in their width), `bool`, `f32`,
+/// `String`, `Vec<u8>` and nested payloads deriving `Tlv8` themselves.
+///
+/// - `Option<T>` fields are only encoded if they're present.
+/// - `#[tlv(type = ..., list)]` on a `Vec<T>` field encodes one item per entry, separated by empty `0x00` items.
+/// - `#[tlv(type = ..., entries)]` on a `Vec<T>` field writes the items of each nested payload inline, one after the
+///   other. The given type is the type of the first item of every entry.
+/// - `#[tlv(..., separator = ...)]` on a `list` or `entries` field separates the entries by empty items of the given
+///   type, e.g. `0xFF` for the `Separator` items of pairing payloads. Entries are only separated if a separator is
+///   given.
+///
+/// ```ignore
+/// use hap::characteristic::tlv8::Tlv8;
+///
+/// #[derive(Tlv8)]
+/// struct ButtonEvent {
+///     #[tlv(type = 0x01)]
+///     button_id: u8,
+///     #[tlv(type = 0x03)]
+///     timestamp: u64,
+///     #[tlv(type = 0x04)]
+///     active_identifier: Option<u32>,
+/// }
+/// ```
+#[proc_macro_derive(Tlv8, attributes(tlv))]
+pub fn derive_tlv8(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+enum Kind {
+    Item,
+    Optional,
+    List,
+    Entries,
+}
+
+struct Field {
+    member: Member,
+    tlv_type: TokenStream2,
+    kind: Kind,
+    separator: Option<TokenStream2>,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = match &input.data {
+        Data::Struct(data) => data
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(i, field)| parse_field(i, field))
+            .collect::<syn::Result<Vec<_>>>()?,
+        _ => return Err(Error::new(input.span(), "`Tlv8` can only be derived for structs")),
+    };
+
+    let encode = fields.iter().map(|field| {
+        let member = &field.member;
+        let tlv_type = &field.tlv_type;
+        match field.kind {
+            Kind::Item => quote! { writer.item(#tlv_type, &self.#member); },
+            Kind::Optional => quote! { writer.optional(#tlv_type, &self.#member); },
+            Kind::List => {
+                let separator = match &field.separator {
+                    Some(separator) => separator.clone(),
+                    None => quote! { ::hap::characteristic::tlv8::SEPARATOR },
+                };
+                quote! { writer.list(#tlv_type, #separator, &self.#member); }
+            },
+            Kind::Entries => {
+                let separator = optional_separator(field);
+                quote! { writer.entries(#separator, &self.#member); }
+            },
+        }
+    });
+    let decode = fields.iter().map(|field| {
+        let member = &field.member;
+        let tlv_type = &field.tlv_type;
+        match field.kind {
+            Kind::Item => quote! { #member: reader.item(#tlv_type)? },
+            Kind::Optional => quote! { #member: reader.optional(#tlv_type)? },
+            Kind::List => quote! { #member: reader.list(#tlv_type)? },
+            Kind::Entries => {
+                let separator = optional_separator(field);
+                quote! { #member: reader.entries(#tlv_type, #separator)? }
+            },
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::hap::characteristic::tlv8::Tlv8Value for #name #ty_generics #where_clause {
+            fn encode(&self) -> ::std::vec::Vec<u8> {
+                let mut writer = ::hap::characteristic::tlv8::Tlv8Writer::new();
+                #(#encode)*
+                writer.into_bytes()
+            }
+
+            fn decode(bytes: ::hap::bytes::Bytes) -> ::hap::Result<Self> {
+                let reader = ::hap::characteristic::tlv8::Tlv8Reader::new(bytes)?;
+                Ok(Self { #(#decode,)* })
+            }
+        }
+    })
+}
+
+fn parse_field(index: usize, field: &syn::Field) -> syn::Result<Field> {
+    let member = match &field.ident {
+        Some(ident) => Member::Named(ident.clone()),
+        None => Member::Unnamed(index.into()),
+    };
+
+    let mut tlv_type = None;
+    let mut kind = if is_option(&field.ty) { Kind::Optional } else { Kind::Item };
+    let mut separator = None;
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("tlv")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("type") {
+                tlv_type = Some(parse_tlv_type(meta.value()?.parse()?)?);
+            } else if meta.path.is_ident("list") {
+                kind = Kind::List;
+            } else if meta.path.is_ident("entries") {
+                kind = Kind::Entries;
+            } else if meta.path.is_ident("separator") {
+                separator = Some(parse_tlv_type(meta.value()?.parse()?)?);
+            } else {
+                return Err(meta.error("expected `type`, `list`, `entries` or `separator`"));
+            }
+            Ok(())
+        })?;
+    }
+
+    let tlv_type =
+        tlv_type.ok_or_else(|| Error::new(field.span(), "missing TLV type, e.g. `#[tlv(type = 0x01)]`"))?;
+    if separator.is_some() && !matches!(kind, Kind::List | Kind::Entries) {
+        return Err(Error::new(field.span(), "a separator can only be given for `list` or `entries` fields"));
+    }
+
+    Ok(Field {
+        member,
+        tlv_type,
+        kind,
+        separator,
+    })
+}
+
+/// Converts the separator of an `entries` field to an `Option<u8>` expression.
+fn optional_separator(field: &Field) -> TokenStream2 {
+    match &field.separator {
+        Some(separator) => quote! { ::std::option::Option::Some(#separator) },
+        None => quote! { ::std::option::Option::None },
+    }
+}
+
+/// Converts the TLV type of a field to a `u8` expression. Integer literals are range-checked at expansion time.
+fn parse_tlv_type(expr: Expr) -> syn::Result<TokenStream2> {
+    match &expr {
+        Expr::Lit(lit) => match &lit.lit {
+            Lit::Int(int) => {
+                let tlv_type = int.base10_parse::<u8>()?;
+                Ok(quote! { #tlv_type })
+            },
+            _ => Err(Error::new(expr.span(), "expected an integer literal or an enum variant")),
+        },
+        _ => Ok(quote! { #expr as u8 }),
+    }
+}
+
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path.path.segments.last().map(|s| s.ident == "Option").unwrap_or(false),
+        _ => false,
+    }
+}
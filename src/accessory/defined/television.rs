@@ -35,9 +35,6 @@ impl TelevisionAccessory {
         let mut television = TelevisionService::new(television_id, id);
         television.set_primary(true);
 
-        // TODO - figure out how to auto-set reasonable default values for tlv8 characteristics
-        television.display_order = None;
-
         let speaker_id = 3 + television_id + television.get_characteristics().len() as u64;
         let speaker = SpeakerService::new(speaker_id, id);
 
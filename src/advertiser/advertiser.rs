@@ -0,0 +1,34 @@
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use std::net::IpAddr;
+
+use crate::Result;
+
+/// A service registration announcing the accessory server on the network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceRecord {
+    /// Instance name of the service. This is the name of the accessory.
+    pub name: String,
+    /// Type of the service, e.g. `_hap._tcp`.
+    pub service_type: String,
+    /// Port the accessory server is serving on.
+    pub port: u16,
+    /// TXT records of the service in `<key>=<value>` form.
+    pub txt_records: Vec<String>,
+}
+
+/// [`Advertiser`](Advertiser) is implemented by the service discovery methods HAP supports. Currently, that's
+/// [`MdnsAdvertiser`](crate::advertiser::MdnsAdvertiser), which is the default,
+/// [`AvahiAdvertiser`](crate::advertiser::AvahiAdvertiser) and
+/// [`MemoryAdvertiser`](crate::advertiser::MemoryAdvertiser).
+#[async_trait]
+pub trait Advertiser: Send {
+    /// Starts the [`Advertiser`](Advertiser) on the network interfaces of the given IPs, or on all interfaces if there
+    /// are none or one of them is unspecified. Returns a task driving the advertiser that has to be thrown on a
+    /// scheduler and finishes once the advertiser is stopped. Calling it on a started advertiser restarts it.
+    async fn start(&mut self, hosts: &[IpAddr]) -> Result<BoxFuture<'static, ()>>;
+    /// Registers a [`ServiceRecord`](ServiceRecord), replacing a previously registered one.
+    async fn register(&mut self, record: &ServiceRecord) -> Result<()>;
+    /// Unregisters the current [`ServiceRecord`](ServiceRecord) and stops the [`Advertiser`](Advertiser).
+    async fn stop(&mut self) -> Result<()>;
+}
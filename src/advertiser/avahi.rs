@@ -0,0 +1,132 @@
+use async_trait::async_trait;
+use futures::future::{self, BoxFuture, FutureExt};
+use log::debug;
+use std::{
+    fs,
+    io::ErrorKind,
+    net::IpAddr,
+    path::{Path, PathBuf},
+};
+use tokio::task::spawn_blocking;
+
+use crate::{
+    advertiser::{Advertiser, ServiceRecord},
+    Result,
+};
+
+/// [`AvahiAdvertiser`](AvahiAdvertiser) is an implementor of the [`Advertiser`](Advertiser) trait that hands the
+/// service registration to a running avahi daemon by writing a static service file, usually to `/etc/avahi/services`.
+/// The daemon picks changes to the file up by itself and announces the service on all interfaces it's configured for.
+#[derive(Debug)]
+pub struct AvahiAdvertiser {
+    file_path: PathBuf,
+}
+
+impl AvahiAdvertiser {
+    /// Creates a new [`AvahiAdvertiser`](AvahiAdvertiser) writing to the service file at the given path, e.g.
+    /// `/etc/avahi/services/hap.service`.
+    pub fn new<P: AsRef<Path>>(file_path: P) -> Self {
+        AvahiAdvertiser {
+            file_path: file_path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+#[async_trait]
+impl Advertiser for AvahiAdvertiser {
+    async fn start(&mut self, hosts: &[IpAddr]) -> Result<BoxFuture<'static, ()>> {
+        debug!("avahi announces on the interfaces it's configured for; ignoring the hosts {:?}", hosts);
+
+        Ok(future::ready(()).boxed())
+    }
+
+    async fn register(&mut self, record: &ServiceRecord) -> Result<()> {
+        let file_path = self.file_path.clone();
+        let service_file = service_file(record);
+
+        debug!("writing avahi service file to {:?}", &file_path);
+
+        spawn_blocking(move || -> Result<()> {
+            // write to a temporary file first so the daemon never reads a partially written one
+            let tmp_path = file_path.with_extension("tmp");
+            fs::write(&tmp_path, service_file)?;
+            fs::rename(&tmp_path, &file_path)?;
+
+            Ok(())
+        })
+        .await?
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        let file_path = self.file_path.clone();
+
+        debug!("removing avahi service file {:?}", &file_path);
+
+        spawn_blocking(move || -> Result<()> {
+            match fs::remove_file(&file_path) {
+                Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            }
+        })
+        .await?
+    }
+}
+
+/// Renders a [`ServiceRecord`](ServiceRecord) as an avahi service file.
+fn service_file(record: &ServiceRecord) -> String {
+    let mut service_file = String::new();
+    service_file.push_str("<?xml version=\"1.0\" standalone='no'?>\n");
+    service_file.push_str("<!DOCTYPE service-group SYSTEM \"avahi-service.dtd\">\n");
+    service_file.push_str("<service-group>\n");
+    service_file.push_str(&format!("  <name>{}</name>\n", escape(&record.name)));
+    service_file.push_str("  <service>\n");
+    service_file.push_str(&format!("    <type>{}</type>\n", escape(&record.service_type)));
+    service_file.push_str(&format!("    <port>{}</port>\n", record.port));
+    for txt_record in &record.txt_records {
+        service_file.push_str(&format!("    <txt-record>{}</txt-record>\n", escape(txt_record)));
+    }
+    service_file.push_str("  </service>\n");
+    service_file.push_str("</service-group>\n");
+
+    service_file
+}
+
+/// Escapes the XML special characters of a string.
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_service_file() {
+        let record = ServiceRecord {
+            name: "Acme <Lightbulb> & Co".into(),
+            service_type: "_hap._tcp".into(),
+            port: 32000,
+            txt_records: vec!["c#=1".into(), "md=Acme <Lightbulb> & Co".into()],
+        };
+
+        assert_eq!(
+            service_file(&record),
+            "<?xml version=\"1.0\" standalone='no'?>
+<!DOCTYPE service-group SYSTEM \"avahi-service.dtd\">
+<service-group>
+  <name>Acme &lt;Lightbulb&gt; &amp; Co</name>
+  <service>
+    <type>_hap._tcp</type>
+    <port>32000</port>
+    <txt-record>c#=1</txt-record>
+    <txt-record>md=Acme &lt;Lightbulb&gt; &amp; Co</txt-record>
+  </service>
+</service-group>
+"
+        );
+    }
+}
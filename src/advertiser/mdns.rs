@@ -0,0 +1,104 @@
+use async_trait::async_trait;
+use futures::future::{BoxFuture, FutureExt};
+use libmdns::{Responder, Service};
+use log::debug;
+use std::net::IpAddr;
+
+use crate::{
+    advertiser::{Advertiser, ServiceRecord},
+    Result,
+};
+
+/// [`MdnsAdvertiser`](MdnsAdvertiser) is an implementor of the [`Advertiser`](Advertiser) trait that runs a built-in
+/// mDNS responder. This requires port 5353 to be available, so it doesn't work alongside a system mDNS daemon like
+/// avahi that doesn't share the port.
+#[derive(Default)]
+pub struct MdnsAdvertiser {
+    responder: Option<Responder>,
+    service: Option<Service>,
+}
+
+impl MdnsAdvertiser {
+    /// Creates a new [`MdnsAdvertiser`](MdnsAdvertiser).
+    pub fn new() -> Self { MdnsAdvertiser::default() }
+}
+
+#[async_trait]
+impl Advertiser for MdnsAdvertiser {
+    async fn start(&mut self, hosts: &[IpAddr]) -> Result<BoxFuture<'static, ()>> {
+        // dropping a previous responder finishes its task
+        self.service = None;
+        self.responder = None;
+
+        let (responder, task) = if hosts.is_empty() || hosts.iter().any(|host| host.is_unspecified()) {
+            Responder::with_default_handle()?
+        } else {
+            Responder::with_default_handle_and_ip_list(hosts.to_vec())?
+        };
+        self.responder = Some(responder);
+
+        Ok(task.boxed())
+    }
+
+    async fn register(&mut self, record: &ServiceRecord) -> Result<()> {
+        self.service = None;
+
+        match self.responder {
+            Some(ref responder) => {
+                let txt_records = record.txt_records.iter().map(|r| r.as_str()).collect::<Vec<&str>>();
+                self.service = Some(responder.register(
+                    record.service_type.clone(),
+                    record.name.clone(),
+                    record.port,
+                    &txt_records,
+                ));
+            },
+            None => debug!("mDNS responder isn't running; not registering the service"),
+        }
+
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        // dropping the service sends goodbye packets to the network
+        self.service = None;
+        self.responder = None;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::Ipv4Addr;
+
+    fn record() -> ServiceRecord {
+        ServiceRecord {
+            name: "Acme Lightbulb".into(),
+            service_type: "_hap._tcp".into(),
+            port: 32000,
+            txt_records: vec!["c#=1".into(), "md=Acme Lightbulb".into()],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mdns_advertiser() {
+        let mut advertiser = MdnsAdvertiser::new();
+
+        // registering before the responder is started is a no-op
+        advertiser.register(&record()).await.unwrap();
+        assert!(advertiser.service.is_none());
+
+        let task = advertiser.start(&[IpAddr::V4(Ipv4Addr::LOCALHOST)]).await.unwrap();
+        let task = tokio::spawn(task);
+        advertiser.register(&record()).await.unwrap();
+        assert!(advertiser.service.is_some());
+
+        // dropping the responder finishes its task
+        advertiser.stop().await.unwrap();
+        assert!(advertiser.responder.is_none());
+        task.await.unwrap();
+    }
+}
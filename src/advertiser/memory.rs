@@ -0,0 +1,53 @@
+use async_trait::async_trait;
+use futures::future::{self, BoxFuture, FutureExt};
+use std::{
+    net::IpAddr,
+    sync::{Arc, RwLock},
+};
+
+use crate::{
+    advertiser::{Advertiser, ServiceRecord},
+    Result,
+};
+
+/// [`MemoryAdvertiser`](MemoryAdvertiser) is an implementor of the [`Advertiser`](Advertiser) trait that doesn't
+/// announce anything on the network and only keeps the registered [`ServiceRecord`](ServiceRecord) in memory. Useful
+/// for tests or for running a server that's reached by other means. Clones share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryAdvertiser {
+    hosts: Arc<RwLock<Option<Vec<IpAddr>>>>,
+    record: Arc<RwLock<Option<ServiceRecord>>>,
+}
+
+impl MemoryAdvertiser {
+    /// Creates a new [`MemoryAdvertiser`](MemoryAdvertiser).
+    pub fn new() -> Self { MemoryAdvertiser::default() }
+
+    /// Returns the IPs the [`MemoryAdvertiser`](MemoryAdvertiser) was started on, or `None` if it isn't running.
+    pub fn hosts(&self) -> Option<Vec<IpAddr>> { self.hosts.read().expect("reading hosts").clone() }
+
+    /// Returns the currently registered [`ServiceRecord`](ServiceRecord).
+    pub fn record(&self) -> Option<ServiceRecord> { self.record.read().expect("reading service record").clone() }
+}
+
+#[async_trait]
+impl Advertiser for MemoryAdvertiser {
+    async fn start(&mut self, hosts: &[IpAddr]) -> Result<BoxFuture<'static, ()>> {
+        *self.hosts.write().expect("writing hosts") = Some(hosts.to_vec());
+
+        Ok(future::ready(()).boxed())
+    }
+
+    async fn register(&mut self, record: &ServiceRecord) -> Result<()> {
+        *self.record.write().expect("writing service record") = Some(record.clone());
+
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        *self.hosts.write().expect("writing hosts") = None;
+        *self.record.write().expect("writing service record") = None;
+
+        Ok(())
+    }
+}
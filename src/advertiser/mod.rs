@@ -0,0 +1,11 @@
+mod advertiser;
+mod avahi;
+mod mdns;
+mod memory;
+
+pub use self::{
+    advertiser::{Advertiser, ServiceRecord},
+    avahi::AvahiAdvertiser,
+    mdns::MdnsAdvertiser,
+    memory::MemoryAdvertiser,
+};
@@ -0,0 +1,21 @@
+use async_trait::async_trait;
+
+use crate::{BonjourFeatureFlag, Result};
+
+/// [`Authenticator`](Authenticator) is implemented by the means an accessory can prove its authenticity to controllers
+/// with during pair setup, e.g. an MFi authentication coprocessor or a software token. The crate ships with
+/// [`FileAuthenticator`](crate::authenticator::FileAuthenticator), which reads a software token and a signing key from
+/// files. Other implementations, like one talking to a coprocessor, can be plugged in via
+/// [`Server::set_authenticator`](crate::server::Server::set_authenticator).
+#[async_trait]
+pub trait Authenticator: Send {
+    /// Returns the Bonjour Feature Flag announcing the kind of authentication the
+    /// [`Authenticator`](Authenticator) performs, i.e. `SupportsHardwareAuthentication` or
+    /// `SupportsSoftwareAuthentication`.
+    fn feature_flag(&self) -> BonjourFeatureFlag;
+    /// Returns the certificate or token identifying the accessory, which is sent to the controller along with the
+    /// signature.
+    async fn certificate(&mut self) -> Result<Vec<u8>>;
+    /// Signs a challenge derived from the pair setup session.
+    async fn sign(&mut self, challenge: &[u8]) -> Result<Vec<u8>>;
+}
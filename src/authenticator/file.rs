@@ -0,0 +1,83 @@
+use async_trait::async_trait;
+use ed25519_dalek::Keypair;
+use signature::Signer;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tokio::task::spawn_blocking;
+
+use crate::{authenticator::Authenticator, BonjourFeatureFlag, Error, Result};
+
+/// [`FileAuthenticator`](FileAuthenticator) is an implementor of the [`Authenticator`](Authenticator) trait that
+/// authenticates the accessory with a software token read from a file. Challenges are signed with an Ed25519 keypair,
+/// stored as its 64 raw bytes in a second file. Both files are read on every pair setup, so they can be replaced
+/// while the server is running.
+#[derive(Debug)]
+pub struct FileAuthenticator {
+    token_path: PathBuf,
+    keypair_path: PathBuf,
+}
+
+impl FileAuthenticator {
+    /// Creates a new [`FileAuthenticator`](FileAuthenticator) reading the token and the keypair from the given paths.
+    pub fn new<P: AsRef<Path>, Q: AsRef<Path>>(token_path: P, keypair_path: Q) -> Self {
+        FileAuthenticator {
+            token_path: token_path.as_ref().to_path_buf(),
+            keypair_path: keypair_path.as_ref().to_path_buf(),
+        }
+    }
+
+    async fn read(path: &Path) -> Result<Vec<u8>> {
+        let path = path.to_path_buf();
+        spawn_blocking(move || -> Result<Vec<u8>> { Ok(fs::read(path)?) }).await?
+    }
+}
+
+#[async_trait]
+impl Authenticator for FileAuthenticator {
+    fn feature_flag(&self) -> BonjourFeatureFlag { BonjourFeatureFlag::SupportsSoftwareAuthentication }
+
+    async fn certificate(&mut self) -> Result<Vec<u8>> { FileAuthenticator::read(&self.token_path).await }
+
+    async fn sign(&mut self, challenge: &[u8]) -> Result<Vec<u8>> {
+        let keypair_bytes = FileAuthenticator::read(&self.keypair_path).await?;
+        let keypair = Keypair::from_bytes(&keypair_bytes).map_err(|_| Error::InvalidAuthenticationKey)?;
+
+        Ok(keypair.sign(challenge).to_bytes().to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::rngs::OsRng;
+    use signature::Verifier;
+
+    #[tokio::test]
+    async fn test_file_authenticator() {
+        let mut temp_dir = std::env::temp_dir();
+        temp_dir.push("hap-file-authenticator");
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        let token_path = temp_dir.join("token");
+        let keypair_path = temp_dir.join("keypair");
+
+        let mut csprng = OsRng {};
+        let keypair = Keypair::generate(&mut csprng);
+        fs::write(&token_path, b"token").unwrap();
+        fs::write(&keypair_path, keypair.to_bytes()).unwrap();
+
+        let mut authenticator = FileAuthenticator::new(&token_path, &keypair_path);
+        assert_eq!(authenticator.certificate().await.unwrap(), b"token".to_vec());
+
+        let signature = authenticator.sign(b"challenge").await.unwrap();
+        let signature = ed25519_dalek::Signature::from_bytes(&signature).unwrap();
+        assert!(keypair.public.verify(b"challenge", &signature).is_ok());
+
+        // an invalid keypair file is refused
+        fs::write(&keypair_path, b"invalid").unwrap();
+        assert!(matches!(authenticator.sign(b"challenge").await, Err(Error::InvalidAuthenticationKey)));
+    }
+}
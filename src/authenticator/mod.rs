@@ -0,0 +1,4 @@
+mod authenticator;
+mod file;
+
+pub use self::{authenticator::Authenticator, file::FileAuthenticator};
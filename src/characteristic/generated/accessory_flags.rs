@@ -3,6 +3,7 @@
 use async_trait::async_trait;
 use serde::Serialize;
 use serde_json::json;
+use std::time::Duration;
 
 use crate::{
     characteristic::{
@@ -17,6 +18,8 @@ use crate::{
         OnReadFuture,
         OnUpdateFn,
         OnUpdateFuture,
+        OnUpdateResponseFn,
+        OnUpdateResponseFuture,
         Perm,
         Unit,
     },
@@ -87,12 +90,22 @@ impl HapCharacteristic for AccessoryFlagsCharacteristic {
         HapCharacteristic::set_event_notifications(&mut self.0, event_notifications)
     }
 
+    fn get_min_event_interval(&self) -> Option<Duration> { HapCharacteristic::get_min_event_interval(&self.0) }
+
+    fn set_min_event_interval(&mut self, min_event_interval: Option<Duration>) {
+        HapCharacteristic::set_min_event_interval(&mut self.0, min_event_interval)
+    }
+
     async fn get_value(&mut self) -> Result<serde_json::Value> { HapCharacteristic::get_value(&mut self.0).await }
 
     async fn set_value(&mut self, value: serde_json::Value) -> Result<()> {
         HapCharacteristic::set_value(&mut self.0, value).await
     }
 
+    async fn set_value_with_response(&mut self, value: serde_json::Value) -> Result<serde_json::Value> {
+        HapCharacteristic::set_value_with_response(&mut self.0, value).await
+    }
+
     fn get_unit(&self) -> Option<Unit> { HapCharacteristic::get_unit(&self.0) }
 
     fn set_unit(&mut self, unit: Option<Unit>) { HapCharacteristic::set_unit(&mut self.0, unit) }
@@ -160,6 +173,10 @@ impl CharacteristicCallbacks<u32> for AccessoryFlagsCharacteristic {
     fn on_read(&mut self, f: Option<impl OnReadFn<u32>>) { CharacteristicCallbacks::on_read(&mut self.0, f) }
 
     fn on_update(&mut self, f: Option<impl OnUpdateFn<u32>>) { CharacteristicCallbacks::on_update(&mut self.0, f) }
+
+    fn on_update_with_response(&mut self, f: Option<impl OnUpdateResponseFn<u32>>) {
+        CharacteristicCallbacks::on_update_with_response(&mut self.0, f)
+    }
 }
 
 impl AsyncCharacteristicCallbacks<u32> for AccessoryFlagsCharacteristic {
@@ -170,4 +187,8 @@ impl AsyncCharacteristicCallbacks<u32> for AccessoryFlagsCharacteristic {
     fn on_update_async(&mut self, f: Option<impl OnUpdateFuture<u32>>) {
         AsyncCharacteristicCallbacks::on_update_async(&mut self.0, f)
     }
+
+    fn on_update_with_response_async(&mut self, f: Option<impl OnUpdateResponseFuture<u32>>) {
+        AsyncCharacteristicCallbacks::on_update_with_response_async(&mut self.0, f)
+    }
 }
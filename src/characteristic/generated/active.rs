@@ -1,8 +1,9 @@
 // this file is auto-generated by hap-codegen
 
 use async_trait::async_trait;
-use serde::Serialize;
+use serde::{ser::Serializer, Serialize};
 use serde_json::json;
+use std::time::Duration;
 
 use crate::{
     characteristic::{
@@ -17,10 +18,14 @@ use crate::{
         OnReadFuture,
         OnUpdateFn,
         OnUpdateFuture,
+        OnUpdateResponseFn,
+        OnUpdateResponseFuture,
         Perm,
+        TypedValue,
         Unit,
     },
     pointer,
+    Error,
     Result,
 };
 
@@ -29,6 +34,42 @@ use crate::{
 #[derive(Debug, Default, Serialize)]
 pub struct ActiveCharacteristic(Characteristic<u8>);
 
+/// Valid values of the Active characteristic.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Active {
+    #[default]
+    Inactive = 0,
+    Active = 1,
+}
+
+impl From<Active> for u8 {
+    fn from(val: Active) -> Self { val as u8 }
+}
+
+impl TryFrom<u8> for Active {
+    type Error = Error;
+
+    fn try_from(val: u8) -> Result<Self> {
+        match val {
+            0 => Ok(Self::Inactive),
+            1 => Ok(Self::Active),
+            _ => Err(Error::ValueNotInValidValues),
+        }
+    }
+}
+
+impl TypedValue<u8> for Active {
+    fn from_value(value: &u8) -> Result<Self> { Self::try_from(*value) }
+
+    fn to_value(&self) -> u8 { (*self).into() }
+}
+
+impl Serialize for Active {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        u8::from(*self).serialize(serializer)
+    }
+}
+
 impl ActiveCharacteristic {
     /// Creates a new Active characteristic.
     pub fn new(id: u64, accessory_id: u64) -> Self {
@@ -46,6 +87,10 @@ impl ActiveCharacteristic {
 			max_value: Some(1),
 			min_value: Some(0),
 			step_value: Some(1),
+			valid_values: Some(vec![
+				0, // INACTIVE
+				1, // ACTIVE
+			]),
             ..Default::default()
         });
 
@@ -61,6 +106,30 @@ impl ActiveCharacteristic {
     }
 }
 
+impl ActiveCharacteristic {
+    /// Returns the value of the characteristic converted to a [`Active`].
+    pub async fn get_typed_value(&mut self) -> Result<Active> {
+        self.0.get_typed_value().await
+    }
+
+    /// Sets the value of the characteristic from a [`Active`].
+    pub async fn set_typed_value(&mut self, val: Active) -> Result<()> {
+        self.0.set_typed_value(val).await
+    }
+
+    /// Sets a callback function on the characteristic that is called every time a controller attempts to read its
+    /// value, working with the typed [`Active`].
+    pub fn on_read_typed(&mut self, f: Option<impl OnReadFn<Active>>) {
+        self.0.on_read_typed(f)
+    }
+
+    /// Sets a callback function on the characteristic that is called every time a controller attempts to update its
+    /// value, working with the typed [`Active`].
+    pub fn on_update_typed(&mut self, f: Option<impl OnUpdateFn<Active>>) {
+        self.0.on_update_typed(f)
+    }
+}
+
 #[async_trait]
 impl HapCharacteristic for ActiveCharacteristic {
     fn get_id(&self) -> u64 { HapCharacteristic::get_id(&self.0) }
@@ -91,12 +160,22 @@ impl HapCharacteristic for ActiveCharacteristic {
         HapCharacteristic::set_event_notifications(&mut self.0, event_notifications)
     }
 
+    fn get_min_event_interval(&self) -> Option<Duration> { HapCharacteristic::get_min_event_interval(&self.0) }
+
+    fn set_min_event_interval(&mut self, min_event_interval: Option<Duration>) {
+        HapCharacteristic::set_min_event_interval(&mut self.0, min_event_interval)
+    }
+
     async fn get_value(&mut self) -> Result<serde_json::Value> { HapCharacteristic::get_value(&mut self.0).await }
 
     async fn set_value(&mut self, value: serde_json::Value) -> Result<()> {
         HapCharacteristic::set_value(&mut self.0, value).await
     }
 
+    async fn set_value_with_response(&mut self, value: serde_json::Value) -> Result<serde_json::Value> {
+        HapCharacteristic::set_value_with_response(&mut self.0, value).await
+    }
+
     fn get_unit(&self) -> Option<Unit> { HapCharacteristic::get_unit(&self.0) }
 
     fn set_unit(&mut self, unit: Option<Unit>) { HapCharacteristic::set_unit(&mut self.0, unit) }
@@ -164,6 +243,10 @@ impl CharacteristicCallbacks<u8> for ActiveCharacteristic {
     fn on_read(&mut self, f: Option<impl OnReadFn<u8>>) { CharacteristicCallbacks::on_read(&mut self.0, f) }
 
     fn on_update(&mut self, f: Option<impl OnUpdateFn<u8>>) { CharacteristicCallbacks::on_update(&mut self.0, f) }
+
+    fn on_update_with_response(&mut self, f: Option<impl OnUpdateResponseFn<u8>>) {
+        CharacteristicCallbacks::on_update_with_response(&mut self.0, f)
+    }
 }
 
 impl AsyncCharacteristicCallbacks<u8> for ActiveCharacteristic {
@@ -174,4 +257,8 @@ impl AsyncCharacteristicCallbacks<u8> for ActiveCharacteristic {
     fn on_update_async(&mut self, f: Option<impl OnUpdateFuture<u8>>) {
         AsyncCharacteristicCallbacks::on_update_async(&mut self.0, f)
     }
+
+    fn on_update_with_response_async(&mut self, f: Option<impl OnUpdateResponseFuture<u8>>) {
+        AsyncCharacteristicCallbacks::on_update_with_response_async(&mut self.0, f)
+    }
 }
@@ -3,9 +3,11 @@
 use async_trait::async_trait;
 use serde::Serialize;
 use serde_json::json;
+use std::time::Duration;
 
 use crate::{
     characteristic::{
+        tlv8::CharacteristicValueTransitionControl,
         AsyncCharacteristicCallbacks,
         Characteristic,
         CharacteristicCallbacks,
@@ -17,6 +19,8 @@ use crate::{
         OnReadFuture,
         OnUpdateFn,
         OnUpdateFuture,
+        OnUpdateResponseFn,
+        OnUpdateResponseFuture,
         Perm,
         Unit,
     },
@@ -57,6 +61,30 @@ impl CharacteristicValueTransitionControlCharacteristic {
     }
 }
 
+impl CharacteristicValueTransitionControlCharacteristic {
+    /// Returns the value of the characteristic converted to a [`CharacteristicValueTransitionControl`].
+    pub async fn get_typed_value(&mut self) -> Result<CharacteristicValueTransitionControl> {
+        self.0.get_typed_value().await
+    }
+
+    /// Sets the value of the characteristic from a [`CharacteristicValueTransitionControl`].
+    pub async fn set_typed_value(&mut self, val: CharacteristicValueTransitionControl) -> Result<()> {
+        self.0.set_typed_value(val).await
+    }
+
+    /// Sets a callback function on the characteristic that is called every time a controller attempts to read its
+    /// value, working with the typed [`CharacteristicValueTransitionControl`].
+    pub fn on_read_typed(&mut self, f: Option<impl OnReadFn<CharacteristicValueTransitionControl>>) {
+        self.0.on_read_typed(f)
+    }
+
+    /// Sets a callback function on the characteristic that is called every time a controller attempts to update its
+    /// value, working with the typed [`CharacteristicValueTransitionControl`].
+    pub fn on_update_typed(&mut self, f: Option<impl OnUpdateFn<CharacteristicValueTransitionControl>>) {
+        self.0.on_update_typed(f)
+    }
+}
+
 #[async_trait]
 impl HapCharacteristic for CharacteristicValueTransitionControlCharacteristic {
     fn get_id(&self) -> u64 { HapCharacteristic::get_id(&self.0) }
@@ -87,12 +115,22 @@ impl HapCharacteristic for CharacteristicValueTransitionControlCharacteristic {
         HapCharacteristic::set_event_notifications(&mut self.0, event_notifications)
     }
 
+    fn get_min_event_interval(&self) -> Option<Duration> { HapCharacteristic::get_min_event_interval(&self.0) }
+
+    fn set_min_event_interval(&mut self, min_event_interval: Option<Duration>) {
+        HapCharacteristic::set_min_event_interval(&mut self.0, min_event_interval)
+    }
+
     async fn get_value(&mut self) -> Result<serde_json::Value> { HapCharacteristic::get_value(&mut self.0).await }
 
     async fn set_value(&mut self, value: serde_json::Value) -> Result<()> {
         HapCharacteristic::set_value(&mut self.0, value).await
     }
 
+    async fn set_value_with_response(&mut self, value: serde_json::Value) -> Result<serde_json::Value> {
+        HapCharacteristic::set_value_with_response(&mut self.0, value).await
+    }
+
     fn get_unit(&self) -> Option<Unit> { HapCharacteristic::get_unit(&self.0) }
 
     fn set_unit(&mut self, unit: Option<Unit>) { HapCharacteristic::set_unit(&mut self.0, unit) }
@@ -160,6 +198,10 @@ impl CharacteristicCallbacks<Vec<u8>> for CharacteristicValueTransitionControlCh
     fn on_read(&mut self, f: Option<impl OnReadFn<Vec<u8>>>) { CharacteristicCallbacks::on_read(&mut self.0, f) }
 
     fn on_update(&mut self, f: Option<impl OnUpdateFn<Vec<u8>>>) { CharacteristicCallbacks::on_update(&mut self.0, f) }
+
+    fn on_update_with_response(&mut self, f: Option<impl OnUpdateResponseFn<Vec<u8>>>) {
+        CharacteristicCallbacks::on_update_with_response(&mut self.0, f)
+    }
 }
 
 impl AsyncCharacteristicCallbacks<Vec<u8>> for CharacteristicValueTransitionControlCharacteristic {
@@ -170,4 +212,8 @@ impl AsyncCharacteristicCallbacks<Vec<u8>> for CharacteristicValueTransitionCont
     fn on_update_async(&mut self, f: Option<impl OnUpdateFuture<Vec<u8>>>) {
         AsyncCharacteristicCallbacks::on_update_async(&mut self.0, f)
     }
+
+    fn on_update_with_response_async(&mut self, f: Option<impl OnUpdateResponseFuture<Vec<u8>>>) {
+        AsyncCharacteristicCallbacks::on_update_with_response_async(&mut self.0, f)
+    }
 }
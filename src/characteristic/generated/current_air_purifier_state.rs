@@ -1,8 +1,9 @@
 // this file is auto-generated by hap-codegen
 
 use async_trait::async_trait;
-use serde::Serialize;
+use serde::{ser::Serializer, Serialize};
 use serde_json::json;
+use std::time::Duration;
 
 use crate::{
     characteristic::{
@@ -17,10 +18,14 @@ use crate::{
         OnReadFuture,
         OnUpdateFn,
         OnUpdateFuture,
+        OnUpdateResponseFn,
+        OnUpdateResponseFuture,
         Perm,
+        TypedValue,
         Unit,
     },
     pointer,
+    Error,
     Result,
 };
 
@@ -29,10 +34,42 @@ use crate::{
 #[derive(Debug, Default, Serialize)]
 pub struct CurrentAirPurifierStateCharacteristic(Characteristic<u8>);
 
-pub enum Value {
-	Inactive = 0,
-	Idle = 1,
-	PurifyingAir = 2,
+/// Valid values of the Current Air Purifier State characteristic.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CurrentAirPurifierState {
+    #[default]
+    Inactive = 0,
+    Idle = 1,
+    PurifyingAir = 2,
+}
+
+impl From<CurrentAirPurifierState> for u8 {
+    fn from(val: CurrentAirPurifierState) -> Self { val as u8 }
+}
+
+impl TryFrom<u8> for CurrentAirPurifierState {
+    type Error = Error;
+
+    fn try_from(val: u8) -> Result<Self> {
+        match val {
+            0 => Ok(Self::Inactive),
+            1 => Ok(Self::Idle),
+            2 => Ok(Self::PurifyingAir),
+            _ => Err(Error::ValueNotInValidValues),
+        }
+    }
+}
+
+impl TypedValue<u8> for CurrentAirPurifierState {
+    fn from_value(value: &u8) -> Result<Self> { Self::try_from(*value) }
+
+    fn to_value(&self) -> u8 { (*self).into() }
+}
+
+impl Serialize for CurrentAirPurifierState {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        u8::from(*self).serialize(serializer)
+    }
 }
 
 impl CurrentAirPurifierStateCharacteristic {
@@ -71,6 +108,30 @@ impl CurrentAirPurifierStateCharacteristic {
     }
 }
 
+impl CurrentAirPurifierStateCharacteristic {
+    /// Returns the value of the characteristic converted to a [`CurrentAirPurifierState`].
+    pub async fn get_typed_value(&mut self) -> Result<CurrentAirPurifierState> {
+        self.0.get_typed_value().await
+    }
+
+    /// Sets the value of the characteristic from a [`CurrentAirPurifierState`].
+    pub async fn set_typed_value(&mut self, val: CurrentAirPurifierState) -> Result<()> {
+        self.0.set_typed_value(val).await
+    }
+
+    /// Sets a callback function on the characteristic that is called every time a controller attempts to read its
+    /// value, working with the typed [`CurrentAirPurifierState`].
+    pub fn on_read_typed(&mut self, f: Option<impl OnReadFn<CurrentAirPurifierState>>) {
+        self.0.on_read_typed(f)
+    }
+
+    /// Sets a callback function on the characteristic that is called every time a controller attempts to update its
+    /// value, working with the typed [`CurrentAirPurifierState`].
+    pub fn on_update_typed(&mut self, f: Option<impl OnUpdateFn<CurrentAirPurifierState>>) {
+        self.0.on_update_typed(f)
+    }
+}
+
 #[async_trait]
 impl HapCharacteristic for CurrentAirPurifierStateCharacteristic {
     fn get_id(&self) -> u64 { HapCharacteristic::get_id(&self.0) }
@@ -101,12 +162,22 @@ impl HapCharacteristic for CurrentAirPurifierStateCharacteristic {
         HapCharacteristic::set_event_notifications(&mut self.0, event_notifications)
     }
 
+    fn get_min_event_interval(&self) -> Option<Duration> { HapCharacteristic::get_min_event_interval(&self.0) }
+
+    fn set_min_event_interval(&mut self, min_event_interval: Option<Duration>) {
+        HapCharacteristic::set_min_event_interval(&mut self.0, min_event_interval)
+    }
+
     async fn get_value(&mut self) -> Result<serde_json::Value> { HapCharacteristic::get_value(&mut self.0).await }
 
     async fn set_value(&mut self, value: serde_json::Value) -> Result<()> {
         HapCharacteristic::set_value(&mut self.0, value).await
     }
 
+    async fn set_value_with_response(&mut self, value: serde_json::Value) -> Result<serde_json::Value> {
+        HapCharacteristic::set_value_with_response(&mut self.0, value).await
+    }
+
     fn get_unit(&self) -> Option<Unit> { HapCharacteristic::get_unit(&self.0) }
 
     fn set_unit(&mut self, unit: Option<Unit>) { HapCharacteristic::set_unit(&mut self.0, unit) }
@@ -174,6 +245,10 @@ impl CharacteristicCallbacks<u8> for CurrentAirPurifierStateCharacteristic {
     fn on_read(&mut self, f: Option<impl OnReadFn<u8>>) { CharacteristicCallbacks::on_read(&mut self.0, f) }
 
     fn on_update(&mut self, f: Option<impl OnUpdateFn<u8>>) { CharacteristicCallbacks::on_update(&mut self.0, f) }
+
+    fn on_update_with_response(&mut self, f: Option<impl OnUpdateResponseFn<u8>>) {
+        CharacteristicCallbacks::on_update_with_response(&mut self.0, f)
+    }
 }
 
 impl AsyncCharacteristicCallbacks<u8> for CurrentAirPurifierStateCharacteristic {
@@ -184,4 +259,8 @@ impl AsyncCharacteristicCallbacks<u8> for CurrentAirPurifierStateCharacteristic
     fn on_update_async(&mut self, f: Option<impl OnUpdateFuture<u8>>) {
         AsyncCharacteristicCallbacks::on_update_async(&mut self.0, f)
     }
+
+    fn on_update_with_response_async(&mut self, f: Option<impl OnUpdateResponseFuture<u8>>) {
+        AsyncCharacteristicCallbacks::on_update_with_response_async(&mut self.0, f)
+    }
 }
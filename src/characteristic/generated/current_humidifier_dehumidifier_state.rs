@@ -1,8 +1,9 @@
 // this file is auto-generated by hap-codegen
 
 use async_trait::async_trait;
-use serde::Serialize;
+use serde::{ser::Serializer, Serialize};
 use serde_json::json;
+use std::time::Duration;
 
 use crate::{
     characteristic::{
@@ -17,10 +18,14 @@ use crate::{
         OnReadFuture,
         OnUpdateFn,
         OnUpdateFuture,
+        OnUpdateResponseFn,
+        OnUpdateResponseFuture,
         Perm,
+        TypedValue,
         Unit,
     },
     pointer,
+    Error,
     Result,
 };
 
@@ -29,11 +34,44 @@ use crate::{
 #[derive(Debug, Default, Serialize)]
 pub struct CurrentHumidifierDehumidifierStateCharacteristic(Characteristic<u8>);
 
-pub enum Value {
-	Inactive = 0,
-	Idle = 1,
-	Humidifying = 2,
-	Dehumidifying = 3,
+/// Valid values of the Current Humidifier-Dehumidifier State characteristic.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CurrentHumidifierDehumidifierState {
+    #[default]
+    Inactive = 0,
+    Idle = 1,
+    Humidifying = 2,
+    Dehumidifying = 3,
+}
+
+impl From<CurrentHumidifierDehumidifierState> for u8 {
+    fn from(val: CurrentHumidifierDehumidifierState) -> Self { val as u8 }
+}
+
+impl TryFrom<u8> for CurrentHumidifierDehumidifierState {
+    type Error = Error;
+
+    fn try_from(val: u8) -> Result<Self> {
+        match val {
+            0 => Ok(Self::Inactive),
+            1 => Ok(Self::Idle),
+            2 => Ok(Self::Humidifying),
+            3 => Ok(Self::Dehumidifying),
+            _ => Err(Error::ValueNotInValidValues),
+        }
+    }
+}
+
+impl TypedValue<u8> for CurrentHumidifierDehumidifierState {
+    fn from_value(value: &u8) -> Result<Self> { Self::try_from(*value) }
+
+    fn to_value(&self) -> u8 { (*self).into() }
+}
+
+impl Serialize for CurrentHumidifierDehumidifierState {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        u8::from(*self).serialize(serializer)
+    }
 }
 
 impl CurrentHumidifierDehumidifierStateCharacteristic {
@@ -73,6 +111,30 @@ impl CurrentHumidifierDehumidifierStateCharacteristic {
     }
 }
 
+impl CurrentHumidifierDehumidifierStateCharacteristic {
+    /// Returns the value of the characteristic converted to a [`CurrentHumidifierDehumidifierState`].
+    pub async fn get_typed_value(&mut self) -> Result<CurrentHumidifierDehumidifierState> {
+        self.0.get_typed_value().await
+    }
+
+    /// Sets the value of the characteristic from a [`CurrentHumidifierDehumidifierState`].
+    pub async fn set_typed_value(&mut self, val: CurrentHumidifierDehumidifierState) -> Result<()> {
+        self.0.set_typed_value(val).await
+    }
+
+    /// Sets a callback function on the characteristic that is called every time a controller attempts to read its
+    /// value, working with the typed [`CurrentHumidifierDehumidifierState`].
+    pub fn on_read_typed(&mut self, f: Option<impl OnReadFn<CurrentHumidifierDehumidifierState>>) {
+        self.0.on_read_typed(f)
+    }
+
+    /// Sets a callback function on the characteristic that is called every time a controller attempts to update its
+    /// value, working with the typed [`CurrentHumidifierDehumidifierState`].
+    pub fn on_update_typed(&mut self, f: Option<impl OnUpdateFn<CurrentHumidifierDehumidifierState>>) {
+        self.0.on_update_typed(f)
+    }
+}
+
 #[async_trait]
 impl HapCharacteristic for CurrentHumidifierDehumidifierStateCharacteristic {
     fn get_id(&self) -> u64 { HapCharacteristic::get_id(&self.0) }
@@ -103,12 +165,22 @@ impl HapCharacteristic for CurrentHumidifierDehumidifierStateCharacteristic {
         HapCharacteristic::set_event_notifications(&mut self.0, event_notifications)
     }
 
+    fn get_min_event_interval(&self) -> Option<Duration> { HapCharacteristic::get_min_event_interval(&self.0) }
+
+    fn set_min_event_interval(&mut self, min_event_interval: Option<Duration>) {
+        HapCharacteristic::set_min_event_interval(&mut self.0, min_event_interval)
+    }
+
     async fn get_value(&mut self) -> Result<serde_json::Value> { HapCharacteristic::get_value(&mut self.0).await }
 
     async fn set_value(&mut self, value: serde_json::Value) -> Result<()> {
         HapCharacteristic::set_value(&mut self.0, value).await
     }
 
+    async fn set_value_with_response(&mut self, value: serde_json::Value) -> Result<serde_json::Value> {
+        HapCharacteristic::set_value_with_response(&mut self.0, value).await
+    }
+
     fn get_unit(&self) -> Option<Unit> { HapCharacteristic::get_unit(&self.0) }
 
     fn set_unit(&mut self, unit: Option<Unit>) { HapCharacteristic::set_unit(&mut self.0, unit) }
@@ -176,6 +248,10 @@ impl CharacteristicCallbacks<u8> for CurrentHumidifierDehumidifierStateCharacter
     fn on_read(&mut self, f: Option<impl OnReadFn<u8>>) { CharacteristicCallbacks::on_read(&mut self.0, f) }
 
     fn on_update(&mut self, f: Option<impl OnUpdateFn<u8>>) { CharacteristicCallbacks::on_update(&mut self.0, f) }
+
+    fn on_update_with_response(&mut self, f: Option<impl OnUpdateResponseFn<u8>>) {
+        CharacteristicCallbacks::on_update_with_response(&mut self.0, f)
+    }
 }
 
 impl AsyncCharacteristicCallbacks<u8> for CurrentHumidifierDehumidifierStateCharacteristic {
@@ -186,4 +262,8 @@ impl AsyncCharacteristicCallbacks<u8> for CurrentHumidifierDehumidifierStateChar
     fn on_update_async(&mut self, f: Option<impl OnUpdateFuture<u8>>) {
         AsyncCharacteristicCallbacks::on_update_async(&mut self.0, f)
     }
+
+    fn on_update_with_response_async(&mut self, f: Option<impl OnUpdateResponseFuture<u8>>) {
+        AsyncCharacteristicCallbacks::on_update_with_response_async(&mut self.0, f)
+    }
 }
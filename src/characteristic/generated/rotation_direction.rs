@@ -1,8 +1,9 @@
 // this file is auto-generated by hap-codegen
 
 use async_trait::async_trait;
-use serde::Serialize;
+use serde::{ser::Serializer, Serialize};
 use serde_json::json;
+use std::time::Duration;
 
 use crate::{
     characteristic::{
@@ -17,10 +18,14 @@ use crate::{
         OnReadFuture,
         OnUpdateFn,
         OnUpdateFuture,
+        OnUpdateResponseFn,
+        OnUpdateResponseFuture,
         Perm,
+        TypedValue,
         Unit,
     },
     pointer,
+    Error,
     Result,
 };
 
@@ -29,9 +34,40 @@ use crate::{
 #[derive(Debug, Default, Serialize)]
 pub struct RotationDirectionCharacteristic(Characteristic<i32>);
 
-pub enum Value {
-	Clockwise = 0,
-	Counterclockwise = 1,
+/// Valid values of the Rotation Direction characteristic.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RotationDirection {
+    #[default]
+    Clockwise = 0,
+    Counterclockwise = 1,
+}
+
+impl From<RotationDirection> for i32 {
+    fn from(val: RotationDirection) -> Self { val as i32 }
+}
+
+impl TryFrom<i32> for RotationDirection {
+    type Error = Error;
+
+    fn try_from(val: i32) -> Result<Self> {
+        match val {
+            0 => Ok(Self::Clockwise),
+            1 => Ok(Self::Counterclockwise),
+            _ => Err(Error::ValueNotInValidValues),
+        }
+    }
+}
+
+impl TypedValue<i32> for RotationDirection {
+    fn from_value(value: &i32) -> Result<Self> { Self::try_from(*value) }
+
+    fn to_value(&self) -> i32 { (*self).into() }
+}
+
+impl Serialize for RotationDirection {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        i32::from(*self).serialize(serializer)
+    }
 }
 
 impl RotationDirectionCharacteristic {
@@ -70,6 +106,30 @@ impl RotationDirectionCharacteristic {
     }
 }
 
+impl RotationDirectionCharacteristic {
+    /// Returns the value of the characteristic converted to a [`RotationDirection`].
+    pub async fn get_typed_value(&mut self) -> Result<RotationDirection> {
+        self.0.get_typed_value().await
+    }
+
+    /// Sets the value of the characteristic from a [`RotationDirection`].
+    pub async fn set_typed_value(&mut self, val: RotationDirection) -> Result<()> {
+        self.0.set_typed_value(val).await
+    }
+
+    /// Sets a callback function on the characteristic that is called every time a controller attempts to read its
+    /// value, working with the typed [`RotationDirection`].
+    pub fn on_read_typed(&mut self, f: Option<impl OnReadFn<RotationDirection>>) {
+        self.0.on_read_typed(f)
+    }
+
+    /// Sets a callback function on the characteristic that is called every time a controller attempts to update its
+    /// value, working with the typed [`RotationDirection`].
+    pub fn on_update_typed(&mut self, f: Option<impl OnUpdateFn<RotationDirection>>) {
+        self.0.on_update_typed(f)
+    }
+}
+
 #[async_trait]
 impl HapCharacteristic for RotationDirectionCharacteristic {
     fn get_id(&self) -> u64 { HapCharacteristic::get_id(&self.0) }
@@ -100,12 +160,22 @@ impl HapCharacteristic for RotationDirectionCharacteristic {
         HapCharacteristic::set_event_notifications(&mut self.0, event_notifications)
     }
 
+    fn get_min_event_interval(&self) -> Option<Duration> { HapCharacteristic::get_min_event_interval(&self.0) }
+
+    fn set_min_event_interval(&mut self, min_event_interval: Option<Duration>) {
+        HapCharacteristic::set_min_event_interval(&mut self.0, min_event_interval)
+    }
+
     async fn get_value(&mut self) -> Result<serde_json::Value> { HapCharacteristic::get_value(&mut self.0).await }
 
     async fn set_value(&mut self, value: serde_json::Value) -> Result<()> {
         HapCharacteristic::set_value(&mut self.0, value).await
     }
 
+    async fn set_value_with_response(&mut self, value: serde_json::Value) -> Result<serde_json::Value> {
+        HapCharacteristic::set_value_with_response(&mut self.0, value).await
+    }
+
     fn get_unit(&self) -> Option<Unit> { HapCharacteristic::get_unit(&self.0) }
 
     fn set_unit(&mut self, unit: Option<Unit>) { HapCharacteristic::set_unit(&mut self.0, unit) }
@@ -173,6 +243,10 @@ impl CharacteristicCallbacks<i32> for RotationDirectionCharacteristic {
     fn on_read(&mut self, f: Option<impl OnReadFn<i32>>) { CharacteristicCallbacks::on_read(&mut self.0, f) }
 
     fn on_update(&mut self, f: Option<impl OnUpdateFn<i32>>) { CharacteristicCallbacks::on_update(&mut self.0, f) }
+
+    fn on_update_with_response(&mut self, f: Option<impl OnUpdateResponseFn<i32>>) {
+        CharacteristicCallbacks::on_update_with_response(&mut self.0, f)
+    }
 }
 
 impl AsyncCharacteristicCallbacks<i32> for RotationDirectionCharacteristic {
@@ -183,4 +257,8 @@ impl AsyncCharacteristicCallbacks<i32> for RotationDirectionCharacteristic {
     fn on_update_async(&mut self, f: Option<impl OnUpdateFuture<i32>>) {
         AsyncCharacteristicCallbacks::on_update_async(&mut self.0, f)
     }
+
+    fn on_update_with_response_async(&mut self, f: Option<impl OnUpdateResponseFuture<i32>>) {
+        AsyncCharacteristicCallbacks::on_update_with_response_async(&mut self.0, f)
+    }
 }
@@ -1,20 +1,33 @@
 use async_trait::async_trait;
+use bytes::Bytes;
 use erased_serde::serialize_trait_object;
-use futures::future::BoxFuture;
+use futures::future::{BoxFuture, FutureExt};
 use serde::{
     ser::{SerializeStruct, Serializer},
     Deserialize,
     Serialize,
 };
 use serde_json::json;
-use std::fmt;
+use std::{
+    fmt,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+use tokio::{runtime::Handle, time::sleep_until};
 
 use crate::{event::Event, pointer, Error, HapType, Result};
 
 mod generated;
 
+pub mod tlv8;
+
 pub use generated::*;
 
+/// Maximum number of characters of a `string` characteristic value if `max_len` isn't set.
+const DEFAULT_MAX_LEN: u16 = 64;
+/// Maximum number of bytes of a `data` characteristic value if `max_data_len` isn't set.
+const DEFAULT_MAX_DATA_LEN: u32 = 2097152;
+
 /// A characteristic. A characteristic is a feature that represents data or an associated behavior of a service. The
 /// characteristic is defined by a universally unique type, and has additional properties that determine how the value
 /// of the characteristic can be accessed.
@@ -33,8 +46,13 @@ pub struct Characteristic<T: fmt::Debug + Default + Clone + Serialize + Send + S
     /// String describing the characteristic on a manufacturer-specific basis, such as an indoor versus outdoor
     /// temperature reading.
     description: Option<String>,
-    /// Boolean indicating if event notifications are enabled for this characteristic.
+    /// Boolean indicating if event notifications are enabled for this characteristic. Controllers subscribe to event
+    /// notifications individually, so the server reports the subscription state of the requesting controller instead
+    /// for characteristics with the `Events` permission.
     event_notifications: Option<bool>,
+    /// Minimum interval between the event notifications of the characteristic. Value changes within the interval are
+    /// held back, and only the latest one of them is emitted once the interval has passed.
+    min_event_interval: Option<Duration>,
 
     /// The value of the characteristic, which must conform to the `format` property. This property must be present if
     /// and only if the characteristic contains the Paired Read permission.
@@ -44,32 +62,33 @@ pub struct Characteristic<T: fmt::Debug + Default + Clone + Serialize + Send + S
 
     /// Maximum value for the characteristic, which is only appropriate for characteristics that have a format of `Int`
     /// or `Float`.
-    max_value: Option<T>, // TODO - use this value in `set_value`
+    max_value: Option<T>,
     // Minimum value for the characteristic, which is only appropriate for characteristics that have a format of `Int`
     // or `Float`.
-    min_value: Option<T>, // TODO - use this value in `set_value`
+    min_value: Option<T>,
     /// Minimum step value for the characteristic, which is only appropriate for characteristics that have a format of
     /// ”int” or ”float”. For example, if this were 0.15, the characteris- tic value can be incremented from the min-
     /// imum value in multiples of 0.15. For “float”, the “Value” needs to be rounded on the ac- cessory side to the
     /// closest allowed value per the ”Step Value” (e.g. a value of 10.150001 received on the accessory side with a
     /// ”Step Value” of 0.15 and a ”Minimum Value” of 10.0 needs to be interpreted as 10.15).
-    step_value: Option<T>, // TODO - use this value in `set_value`
+    step_value: Option<T>,
     /// Maximum number of characters if the for- mat is ”string”. If this property is omitted for ”string” formats,
     /// then the default value is 64. The maximum value allowed is 256.
-    max_len: Option<u16>, // TODO - use this value in `set_value`
+    max_len: Option<u16>,
     /// Maximum number of characters if the format is ”data”. If this property is omitted for ”data” formats, then the
     /// default value is 2097152.
-    max_data_len: Option<u32>, // TODO - use this value in `set_value`
+    max_data_len: Option<u32>,
     /// An array of numbers where each element represents a valid value.
-    valid_values: Option<Vec<T>>, // TODO - use this value in `set_value`
+    valid_values: Option<Vec<T>>,
     /// A 2 element array representing the starting value and ending value of the range of valid values.
-    valid_values_range: Option<[T; 2]>, // TODO - use this value in `set_value`
+    valid_values_range: Option<[T; 2]>,
 
     /// Specified TTL in milliseconds the controller requests the accessory to securely execute a write command.
-    /// Maximum value of this is 9007199254740991.
-    ttl: Option<u64>, // TODO - use this value in `set_value`
+    /// Maximum value of this is 9007199254740991. Timed write transactions themselves are tracked per connection by
+    /// the server via `PUT /prepare`.
+    ttl: Option<u64>,
     /// 64-bit unsigned integer assigned by the controller to uniquely identify the timed write transaction.
-    pid: Option<u64>, // TODO - use this value in `set_value`
+    pid: Option<u64>,
 
     /// Sets a callback function on a characteristic that is called every time a controller attempts to read its value.
     /// Returning a `Some(T)` from this function changes the value of the characteristic before the controller reads
@@ -77,8 +96,9 @@ pub struct Characteristic<T: fmt::Debug + Default + Clone + Serialize + Send + S
     on_read: Option<Box<dyn OnReadFn<T>>>,
     /// Sets a callback function on a characteristic that is called every time a controller attempts to update its
     /// value. The first argument is a reference to the current value of the characteristic and the second argument is
-    /// a reference to the value the controller attempts to change the characteristic's to.
-    on_update: Option<Box<dyn OnUpdateFn<T>>>,
+    /// a reference to the value the controller attempts to change the characteristic's to. Returning a `Some(T)` from
+    /// this function sets the payload of a write response.
+    on_update: Option<Box<dyn OnUpdateResponseFn<T>>>,
     /// Sets an async callback function on a characteristic that is driven to completion by the async runtime driving
     /// the HAP server every time a controller attempts to read its value. Returning a `Some(T)` from this function
     /// changes the value of the characteristic before the controller reads it so the controller reads the new value.
@@ -86,10 +106,18 @@ pub struct Characteristic<T: fmt::Debug + Default + Clone + Serialize + Send + S
     /// Sets an async callback function on a characteristic that is driven to completion by the async runtime driving
     /// the HAP server every time a controller attempts to update its value. The first argument is a reference to the
     /// current value of the characteristic and the second argument is a reference to the value the controller attempts
-    /// to change the characteristic's to.
-    on_update_async: Option<Box<dyn OnUpdateFuture<T>>>,
+    /// to change the characteristic's to. Returning a `Some(T)` from this function sets the payload of a write response.
+    on_update_async: Option<Box<dyn OnUpdateResponseFuture<T>>>,
 
     event_emitter: Option<pointer::EventEmitter>,
+    event_throttle: Arc<Mutex<EventThrottle>>,
+}
+
+/// State of the event notifications of a characteristic with a minimum event interval.
+#[derive(Debug, Default)]
+struct EventThrottle {
+    last_emitted: Option<Instant>,
+    pending: Option<Event>,
 }
 
 impl<T: fmt::Debug + Default + Clone + Serialize + Send + Sync> fmt::Debug for Characteristic<T> {
@@ -102,6 +130,7 @@ impl<T: fmt::Debug + Default + Clone + Serialize + Send + Sync> fmt::Debug for C
             .field("perms", &self.perms)
             .field("description", &self.description)
             .field("event_notifications", &self.event_notifications)
+            .field("min_event_interval", &self.min_event_interval)
             .field("value", &self.value)
             .field("unit", &self.unit)
             .field("max_value", &self.max_value)
@@ -150,6 +179,7 @@ where
             perms,
             description,
             event_notifications,
+            min_event_interval: None,
             value,
             unit,
             max_value,
@@ -166,6 +196,7 @@ where
             on_read_async: None,
             on_update_async: None,
             event_emitter: None,
+            event_throttle: Arc::default(),
         }
     }
 
@@ -207,6 +238,15 @@ where
         self.event_notifications = event_notifications;
     }
 
+    /// Returns the minimum interval between the event notifications of the characteristic.
+    pub fn get_min_event_interval(&self) -> Option<Duration> { self.min_event_interval }
+
+    /// Sets the minimum interval between the event notifications of the characteristic, e.g. to keep a chatty sensor
+    /// from flooding controllers with notifications.
+    pub fn set_min_event_interval(&mut self, min_event_interval: Option<Duration>) {
+        self.min_event_interval = min_event_interval;
+    }
+
     /// Returns the value of the characteristic.
     pub async fn get_value(&mut self) -> Result<T> {
         let mut val = None;
@@ -224,46 +264,156 @@ where
     }
 
     /// Sets the value of the characteristic.
+    ///
+    /// The value is validated against the metadata of the characteristic before it's passed to the `on_update`
+    /// callbacks. Numeric values have to be within `min_value`, `max_value`, `valid_values` and `valid_values_range`,
+    /// float values are rounded to the closest multiple of `step_value` (relative to `min_value`), and string and data
+    /// values have to respect `max_len` and `max_data_len`.
     pub async fn set_value(&mut self, val: T) -> Result<()> {
-        // TODO - check for min/max on types implementing PartialOrd
-        // if let Some(ref max) = self.inner.try_borrow()?.max_value {
-        //     if &val > max {
-        //         return Err(Error::from_str("value above max_value"));
-        //     }
-        // }
-        // if let Some(ref min) = self.inner.try_borrow()?.min_value {
-        //     if &val < min {
-        //         return Err(Error::from_str("value below min_value"));
-        //     }
-        // }
+        self.set_value_with_response(val).await?;
+
+        Ok(())
+    }
+
+    /// Sets the value of the characteristic and returns the payload of the write response requested by a controller.
+    /// The payload is the value returned by the `on_update` callbacks or, if none of them returned one, the new value
+    /// of the characteristic.
+    pub async fn set_value_with_response(&mut self, val: T) -> Result<T> {
+        let val = self.validate_value(val)?;
 
         let old_val = self.value.clone();
+        let mut response = None;
         if let Some(ref mut on_update) = self.on_update {
-            on_update(&old_val, &val).map_err(|e| Error::ValueOnUpdate(e))?;
+            if let Some(r) = on_update(&old_val, &val).map_err(|e| Error::ValueOnUpdate(e))? {
+                response = Some(r);
+            }
         }
         if let Some(ref mut on_update_async) = self.on_update_async {
-            on_update_async(old_val, val.clone())
+            if let Some(r) = on_update_async(old_val, val.clone())
                 .await
-                .map_err(|e| Error::ValueOnUpdate(e))?;
+                .map_err(|e| Error::ValueOnUpdate(e))?
+            {
+                response = Some(r);
+            }
         }
 
-        if self.event_notifications == Some(true) {
+        // value changes are emitted regardless of subscriptions, which are checked per connection
+        if self.perms.contains(&Perm::Events) {
             if let Some(ref event_emitter) = self.event_emitter {
-                event_emitter
-                    .lock()
-                    .await
-                    .emit(&Event::CharacteristicValueChanged {
-                        aid: self.accessory_id,
-                        iid: self.id,
-                        value: json!(&val),
-                    })
-                    .await;
+                let event = Event::CharacteristicValueChanged {
+                    aid: self.accessory_id,
+                    iid: self.id,
+                    value: value_to_json(self.format, &val),
+                };
+                match self.min_event_interval {
+                    Some(min_event_interval) =>
+                        emit_throttled(&self.event_throttle, event_emitter, event, min_event_interval),
+                    None => event_emitter.emit(event),
+                }
             }
         }
 
+        let response = response.unwrap_or_else(|| val.clone());
         self.value = val;
 
-        Ok(())
+        Ok(response)
+    }
+
+    /// Returns the value of the characteristic converted to a typed representation like a TLV8 payload struct.
+    pub async fn get_typed_value<V: TypedValue<T>>(&mut self) -> Result<V> { V::from_value(&self.get_value().await?) }
+
+    /// Sets the value of the characteristic from a typed representation like a TLV8 payload struct.
+    pub async fn set_typed_value<V: TypedValue<T>>(&mut self, val: V) -> Result<()> {
+        self.set_value(val.to_value()).await
+    }
+
+    /// Converts a JSON value sent by a controller to a value of the characteristic's type.
+    fn value_from_json(&self, value: serde_json::Value) -> Result<T> {
+        match self.format {
+            // for whatever reason, the controller is setting boolean values either as a boolean or as an integer
+            Format::Bool if value.is_number() => {
+                let num_v: u8 = serde_json::from_value(value)?;
+                if num_v == 0 {
+                    Ok(serde_json::from_value(json!(false))?)
+                } else if num_v == 1 {
+                    Ok(serde_json::from_value(json!(true))?)
+                } else {
+                    Err(Error::InvalidValue(self.format))
+                }
+            },
+            // controllers send `data` and `tlv8` values as Base64 strings
+            Format::Tlv8 | Format::Data if value.is_string() => {
+                let bytes = base64::decode(value.as_str().unwrap_or_default())
+                    .map_err(|_| Error::InvalidValue(self.format))?;
+                serde_json::from_value(json!(bytes)).map_err(|_| Error::InvalidValue(self.format))
+            },
+            _ => serde_json::from_value(value).map_err(|_| Error::InvalidValue(self.format)),
+        }
+    }
+
+    /// Checks a value against the metadata of the characteristic and returns the value that should be stored.
+    fn validate_value(&self, val: T) -> Result<T> {
+        match self.format {
+            Format::UInt8 | Format::UInt16 | Format::UInt32 | Format::UInt64 | Format::Int32 | Format::Float => {
+                let mut val = val;
+                let mut num = json!(&val).as_f64().ok_or(Error::InvalidValue(self.format))?;
+                let min = self.min_value.as_ref().and_then(|v| json!(v).as_f64());
+                let max = self.max_value.as_ref().and_then(|v| json!(v).as_f64());
+
+                if self.format == Format::Float {
+                    if let Some(step) = self.step_value.as_ref().and_then(|v| json!(v).as_f64()) {
+                        if step > 0.0 {
+                            let base = min.unwrap_or(0.0);
+                            num = base + ((num - base) / step).round() * step;
+                            val = serde_json::from_value(json!(num)).map_err(|_| Error::InvalidValue(self.format))?;
+                        }
+                    }
+                }
+
+                if let Some(min) = min {
+                    if num < min {
+                        return Err(Error::ValueBelowMinValue);
+                    }
+                }
+                if let Some(max) = max {
+                    if num > max {
+                        return Err(Error::ValueAboveMaxValue);
+                    }
+                }
+                if let Some(ref valid_values) = self.valid_values {
+                    if !valid_values.iter().any(|v| json!(v).as_f64() == Some(num)) {
+                        return Err(Error::ValueNotInValidValues);
+                    }
+                }
+                if let Some([ref start, ref end]) = self.valid_values_range {
+                    let start = json!(start).as_f64();
+                    let end = json!(end).as_f64();
+                    if start.map(|s| num < s).unwrap_or(false) || end.map(|e| num > e).unwrap_or(false) {
+                        return Err(Error::ValueOutsideValidValuesRange);
+                    }
+                }
+
+                Ok(val)
+            },
+            Format::String => {
+                let len = json!(&val).as_str().map(|s| s.chars().count()).unwrap_or(0);
+                if len > self.max_len.unwrap_or(DEFAULT_MAX_LEN) as usize {
+                    return Err(Error::ValueAboveMaxLen);
+                }
+
+                Ok(val)
+            },
+            Format::Tlv8 | Format::Data => {
+                // values are stored decoded, so this checks the decoded size rather than the Base64 length
+                let len = json!(&val).as_array().map(|a| a.len()).unwrap_or(0);
+                if len > self.max_data_len.unwrap_or(DEFAULT_MAX_DATA_LEN) as usize {
+                    return Err(Error::ValueAboveMaxDataLen);
+                }
+
+                Ok(val)
+            },
+            Format::Bool => Ok(val),
+        }
     }
 
     /// Returns the [`Unit`](Unit) of the characteristic.
@@ -337,7 +487,18 @@ where
     /// value. The first argument is a reference to the current value of the characteristic and the second argument is a
     /// reference to the value the controller attempts to change the characteristic's to.
     pub fn on_update(&mut self, f: Option<impl OnUpdateFn<T>>) {
-        self.on_update = f.map(|f| Box::new(f) as Box<dyn OnUpdateFn<T>>);
+        self.on_update = f.map(|mut f| {
+            Box::new(move |old_val: &T, new_val: &T| f(old_val, new_val).map(|_| None)) as Box<dyn OnUpdateResponseFn<T>>
+        });
+    }
+
+    /// Sets a callback function on a characteristic that is called every time a controller attempts to update its
+    /// value. The first argument is a reference to the current value of the characteristic and the second argument is a
+    /// reference to the value the controller attempts to change the characteristic's to. Returning a `Some(T)` from
+    /// this function sets the payload of a write response, which lets control point characteristics answer with a
+    /// value that differs from the stored one.
+    pub fn on_update_with_response(&mut self, f: Option<impl OnUpdateResponseFn<T>>) {
+        self.on_update = f.map(|f| Box::new(f) as Box<dyn OnUpdateResponseFn<T>>);
     }
 
     /// Sets an async callback function on a characteristic that is driven to completion by the async runtime driving
@@ -352,7 +513,42 @@ where
     /// current value of the characteristic and the second argument is a reference to the value the controller attempts
     /// to change the characteristic's to.
     pub fn on_update_async(&mut self, f: Option<impl OnUpdateFuture<T>>) {
-        self.on_update_async = f.map(|f| Box::new(f) as Box<dyn OnUpdateFuture<T>>);
+        self.on_update_async = f.map(|mut f| {
+            Box::new(move |old_val: T, new_val: T| {
+                let fut = f(old_val, new_val);
+                async move { fut.await.map(|_| None) }.boxed()
+            }) as Box<dyn OnUpdateResponseFuture<T>>
+        });
+    }
+
+    /// Sets an async callback function on a characteristic that is driven to completion by the async runtime driving
+    /// the HAP server every time a controller attempts to update its value. The first argument is a reference to the
+    /// current value of the characteristic and the second argument is a reference to the value the controller attempts
+    /// to change the characteristic's to. Returning a `Some(T)` from this function sets the payload of a write
+    /// response, which lets control point characteristics answer with a value that differs from the stored one.
+    pub fn on_update_with_response_async(&mut self, f: Option<impl OnUpdateResponseFuture<T>>) {
+        self.on_update_async = f.map(|f| Box::new(f) as Box<dyn OnUpdateResponseFuture<T>>);
+    }
+
+    /// Sets a callback function on a characteristic that is called every time a controller attempts to read its value,
+    /// working with a typed representation of the value like a TLV8 payload struct.
+    pub fn on_read_typed<V: TypedValue<T> + Default + Clone + Serialize + Send + Sync>(
+        &mut self,
+        f: Option<impl OnReadFn<V>>,
+    ) {
+        self.on_read(f.map(|f| move || Ok(f()?.map(|val: V| val.to_value()))));
+    }
+
+    /// Sets a callback function on a characteristic that is called every time a controller attempts to update its
+    /// value, working with a typed representation of the value like a TLV8 payload struct. If the current value can't
+    /// be converted, e.g. because it was never set, the default of the typed representation is passed instead.
+    pub fn on_update_typed<V: TypedValue<T> + Default + Clone + Serialize + Send + Sync>(
+        &mut self,
+        f: Option<impl OnUpdateFn<V>>,
+    ) {
+        self.on_update(f.map(|mut f| {
+            move |old_val: &T, new_val: &T| f(&V::from_value(old_val).unwrap_or_default(), &V::from_value(new_val)?)
+        }));
     }
 
     /// Sets a pointer to an `EventEmitter` on the characteristic.
@@ -376,7 +572,7 @@ impl<T: fmt::Debug + Default + Clone + Serialize + Send + Sync> Serialize for Ch
         }
 
         if self.perms.contains(&Perm::PairedRead) {
-            state.serialize_field("value", &self.value)?;
+            state.serialize_field("value", &value_to_json(self.format, &self.value))?;
         }
         if let Some(ref unit) = self.unit {
             state.serialize_field("unit", unit)?;
@@ -412,6 +608,54 @@ impl<T: fmt::Debug + Default + Clone + Serialize + Send + Sync> Serialize for Ch
     }
 }
 
+/// Emits an event of a characteristic with a minimum event interval. If the last event was emitted less than the
+/// interval ago, the event is held back until the interval has passed, replacing any event held back before. Outside of
+/// a tokio runtime, events can't be held back and are emitted right away.
+fn emit_throttled(
+    event_throttle: &Arc<Mutex<EventThrottle>>,
+    event_emitter: &pointer::EventEmitter,
+    event: Event,
+    min_event_interval: Duration,
+) {
+    let mut throttle = event_throttle.lock().unwrap_or_else(PoisonError::into_inner);
+    let now = Instant::now();
+    let runtime = Handle::try_current();
+    match (throttle.last_emitted, runtime) {
+        (Some(last_emitted), Ok(runtime)) if now < last_emitted + min_event_interval => {
+            // the first held back event schedules the emission, later ones just replace it
+            if throttle.pending.replace(event).is_none() {
+                let event_throttle = event_throttle.clone();
+                let event_emitter = event_emitter.clone();
+                runtime.spawn(async move {
+                    sleep_until((last_emitted + min_event_interval).into()).await;
+                    let mut throttle = event_throttle.lock().unwrap_or_else(PoisonError::into_inner);
+                    throttle.last_emitted = Some(Instant::now());
+                    if let Some(event) = throttle.pending.take() {
+                        event_emitter.emit(event);
+                    }
+                });
+            }
+        },
+        _ => {
+            throttle.last_emitted = Some(now);
+            event_emitter.emit(event);
+        },
+    }
+}
+
+/// Converts a characteristic value to the JSON value sent to controllers. `data` and `tlv8` values are encoded as
+/// Base64 strings.
+fn value_to_json<T: Serialize>(format: Format, val: &T) -> serde_json::Value {
+    let value = json!(val);
+    match format {
+        Format::Tlv8 | Format::Data => match serde_json::from_value::<Vec<u8>>(value.clone()) {
+            Ok(bytes) => json!(base64::encode(bytes)),
+            Err(_) => value,
+        },
+        _ => value,
+    }
+}
+
 /// Permission of a characteristic.
 #[derive(Debug, Copy, Clone, Serialize, PartialEq)]
 pub enum Perm {
@@ -508,10 +752,16 @@ pub trait HapCharacteristic: HapCharacteristicSetup + erased_serde::Serialize +
     fn get_event_notifications(&self) -> Option<bool>;
     /// Sets the `event_notifications` value of the characteristic.
     fn set_event_notifications(&mut self, event_notifications: Option<bool>);
+    /// Returns the minimum interval between the event notifications of the characteristic.
+    fn get_min_event_interval(&self) -> Option<Duration>;
+    /// Sets the minimum interval between the event notifications of the characteristic.
+    fn set_min_event_interval(&mut self, min_event_interval: Option<Duration>);
     /// Returns the value of the characteristic.
     async fn get_value(&mut self) -> Result<serde_json::Value>;
     /// Sets the value of the characteristic.
     async fn set_value(&mut self, value: serde_json::Value) -> Result<()>;
+    /// Sets the value of the characteristic and returns the payload of the write response requested by a controller.
+    async fn set_value_with_response(&mut self, value: serde_json::Value) -> Result<serde_json::Value>;
     /// Returns the [`Unit`](Unit) of the characteristic.
     fn get_unit(&self) -> Option<Unit>;
     /// Sets the [`Unit`](Unit) of the characteristic.
@@ -563,6 +813,21 @@ pub trait HapCharacteristicSetup {
     fn set_event_emitter(&mut self, event_emitter: Option<pointer::EventEmitter>);
 }
 
+/// [`TypedValue`](TypedValue) is implemented by typed representations of characteristic values, like the TLV8 payload
+/// structs in [`tlv8`](tlv8), that are converted from and to the raw value of type `T` stored in the characteristic.
+pub trait TypedValue<T>: Sized {
+    /// Converts a raw characteristic value to the typed representation.
+    fn from_value(value: &T) -> Result<Self>;
+    /// Converts the typed representation to a raw characteristic value.
+    fn to_value(&self) -> T;
+}
+
+impl<V: tlv8::Tlv8Value> TypedValue<Vec<u8>> for V {
+    fn from_value(value: &Vec<u8>) -> Result<Self> { V::decode(Bytes::copy_from_slice(value)) }
+
+    fn to_value(&self) -> Vec<u8> { self.encode() }
+}
+
 /// [`OnReadFn`](OnReadFn) represents a callback function to be set on a characteristic that is called every time a
 /// controller attempts to read its value. Returning a `Some(T)` from this function changes the value of the
 /// characteristic before the controller reads it so the controller reads the new value.
@@ -588,6 +853,19 @@ impl<F, T: Default + Clone + Serialize + Send + Sync> OnUpdateFn<T> for F where
 {
 }
 
+/// [`OnUpdateResponseFn`](OnUpdateResponseFn) represents a callback function to be set on a characteristic that is
+/// called every time a controller attempts to update its value. The first argument is a reference to the current value
+/// of the characteristic and the second argument is a reference to the value the controller attempts to change the
+/// characteristic's to. Returning a `Some(T)` from this function sets the payload of a write response.
+pub trait OnUpdateResponseFn<T: Default + Clone + Serialize + Send + Sync>:
+    FnMut(&T, &T) -> std::result::Result<Option<T>, Box<dyn std::error::Error + Send + Sync>> + 'static + Send + Sync
+{
+}
+impl<F, T: Default + Clone + Serialize + Send + Sync> OnUpdateResponseFn<T> for F where
+    F: FnMut(&T, &T) -> std::result::Result<Option<T>, Box<dyn std::error::Error + Send + Sync>> + 'static + Send + Sync
+{
+}
+
 /// [`OnReadFuture`](OnReadFuture) represents an async callback function to be set on a characteristic that is driven to
 /// completion by the async runtime driving the HAP server every time a controller attempts to read its value. Returning
 /// a `Some(T)` from this function changes the value of the characteristic before the controller reads it so the
@@ -626,7 +904,28 @@ impl<F, T: Default + Clone + Serialize + Send + Sync> OnUpdateFuture<T> for F wh
 {
 }
 
-/// Trait containing the [`OnReadFn`](OnReadFn) and [`OnUpdateFn`](OnUpdateFn) callback functions.
+/// [`OnUpdateResponseFuture`](OnUpdateResponseFuture) represents an async callback function to be set on a
+/// characteristic that is driven to completion by the async runtime driving the HAP server every time a controller
+/// attempts to update its value. The first argument is a reference to the current value of the characteristic and the
+/// second argument is a reference to the value the controller attempts to change the characteristic's to. Returning a
+/// `Some(T)` from this function sets the payload of a write response.
+pub trait OnUpdateResponseFuture<T: Default + Clone + Serialize + Send + Sync>:
+    FnMut(T, T) -> BoxFuture<'static, std::result::Result<Option<T>, Box<dyn std::error::Error + Send + Sync>>>
+    + 'static
+    + Send
+    + Sync
+{
+}
+impl<F, T: Default + Clone + Serialize + Send + Sync> OnUpdateResponseFuture<T> for F where
+    F: FnMut(T, T) -> BoxFuture<'static, std::result::Result<Option<T>, Box<dyn std::error::Error + Send + Sync>>>
+        + 'static
+        + Send
+        + Sync
+{
+}
+
+/// Trait containing the [`OnReadFn`](OnReadFn), [`OnUpdateFn`](OnUpdateFn) and
+/// [`OnUpdateResponseFn`](OnUpdateResponseFn) callback functions.
 pub trait CharacteristicCallbacks<T: fmt::Debug + Default + Clone + Serialize + Send + Sync> {
     /// Sets a callback function on a characteristic that is called every time a controller attempts to read its value.
     /// Returning a `Some(T)` from this function changes the value of the characteristic before the controller reads
@@ -636,9 +935,15 @@ pub trait CharacteristicCallbacks<T: fmt::Debug + Default + Clone + Serialize +
     /// value. The first argument is a reference to the current value of the characteristic and the second argument is a
     /// reference to the value the controller attempts to change the characteristic's to.
     fn on_update(&mut self, f: Option<impl OnUpdateFn<T>>);
+    /// Sets a callback function on a characteristic that is called every time a controller attempts to update its
+    /// value. The first argument is a reference to the current value of the characteristic and the second argument is a
+    /// reference to the value the controller attempts to change the characteristic's to. Returning a `Some(T)` from
+    /// this function sets the payload of a write response.
+    fn on_update_with_response(&mut self, f: Option<impl OnUpdateResponseFn<T>>);
 }
 
-/// Trait containing the [`OnReadFuture`](OnReadFuture) and [`OnUpdateFuture`](OnUpdateFuture) callback functions.
+/// Trait containing the [`OnReadFuture`](OnReadFuture), [`OnUpdateFuture`](OnUpdateFuture) and
+/// [`OnUpdateResponseFuture`](OnUpdateResponseFuture) callback functions.
 pub trait AsyncCharacteristicCallbacks<T: fmt::Debug + Default + Clone + Serialize + Send + Sync> {
     /// Sets an async callback function on a characteristic that is driven to completion by the async runtime driving
     /// the HAP server every time a controller attempts to read its value. Returning a `Some(T)` from this function
@@ -649,6 +954,12 @@ pub trait AsyncCharacteristicCallbacks<T: fmt::Debug + Default + Clone + Seriali
     /// current value of the characteristic and the second argument is a reference to the value the controller attempts
     /// to change the characteristic's to.
     fn on_update_async(&mut self, f: Option<impl OnUpdateFuture<T>>);
+    /// Sets an async callback function on a characteristic that is driven to completion by the async runtime driving
+    /// the HAP server every time a controller attempts to update its value. The first argument is a reference to the
+    /// current value of the characteristic and the second argument is a reference to the value the controller attempts
+    /// to change the characteristic's to. Returning a `Some(T)` from this function sets the payload of a write
+    /// response.
+    fn on_update_with_response_async(&mut self, f: Option<impl OnUpdateResponseFuture<T>>);
 }
 
 #[async_trait]
@@ -682,29 +993,28 @@ where
         Characteristic::set_event_notifications(self, event_notifications)
     }
 
+    fn get_min_event_interval(&self) -> Option<Duration> { Characteristic::get_min_event_interval(self) }
+
+    fn set_min_event_interval(&mut self, min_event_interval: Option<Duration>) {
+        Characteristic::set_min_event_interval(self, min_event_interval)
+    }
+
     async fn get_value(&mut self) -> Result<serde_json::Value> {
         let value = Characteristic::get_value(self).await?;
-        Ok(json!(value))
+        Ok(value_to_json(self.format, &value))
     }
 
     async fn set_value(&mut self, value: serde_json::Value) -> Result<()> {
-        let v;
-        // for whatever reason, the controller is setting boolean values either as a boolean or as an integer
-        if self.format == Format::Bool && value.is_number() {
-            let num_v: u8 = serde_json::from_value(value)?;
-            if num_v == 0 {
-                v = serde_json::from_value(json!(false))?;
-            } else if num_v == 1 {
-                v = serde_json::from_value(json!(true))?;
-            } else {
-                return Err(Error::InvalidValue(Characteristic::get_format(self)));
-            }
-        } else {
-            v = serde_json::from_value(value).map_err(|_| Error::InvalidValue(Characteristic::get_format(self)))?;
-        }
+        let v = self.value_from_json(value)?;
         Characteristic::set_value(self, v).await
     }
 
+    async fn set_value_with_response(&mut self, value: serde_json::Value) -> Result<serde_json::Value> {
+        let v = self.value_from_json(value)?;
+        let response = Characteristic::set_value_with_response(self, v).await?;
+        Ok(value_to_json(self.format, &response))
+    }
+
     fn get_unit(&self) -> Option<Unit> { Characteristic::get_unit(self) }
 
     fn set_unit(&mut self, unit: Option<Unit>) { Characteristic::set_unit(self, unit) }
@@ -813,6 +1123,10 @@ where
     fn on_read(&mut self, f: Option<impl OnReadFn<T>>) { Characteristic::on_read(self, f) }
 
     fn on_update(&mut self, f: Option<impl OnUpdateFn<T>>) { Characteristic::on_update(self, f) }
+
+    fn on_update_with_response(&mut self, f: Option<impl OnUpdateResponseFn<T>>) {
+        Characteristic::on_update_with_response(self, f)
+    }
 }
 
 impl<T: fmt::Debug + Default + Clone + Serialize + Send + Sync> AsyncCharacteristicCallbacks<T> for Characteristic<T>
@@ -822,6 +1136,10 @@ where
     fn on_read_async(&mut self, f: Option<impl OnReadFuture<T>>) { Characteristic::on_read_async(self, f) }
 
     fn on_update_async(&mut self, f: Option<impl OnUpdateFuture<T>>) { Characteristic::on_update_async(self, f) }
+
+    fn on_update_with_response_async(&mut self, f: Option<impl OnUpdateResponseFuture<T>>) {
+        Characteristic::on_update_with_response_async(self, f)
+    }
 }
 
 #[cfg(test)]
@@ -838,6 +1156,7 @@ mod tests {
             perms: vec![Perm::PairedRead, Perm::Events],
             description: Some("Acme Tilt Angle".into()),
             event_notifications: Some(true),
+            min_event_interval: None,
 
             value: 123,
             unit: Some(Unit::ArcDegrees),
@@ -859,8 +1178,276 @@ mod tests {
             on_update_async: None,
 
             event_emitter: None,
+            event_throttle: Arc::default(),
         };
         let json = serde_json::to_string(&characteristic).unwrap();
         assert_eq!(json, "{\"iid\":1,\"type\":\"C1\",\"format\":\"uint16\",\"perms\":[\"pr\",\"ev\"],\"description\":\"Acme Tilt Angle\",\"ev\":true,\"value\":123,\"unit\":\"arcdegrees\",\"maxValue\":360,\"minValue\":0,\"minStep\":1,\"valid-values-range\":[0,360]}".to_string());
     }
+
+    #[tokio::test]
+    async fn test_set_value_numeric_constraints() {
+        let mut characteristic = Characteristic::<u8>::new(
+            1,
+            1,
+            HapType::CurrentDoorState,
+            Format::UInt8,
+            vec![Perm::PairedRead, Perm::PairedWrite],
+            None,
+            None,
+            0,
+            None,
+            Some(4),
+            Some(0),
+            Some(1),
+            None,
+            None,
+            Some(vec![0, 1, 3]),
+            None,
+            None,
+            None,
+        );
+
+        assert!(characteristic.set_value(3).await.is_ok());
+        assert!(matches!(characteristic.set_value(5).await, Err(Error::ValueAboveMaxValue)));
+        assert!(matches!(characteristic.set_value(2).await, Err(Error::ValueNotInValidValues)));
+        assert_eq!(characteristic.value, 3);
+    }
+
+    #[tokio::test]
+    async fn test_set_value_float_step() {
+        let mut characteristic = Characteristic::<f32>::new(
+            1,
+            1,
+            HapType::TargetTemperature,
+            Format::Float,
+            vec![Perm::PairedRead, Perm::PairedWrite],
+            None,
+            None,
+            10.0,
+            None,
+            Some(38.0),
+            Some(10.0),
+            Some(0.15),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+
+        characteristic.set_value(10.150001).await.unwrap();
+        assert!((characteristic.value - 10.15).abs() < 0.0001);
+        assert!(matches!(characteristic.set_value(9.0).await, Err(Error::ValueBelowMinValue)));
+    }
+
+    #[tokio::test]
+    async fn test_min_event_interval() {
+        use crate::event::EventEmitter;
+        use futures::StreamExt;
+
+        let mut characteristic = Characteristic::<u8>::new(
+            1,
+            1,
+            HapType::CurrentDoorState,
+            Format::UInt8,
+            vec![Perm::PairedRead, Perm::Events],
+            None,
+            None,
+            0,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+
+        let event_emitter = Arc::new(EventEmitter::new());
+        let mut events = event_emitter.subscribe();
+        characteristic.set_event_emitter(Some(event_emitter));
+        characteristic.set_min_event_interval(Some(Duration::from_millis(30)));
+
+        // the first change is emitted right away, the following ones are held back and only the latest is emitted
+        let started = Instant::now();
+        for value in 1..=3 {
+            characteristic.set_value(value).await.unwrap();
+        }
+        assert_eq!(characteristic.value, 3);
+
+        for value in [1, 3] {
+            assert_eq!(
+                events.next().await,
+                Some(Event::CharacteristicValueChanged {
+                    aid: 1,
+                    iid: 1,
+                    value: json!(value),
+                })
+            );
+        }
+        assert!(started.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[test]
+    fn test_min_event_interval_without_runtime() {
+        use crate::event::EventEmitter;
+
+        let mut characteristic = Characteristic::<u8>::new(
+            1,
+            1,
+            HapType::CurrentDoorState,
+            Format::UInt8,
+            vec![Perm::PairedRead, Perm::Events],
+            None,
+            None,
+            0,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+
+        let event_emitter = Arc::new(EventEmitter::new());
+        let mut events = event_emitter.receiver();
+        characteristic.set_event_emitter(Some(event_emitter));
+        characteristic.set_min_event_interval(Some(Duration::from_secs(60)));
+
+        // without a runtime to defer them on, events are emitted right away
+        futures::executor::block_on(async {
+            characteristic.set_value(1).await.unwrap();
+            characteristic.set_value(2).await.unwrap();
+        });
+        for value in [1, 2] {
+            assert_eq!(
+                events.try_recv().unwrap(),
+                Event::CharacteristicValueChanged {
+                    aid: 1,
+                    iid: 1,
+                    value: json!(value),
+                }
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_set_value_with_response() {
+        let mut characteristic = Characteristic::<Vec<u8>>::new(
+            1,
+            1,
+            HapType::LockControlPoint,
+            Format::Tlv8,
+            vec![Perm::PairedWrite, Perm::WriteResponse],
+            None,
+            None,
+            vec![],
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+
+        assert_eq!(characteristic.set_value_with_response(vec![1]).await.unwrap(), vec![1]);
+
+        characteristic.on_update_with_response(Some(|_: &Vec<u8>, new: &Vec<u8>| Ok(Some(vec![new[0], 0xff]))));
+        assert_eq!(characteristic.set_value_with_response(vec![2]).await.unwrap(), vec![2, 0xff]);
+        assert_eq!(characteristic.value, vec![2]);
+    }
+
+    #[tokio::test]
+    async fn test_set_value_length_constraints() {
+        let mut characteristic = Characteristic::<String>::new(
+            1,
+            1,
+            HapType::Name,
+            Format::String,
+            vec![Perm::PairedRead, Perm::PairedWrite],
+            None,
+            None,
+            String::new(),
+            None,
+            None,
+            None,
+            None,
+            Some(4),
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+
+        assert!(characteristic.set_value("Acme".into()).await.is_ok());
+        assert!(matches!(
+            characteristic.set_value("Acme Lamp".into()).await,
+            Err(Error::ValueAboveMaxLen)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_data_value_base64() {
+        let mut characteristic = Characteristic::<Vec<u8>>::new(
+            1,
+            1,
+            HapType::DisplayOrder,
+            Format::Tlv8,
+            vec![Perm::PairedRead, Perm::PairedWrite],
+            None,
+            None,
+            Vec::new(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(3),
+            None,
+            None,
+            None,
+            None,
+        );
+
+        // "AQID" is [1, 2, 3] encoded, which is within `max_data_len` although the encoded value is longer
+        assert!(HapCharacteristic::set_value(&mut characteristic, json!("AQID")).await.is_ok());
+        assert_eq!(characteristic.value, vec![1, 2, 3]);
+        assert_eq!(HapCharacteristic::get_value(&mut characteristic).await.unwrap(), json!("AQID"));
+        assert_eq!(serde_json::to_value(&characteristic).unwrap()["value"], json!("AQID"));
+
+        assert!(matches!(
+            HapCharacteristic::set_value(&mut characteristic, json!("AQIDBA==")).await,
+            Err(Error::ValueAboveMaxDataLen)
+        ));
+        assert!(matches!(
+            HapCharacteristic::set_value(&mut characteristic, json!("not base64!")).await,
+            Err(Error::InvalidValue(Format::Tlv8))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_typed_enum_value() {
+        use crate::characteristic::current_door_state::{CurrentDoorState, CurrentDoorStateCharacteristic};
+
+        let mut characteristic = CurrentDoorStateCharacteristic::new(1, 1);
+
+        assert_eq!(characteristic.get_typed_value().await.unwrap(), CurrentDoorState::Open);
+        assert!(characteristic.set_typed_value(CurrentDoorState::Closing).await.is_ok());
+        assert_eq!(HapCharacteristic::get_value(&mut characteristic).await.unwrap(), json!(3));
+        assert_eq!(CurrentDoorState::try_from(4).unwrap(), CurrentDoorState::Stopped);
+        assert!(matches!(CurrentDoorState::try_from(5), Err(Error::ValueNotInValidValues)));
+    }
 }
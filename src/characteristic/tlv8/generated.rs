@@ -0,0 +1,757 @@
+// this file is auto-generated by hap-codegen
+
+use serde::Serialize;
+
+use crate::characteristic::tlv8::Tlv8;
+
+/// Address of a controller or an accessory an RTP stream is set up with.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct Address {
+    /// IP version of the address. `0` for IPv4, `1` for IPv6.
+    #[tlv(type = 0x01)]
+    pub ip_version: u8,
+    /// IP address.
+    #[tlv(type = 0x02)]
+    pub ip_address: String,
+    /// Port of the video RTP stream.
+    #[tlv(type = 0x03)]
+    pub video_rtp_port: u16,
+    /// Port of the audio RTP stream.
+    #[tlv(type = 0x04)]
+    pub audio_rtp_port: u16,
+}
+
+/// Configuration of an audio codec.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct AudioCodecConfiguration {
+    /// Type of the codec. `2` for AAC-ELD, `3` for Opus.
+    #[tlv(type = 0x01)]
+    pub codec_type: u8,
+    /// Parameters of the codec.
+    #[tlv(type = 0x02)]
+    pub codec_parameters: AudioCodecParameters,
+}
+
+/// Parameters of an audio codec.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct AudioCodecParameters {
+    /// Number of audio channels.
+    #[tlv(type = 0x01)]
+    pub channels: u8,
+    /// Bit rate mode. `0` for variable, `1` for constant bit rate.
+    #[tlv(type = 0x02)]
+    pub bit_rate: u8,
+    /// Sample rate. `0` for 8 kHz, `1` for 16 kHz, `2` for 24 kHz.
+    #[tlv(type = 0x03)]
+    pub sample_rate: u8,
+    /// Packet time in milliseconds.
+    #[tlv(type = 0x04)]
+    pub rtp_time: Option<u8>,
+}
+
+/// RTP parameters of a selected audio stream.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct AudioRtpParameters {
+    /// Payload type of the RTP packets.
+    #[tlv(type = 0x01)]
+    pub payload_type: u8,
+    /// SSRC of the stream.
+    #[tlv(type = 0x02)]
+    pub ssrc: u32,
+    /// Maximum bit rate in kbit/s.
+    #[tlv(type = 0x03)]
+    pub max_bit_rate: u16,
+    /// Minimum RTCP interval in seconds.
+    #[tlv(type = 0x04)]
+    pub min_rtcp_interval: f32,
+    /// Payload type of comfort noise packets.
+    #[tlv(type = 0x06)]
+    pub comfort_noise_payload_type: Option<u8>,
+}
+
+/// Buttons of a target of a remote.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct ButtonConfiguration {
+    /// Configured buttons.
+    #[tlv(type = 0x01, entries)]
+    pub buttons: Vec<TargetButton>,
+}
+
+/// Event of a button of a remote.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct ButtonEvent {
+    /// ID of the button.
+    #[tlv(type = 0x01)]
+    pub button_id: u8,
+    /// State of the button. `0` for up, `1` for down.
+    #[tlv(type = 0x02)]
+    pub button_state: u8,
+    /// Timestamp of the event.
+    #[tlv(type = 0x03)]
+    pub timestamp: u64,
+    /// Identifier of the active target.
+    #[tlv(type = 0x04)]
+    pub active_identifier: u32,
+}
+
+/// Request to read or update the value transitions of a service, e.g. for Adaptive Lighting.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct CharacteristicValueTransitionControl {
+    /// Request to read the active transition of a characteristic.
+    #[tlv(type = 0x01)]
+    pub read_configuration: Option<ReadValueTransitionConfiguration>,
+    /// Request to start or stop transitions.
+    #[tlv(type = 0x02)]
+    pub update_configuration: Option<UpdateValueTransitionConfiguration>,
+}
+
+/// Order in which the input sources of a television are displayed, given as their identifiers.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct DisplayOrder(#[tlv(type = 0x01, list)] pub Vec<u32>);
+
+/// Configuration of a media container used for recordings.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct MediaContainerConfiguration {
+    /// Type of the container. `0` for fragmented MP4.
+    #[tlv(type = 0x01)]
+    pub container_type: u8,
+    /// Parameters of the container.
+    #[tlv(type = 0x02)]
+    pub container_parameters: MediaContainerParameters,
+}
+
+/// Parameters of a media container used for recordings.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct MediaContainerParameters {
+    /// Length of a fragment in milliseconds.
+    #[tlv(type = 0x01)]
+    pub fragment_length: u32,
+}
+
+/// Message of the pairing procedures. Only the first pairing of a List Pairings response is decoded.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct PairingMessage {
+    /// Method of the request.
+    #[tlv(type = 0x00)]
+    pub method: Option<u8>,
+    /// Pairing identifier of a controller or the accessory.
+    #[tlv(type = 0x01)]
+    pub identifier: Option<String>,
+    /// 16 bytes of SRP salt.
+    #[tlv(type = 0x02)]
+    pub salt: Option<Vec<u8>>,
+    /// Curve25519, SRP or Ed25519 public key.
+    #[tlv(type = 0x03)]
+    pub public_key: Option<Vec<u8>>,
+    /// Ed25519 or SRP proof.
+    #[tlv(type = 0x04)]
+    pub proof: Option<Vec<u8>>,
+    /// Encrypted data with the auth tag appended.
+    #[tlv(type = 0x05)]
+    pub encrypted_data: Option<Vec<u8>>,
+    /// State of the procedure.
+    #[tlv(type = 0x06)]
+    pub state: Option<u8>,
+    /// Error code of the response.
+    #[tlv(type = 0x07)]
+    pub error: Option<u8>,
+    /// Seconds to wait before retrying the procedure.
+    #[tlv(type = 0x08)]
+    pub retry_delay: Option<u16>,
+    /// X.509 certificate.
+    #[tlv(type = 0x09)]
+    pub certificate: Option<Vec<u8>>,
+    /// Ed25519 signature.
+    #[tlv(type = 0x0A)]
+    pub signature: Option<Vec<u8>>,
+    /// Permissions of a controller. `0` for a regular user, `1` for an admin.
+    #[tlv(type = 0x0B)]
+    pub permissions: Option<u8>,
+    /// Pairing type flags.
+    #[tlv(type = 0x13)]
+    pub flags: Option<u32>,
+}
+
+/// Request to read the active value transition of a characteristic.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct ReadValueTransitionConfiguration {
+    /// Instance ID of the characteristic.
+    #[tlv(type = 0x01)]
+    pub characteristic_iid: u64,
+}
+
+/// Configuration of an audio codec used for recordings.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct RecordingAudioCodecConfiguration {
+    /// Type of the codec. `0` for AAC-LC, `1` for AAC-ELD.
+    #[tlv(type = 0x01)]
+    pub codec_type: u8,
+    /// Parameters of the codec.
+    #[tlv(type = 0x02)]
+    pub codec_parameters: RecordingAudioCodecParameters,
+}
+
+/// Parameters of an audio codec used for recordings.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct RecordingAudioCodecParameters {
+    /// Number of audio channels.
+    #[tlv(type = 0x01)]
+    pub channels: u8,
+    /// Bit rate modes. `0` for variable, `1` for constant bit rate.
+    #[tlv(type = 0x02, list)]
+    pub bit_rate_modes: Vec<u8>,
+    /// Sample rates. `0` for 8 kHz up to `5` for 48 kHz.
+    #[tlv(type = 0x03, list)]
+    pub sample_rates: Vec<u8>,
+    /// Maximum bit rate in kbit/s.
+    #[tlv(type = 0x04)]
+    pub max_audio_bit_rate: Option<u32>,
+}
+
+/// Configuration of a video codec used for recordings.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct RecordingVideoCodecConfiguration {
+    /// Type of the codec. `0` for H.264.
+    #[tlv(type = 0x01)]
+    pub codec_type: u8,
+    /// Parameters of the codec.
+    #[tlv(type = 0x02)]
+    pub codec_parameters: RecordingVideoCodecParameters,
+    /// Supported resolutions and frame rates.
+    #[tlv(type = 0x03, list)]
+    pub attributes: Vec<VideoAttributes>,
+}
+
+/// Parameters of a video codec used for recordings.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct RecordingVideoCodecParameters {
+    /// H.264 profiles. `0` for constrained baseline, `1` for main, `2` for high.
+    #[tlv(type = 0x01, list)]
+    pub profile_ids: Vec<u8>,
+    /// H.264 levels. `0` for 3.1, `1` for 3.2, `2` for 4.
+    #[tlv(type = 0x02, list)]
+    pub levels: Vec<u8>,
+    /// Bit rate in kbit/s.
+    #[tlv(type = 0x03)]
+    pub bit_rate: Option<u32>,
+    /// Interval between I-frames in milliseconds.
+    #[tlv(type = 0x04)]
+    pub iframe_interval: Option<u32>,
+}
+
+/// Parameters of a selected audio stream.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct SelectedAudioParameters {
+    /// Type of the codec. `2` for AAC-ELD, `3` for Opus.
+    #[tlv(type = 0x01)]
+    pub codec_type: u8,
+    /// Parameters of the codec.
+    #[tlv(type = 0x02)]
+    pub codec_parameters: AudioCodecParameters,
+    /// RTP parameters of the stream.
+    #[tlv(type = 0x03)]
+    pub rtp_parameters: AudioRtpParameters,
+    /// Whether comfort noise is used.
+    #[tlv(type = 0x04)]
+    pub comfort_noise: bool,
+}
+
+/// Audio stream configuration selected by a controller for Siri input.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct SelectedAudioStreamConfiguration {
+    /// Selected codec configuration.
+    #[tlv(type = 0x01)]
+    pub selected_audio_input_stream_configuration: AudioCodecConfiguration,
+}
+
+/// Recording configuration selected by a controller.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct SelectedCameraRecordingConfiguration {
+    /// Selected general recording configuration.
+    #[tlv(type = 0x01)]
+    pub recording_configuration: SupportedCameraRecordingConfiguration,
+    /// Selected video configuration.
+    #[tlv(type = 0x02)]
+    pub video_configuration: RecordingVideoCodecConfiguration,
+    /// Selected audio configuration.
+    #[tlv(type = 0x03)]
+    pub audio_configuration: RecordingAudioCodecConfiguration,
+}
+
+/// RTP stream configuration selected by a controller.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct SelectedRtpStreamConfiguration {
+    /// Session the configuration applies to and the command to execute.
+    #[tlv(type = 0x01)]
+    pub session_control: SessionControl,
+    /// Parameters of the video stream.
+    #[tlv(type = 0x02)]
+    pub selected_video_parameters: Option<SelectedVideoParameters>,
+    /// Parameters of the audio stream.
+    #[tlv(type = 0x03)]
+    pub selected_audio_parameters: Option<SelectedAudioParameters>,
+}
+
+/// Parameters of a selected video stream.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct SelectedVideoParameters {
+    /// Type of the codec. `0` for H.264.
+    #[tlv(type = 0x01)]
+    pub codec_type: u8,
+    /// Parameters of the codec.
+    #[tlv(type = 0x02)]
+    pub codec_parameters: VideoCodecParameters,
+    /// Resolution and frame rate.
+    #[tlv(type = 0x03)]
+    pub attributes: VideoAttributes,
+    /// RTP parameters of the stream.
+    #[tlv(type = 0x04)]
+    pub rtp_parameters: VideoRtpParameters,
+}
+
+/// Signature of a service, announcing its properties and linked services.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct ServiceSignature {
+    /// Properties of the service. `0x0001` for the primary service, `0x0002` for a hidden one.
+    #[tlv(type = 0x0F)]
+    pub service_properties: Option<u16>,
+    /// Instance IDs of the linked services, 2 little-endian bytes each.
+    #[tlv(type = 0x10)]
+    pub linked_services: Option<Vec<u8>>,
+}
+
+/// Session control of a selected RTP stream configuration.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct SessionControl {
+    /// UUID of the session.
+    #[tlv(type = 0x01)]
+    pub session_identifier: Vec<u8>,
+    /// Command. `0` to end, `1` to start, `2` to suspend, `3` to resume, `4` to reconfigure.
+    #[tlv(type = 0x02)]
+    pub command: u8,
+}
+
+/// Data stream transport setup request written by a controller.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct SetupDataStreamTransport {
+    /// Command. `0` to start a session.
+    #[tlv(type = 0x01)]
+    pub session_command_type: u8,
+    /// Type of the transport. `0` for HomeKit Data Stream over TCP.
+    #[tlv(type = 0x02)]
+    pub transport_type: u8,
+    /// Key salt of the controller.
+    #[tlv(type = 0x03)]
+    pub controller_key_salt: Vec<u8>,
+}
+
+/// Data stream transport setup response returned to a controller.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct SetupDataStreamTransportResponse {
+    /// Status of the setup. `0` for success.
+    #[tlv(type = 0x01)]
+    pub status: u8,
+    /// Parameters of the transport session.
+    #[tlv(type = 0x02)]
+    pub transport_session_parameters: TransportSessionParameters,
+    /// Key salt of the accessory.
+    #[tlv(type = 0x03)]
+    pub accessory_key_salt: Vec<u8>,
+}
+
+/// RTP stream endpoint setup. Controllers write the request fields, accessories answer with the response fields.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct SetupEndpoint {
+    /// UUID of the session.
+    #[tlv(type = 0x01)]
+    pub session_id: Vec<u8>,
+    /// Status of the setup. Only present in responses. `0` for success.
+    #[tlv(type = 0x02)]
+    pub status: Option<u8>,
+    /// Address of the controller in requests and of the accessory in responses.
+    #[tlv(type = 0x03)]
+    pub address: Address,
+    /// SRTP parameters of the video stream.
+    #[tlv(type = 0x04)]
+    pub video_srtp_parameters: SrtpParameters,
+    /// SRTP parameters of the audio stream.
+    #[tlv(type = 0x05)]
+    pub audio_srtp_parameters: SrtpParameters,
+    /// SSRC of the video stream. Only present in responses.
+    #[tlv(type = 0x06)]
+    pub video_ssrc: Option<u32>,
+    /// SSRC of the audio stream. Only present in responses.
+    #[tlv(type = 0x07)]
+    pub audio_ssrc: Option<u32>,
+}
+
+/// SRTP parameters of a stream.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct SrtpParameters {
+    /// Crypto suite. `0` for AES_CM_128_HMAC_SHA1_80, `2` for disabled encryption.
+    #[tlv(type = 0x01)]
+    pub crypto_suite: u8,
+    /// SRTP master key.
+    #[tlv(type = 0x02)]
+    pub master_key: Vec<u8>,
+    /// SRTP master salt.
+    #[tlv(type = 0x03)]
+    pub master_salt: Vec<u8>,
+}
+
+/// Streaming status of an RTP stream management service.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct StreamingStatus {
+    /// Status. `0` for available, `1` for in use, `2` for unavailable.
+    #[tlv(type = 0x01)]
+    pub status: u8,
+}
+
+/// Audio configurations supported for recordings.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct SupportedAudioRecordingConfiguration {
+    /// Supported codec configurations.
+    #[tlv(type = 0x01, list)]
+    pub audio_codec_configurations: Vec<RecordingAudioCodecConfiguration>,
+}
+
+/// Audio configurations supported for RTP streams.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct SupportedAudioStreamConfiguration {
+    /// Supported codec configurations.
+    #[tlv(type = 0x01, list)]
+    pub audio_codec_configurations: Vec<AudioCodecConfiguration>,
+    /// Whether comfort noise is supported.
+    #[tlv(type = 0x02)]
+    pub comfort_noise_support: bool,
+}
+
+/// Button supported by a remote.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct SupportedButton {
+    /// ID of the button.
+    #[tlv(type = 0x01)]
+    pub button_id: u8,
+    /// Type of the button.
+    #[tlv(type = 0x02)]
+    pub button_type: u16,
+}
+
+/// Buttons supported by a remote.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct SupportedButtonConfiguration {
+    /// Supported buttons.
+    #[tlv(type = 0x01, entries)]
+    pub buttons: Vec<SupportedButton>,
+}
+
+/// General recording configuration supported by a camera.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct SupportedCameraRecordingConfiguration {
+    /// Length of the prebuffer in milliseconds.
+    #[tlv(type = 0x01)]
+    pub prebuffer_length: u32,
+    /// Bitmask of events triggering a recording. `0x01` for motion, `0x02` for doorbell.
+    #[tlv(type = 0x02)]
+    pub event_trigger_options: u64,
+    /// Supported media container configurations.
+    #[tlv(type = 0x03, list)]
+    pub media_container_configurations: Vec<MediaContainerConfiguration>,
+}
+
+/// Characteristics of a service that support value transitions.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct SupportedCharacteristicValueTransitionConfiguration {
+    /// Supported transitions.
+    #[tlv(type = 0x01, list)]
+    pub configurations: Vec<SupportedValueTransitionConfiguration>,
+}
+
+/// Data stream transport configurations supported by an accessory.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct SupportedDataStreamTransportConfiguration {
+    /// Supported transport configurations.
+    #[tlv(type = 0x01, list)]
+    pub transfer_transport_configurations: Vec<TransferTransportConfiguration>,
+}
+
+/// RTP configuration supported by a camera.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct SupportedRtpConfiguration {
+    /// Supported SRTP crypto suites. `0` for AES_CM_128_HMAC_SHA1_80, `2` for disabled encryption.
+    #[tlv(type = 0x02, list)]
+    pub srtp_crypto_suites: Vec<u8>,
+}
+
+/// Target configuration supported by a remote.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct SupportedTargetConfiguration {
+    /// Maximum number of targets.
+    #[tlv(type = 0x01)]
+    pub maximum_targets: u8,
+    /// Resolution of the button event timestamps.
+    #[tlv(type = 0x02)]
+    pub ticks_per_second: u64,
+    /// Buttons supported by the remote.
+    #[tlv(type = 0x03)]
+    pub supported_button_configuration: SupportedButtonConfiguration,
+    /// Hardware type of the remote. `1` if it supports Siri.
+    #[tlv(type = 0x04)]
+    pub target_type: Option<u8>,
+}
+
+/// Value transition supported by a characteristic.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct SupportedValueTransitionConfiguration {
+    /// Instance ID of the characteristic.
+    #[tlv(type = 0x01)]
+    pub characteristic_iid: u64,
+    /// Type of the transition. `1` for linear, `2` for linear derived from another characteristic.
+    #[tlv(type = 0x02)]
+    pub transition_type: u8,
+}
+
+/// Video configurations supported for recordings.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct SupportedVideoRecordingConfiguration {
+    /// Supported codec configurations.
+    #[tlv(type = 0x01, list)]
+    pub video_codec_configurations: Vec<RecordingVideoCodecConfiguration>,
+}
+
+/// Video configurations supported for RTP streams.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct SupportedVideoStreamConfiguration {
+    /// Supported codec configurations.
+    #[tlv(type = 0x01, list)]
+    pub video_codec_configurations: Vec<VideoCodecConfiguration>,
+}
+
+/// Button configured for a target of a remote.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct TargetButton {
+    /// ID of the button.
+    #[tlv(type = 0x01)]
+    pub button_id: u8,
+    /// Type of the button.
+    #[tlv(type = 0x02)]
+    pub button_type: u16,
+    /// Name of the button.
+    #[tlv(type = 0x03)]
+    pub button_name: Option<String>,
+}
+
+/// Target controlled by a remote.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct TargetConfiguration {
+    /// Identifier of the target.
+    #[tlv(type = 0x01)]
+    pub target_identifier: u32,
+    /// Name of the target.
+    #[tlv(type = 0x02)]
+    pub target_name: Option<String>,
+    /// Category of the target.
+    #[tlv(type = 0x03)]
+    pub target_category: Option<u16>,
+    /// Buttons configured for the target.
+    #[tlv(type = 0x04)]
+    pub button_configuration: Option<ButtonConfiguration>,
+}
+
+/// Targets controlled by a remote, along with the operation a controller performs on them.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct TargetListConfiguration {
+    /// Operation. `0` to list, `1` to add, `2` to remove, `3` to reset, `4` to update.
+    #[tlv(type = 0x01)]
+    pub operation: Option<u8>,
+    /// Targets the operation applies to.
+    #[tlv(type = 0x02, list)]
+    pub target_configurations: Vec<TargetConfiguration>,
+}
+
+/// Transport configuration of a data stream.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct TransferTransportConfiguration {
+    /// Type of the transport. `0` for HomeKit Data Stream over TCP.
+    #[tlv(type = 0x01)]
+    pub transport_type: u8,
+}
+
+/// Range of the value of the characteristic a value transition curve is adjusted by.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct TransitionAdjustmentMultiplierRange {
+    /// Minimum value of the adjustment characteristic.
+    #[tlv(type = 0x01)]
+    pub minimum_adjustment_multiplier: u32,
+    /// Maximum value of the adjustment characteristic.
+    #[tlv(type = 0x02)]
+    pub maximum_adjustment_multiplier: u32,
+}
+
+/// Curve of a value transition.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct TransitionCurveConfiguration {
+    /// Points of the curve.
+    #[tlv(type = 0x01, list)]
+    pub entries: Vec<TransitionEntry>,
+    /// Instance ID of the characteristic adjusting the values of the curve, e.g. the brightness.
+    #[tlv(type = 0x02)]
+    pub adjustment_characteristic_iid: u64,
+    /// Range of the value of the adjustment characteristic.
+    #[tlv(type = 0x03)]
+    pub adjustment_multiplier_range: TransitionAdjustmentMultiplierRange,
+}
+
+/// Point of a value transition curve.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct TransitionEntry {
+    /// Factor of the value of the adjustment characteristic that's added to the value.
+    #[tlv(type = 0x01)]
+    pub adjustment_factor: f32,
+    /// Value of the characteristic at this point.
+    #[tlv(type = 0x02)]
+    pub value: f32,
+    /// Time since the previous point in milliseconds.
+    #[tlv(type = 0x03)]
+    pub transition_offset: u32,
+    /// Time the value is held before moving on to the next point in milliseconds.
+    #[tlv(type = 0x04)]
+    pub duration: Option<u32>,
+}
+
+/// Parameters of a data stream transport session.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct TransportSessionParameters {
+    /// TCP port the accessory listens on.
+    #[tlv(type = 0x01)]
+    pub tcp_listening_port: u16,
+}
+
+/// Request to start or stop value transitions.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct UpdateValueTransitionConfiguration {
+    /// Transitions to start or stop.
+    #[tlv(type = 0x01, list)]
+    pub configurations: Vec<ValueTransitionConfiguration>,
+}
+
+/// Value transition of a characteristic. Without transition parameters, the active transition stops.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct ValueTransitionConfiguration {
+    /// Instance ID of the transitioning characteristic.
+    #[tlv(type = 0x01)]
+    pub characteristic_iid: u64,
+    /// Parameters of the transition.
+    #[tlv(type = 0x02)]
+    pub transition_parameters: Option<ValueTransitionParameters>,
+    /// Curve the value of the characteristic follows.
+    #[tlv(type = 0x05)]
+    pub transition_curve_configuration: Option<TransitionCurveConfiguration>,
+    /// Interval between two value updates in milliseconds.
+    #[tlv(type = 0x06)]
+    pub update_interval: Option<u16>,
+    /// Minimum interval between two event notifications of value updates in milliseconds.
+    #[tlv(type = 0x08)]
+    pub notify_interval_threshold: Option<u32>,
+}
+
+/// Status of an active value transition.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct ValueTransitionConfigurationStatus {
+    /// Instance ID of the transitioning characteristic.
+    #[tlv(type = 0x01)]
+    pub characteristic_iid: u64,
+    /// Parameters of the transition.
+    #[tlv(type = 0x02)]
+    pub transition_parameters: ValueTransitionParameters,
+    /// Time since the start of the transition in milliseconds.
+    #[tlv(type = 0x03)]
+    pub time_since_start: u32,
+}
+
+/// Write response of the Characteristic Value Transition Control, listing the active transitions.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct ValueTransitionControlResponse {
+    /// Status of the active transitions.
+    #[tlv(type = 0x01, list)]
+    pub configuration_status: Vec<ValueTransitionConfigurationStatus>,
+}
+
+/// Parameters identifying a value transition.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct ValueTransitionParameters {
+    /// UUID of the transition.
+    #[tlv(type = 0x01)]
+    pub transition_id: Vec<u8>,
+    /// Start time of the transition in milliseconds since 2001-01-01.
+    #[tlv(type = 0x02)]
+    pub start_time: u64,
+}
+
+/// Resolution and frame rate of a video stream.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct VideoAttributes {
+    /// Image width in pixels.
+    #[tlv(type = 0x01)]
+    pub image_width: u16,
+    /// Image height in pixels.
+    #[tlv(type = 0x02)]
+    pub image_height: u16,
+    /// Frame rate in frames per second.
+    #[tlv(type = 0x03)]
+    pub frame_rate: u8,
+}
+
+/// Configuration of a video codec used for RTP streams.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct VideoCodecConfiguration {
+    /// Type of the codec. `0` for H.264.
+    #[tlv(type = 0x01)]
+    pub codec_type: u8,
+    /// Parameters of the codec.
+    #[tlv(type = 0x02)]
+    pub codec_parameters: VideoCodecParameters,
+    /// Supported resolutions and frame rates.
+    #[tlv(type = 0x03, list)]
+    pub attributes: Vec<VideoAttributes>,
+}
+
+/// Parameters of a video codec used for RTP streams.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct VideoCodecParameters {
+    /// H.264 profiles. `0` for constrained baseline, `1` for main, `2` for high.
+    #[tlv(type = 0x01, list)]
+    pub profile_ids: Vec<u8>,
+    /// H.264 levels. `0` for 3.1, `1` for 3.2, `2` for 4.
+    #[tlv(type = 0x02, list)]
+    pub levels: Vec<u8>,
+    /// Packetization modes. `0` for non-interleaved.
+    #[tlv(type = 0x03, list)]
+    pub packetization_modes: Vec<u8>,
+    /// Whether coordination of video orientation is enabled.
+    #[tlv(type = 0x04)]
+    pub cvo_enabled: Option<bool>,
+    /// ID of the coordination of video orientation RTP extension.
+    #[tlv(type = 0x05)]
+    pub cvo_id: Option<u8>,
+}
+
+/// RTP parameters of a selected video stream.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Tlv8)]
+pub struct VideoRtpParameters {
+    /// Payload type of the RTP packets.
+    #[tlv(type = 0x01)]
+    pub payload_type: u8,
+    /// SSRC of the stream.
+    #[tlv(type = 0x02)]
+    pub ssrc: u32,
+    /// Maximum bit rate in kbit/s.
+    #[tlv(type = 0x03)]
+    pub max_bit_rate: u16,
+    /// Minimum RTCP interval in seconds.
+    #[tlv(type = 0x04)]
+    pub min_rtcp_interval: f32,
+    /// Maximum MTU.
+    #[tlv(type = 0x05)]
+    pub max_mtu: Option<u16>,
+}
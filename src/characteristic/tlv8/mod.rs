@@ -0,0 +1,397 @@
+use bytes::Bytes;
+use serde::Serialize;
+
+use crate::{
+    tlv::{self, MAX_FRAGMENT_LEN},
+    Error,
+    Result,
+};
+
+mod generated;
+
+pub use generated::*;
+pub use hap_derive::Tlv8;
+
+/// Type of the empty item separating two consecutive list entries of the same type, unless another separator is given.
+pub const SEPARATOR: u8 = 0x00;
+
+/// [`Tlv8Value`](Tlv8Value) is implemented by the typed payloads of `Format::Tlv8` characteristics.
+pub trait Tlv8Value: Sized {
+    /// Encodes the payload to its TLV8 representation.
+    fn encode(&self) -> Vec<u8>;
+    /// Decodes the payload from its TLV8 representation.
+    fn decode(bytes: Bytes) -> Result<Self>;
+}
+
+/// [`Tlv8Item`](Tlv8Item) is implemented by the types a single item of a TLV8 payload can hold. Integers are encoded
+/// little-endian, nested payloads are encoded as the value of the item.
+pub trait Tlv8Item: Sized {
+    /// Encodes the value of the item.
+    fn to_bytes(&self) -> Vec<u8>;
+    /// Decodes the value of the item.
+    fn from_bytes(bytes: &Bytes) -> Result<Self>;
+}
+
+macro_rules! impl_tlv8_item_for_uint {
+    ($t:ty) => {
+        impl Tlv8Item for $t {
+            fn to_bytes(&self) -> Vec<u8> { self.to_le_bytes().to_vec() }
+
+            fn from_bytes(bytes: &Bytes) -> Result<Self> {
+                // controllers sometimes send shorter integers than specified, so those are zero-extended
+                if bytes.is_empty() || bytes.len() > std::mem::size_of::<$t>() {
+                    return Err(Error::InvalidTlv8(format!(
+                        "expected an integer of up to {} bytes, got {} bytes",
+                        std::mem::size_of::<$t>(),
+                        bytes.len()
+                    )));
+                }
+
+                let mut le_bytes = [0; std::mem::size_of::<$t>()];
+                le_bytes[..bytes.len()].copy_from_slice(bytes);
+
+                Ok(<$t>::from_le_bytes(le_bytes))
+            }
+        }
+    };
+}
+
+impl_tlv8_item_for_uint!(u8);
+impl_tlv8_item_for_uint!(u16);
+impl_tlv8_item_for_uint!(u32);
+impl_tlv8_item_for_uint!(u64);
+
+impl Tlv8Item for bool {
+    fn to_bytes(&self) -> Vec<u8> { vec![*self as u8] }
+
+    fn from_bytes(bytes: &Bytes) -> Result<Self> { Ok(u8::from_bytes(bytes)? != 0) }
+}
+
+impl Tlv8Item for f32 {
+    fn to_bytes(&self) -> Vec<u8> { self.to_le_bytes().to_vec() }
+
+    fn from_bytes(bytes: &Bytes) -> Result<Self> {
+        let mut le_bytes = [0; 4];
+        if bytes.len() != le_bytes.len() {
+            return Err(Error::InvalidTlv8(format!("expected a float of 4 bytes, got {} bytes", bytes.len())));
+        }
+        le_bytes.copy_from_slice(bytes);
+
+        Ok(f32::from_le_bytes(le_bytes))
+    }
+}
+
+impl Tlv8Item for String {
+    fn to_bytes(&self) -> Vec<u8> { self.as_bytes().to_vec() }
+
+    fn from_bytes(bytes: &Bytes) -> Result<Self> { Ok(std::str::from_utf8(bytes)?.to_owned()) }
+}
+
+impl Tlv8Item for Vec<u8> {
+    fn to_bytes(&self) -> Vec<u8> { self.clone() }
+
+    fn from_bytes(bytes: &Bytes) -> Result<Self> { Ok(bytes.to_vec()) }
+}
+
+impl<T: Tlv8Value> Tlv8Item for T {
+    fn to_bytes(&self) -> Vec<u8> { self.encode() }
+
+    fn from_bytes(bytes: &Bytes) -> Result<Self> { T::decode(bytes.clone()) }
+}
+
+/// Raw TLV8 payload of a characteristic without a documented structure, represented as its items in order.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct Tlv8Items(pub Vec<(u8, Vec<u8>)>);
+
+impl Tlv8Value for Tlv8Items {
+    fn encode(&self) -> Vec<u8> {
+        let mut writer = Tlv8Writer::new();
+        for (tlv_type, value) in &self.0 {
+            writer.raw(*tlv_type, value);
+        }
+
+        writer.into_bytes()
+    }
+
+    fn decode(bytes: Bytes) -> Result<Self> {
+        let items = Tlv8Reader::new(bytes)?
+            .payload
+            .into_items()
+            .into_iter()
+            .map(|item| (item.tlv_type, item.value.to_vec()))
+            .collect();
+
+        Ok(Self(items))
+    }
+}
+
+/// Builds the TLV8 representation of a payload item by item.
+#[derive(Debug, Default)]
+pub struct Tlv8Writer {
+    bytes: Vec<u8>,
+}
+
+impl Tlv8Writer {
+    /// Creates a new empty [`Tlv8Writer`](Tlv8Writer).
+    pub fn new() -> Self { Self::default() }
+
+    /// Writes a single item.
+    pub fn item<T: Tlv8Item>(&mut self, tlv_type: u8, value: &T) { self.raw(tlv_type, &value.to_bytes()); }
+
+    /// Writes a single item if the value is present.
+    pub fn optional<T: Tlv8Item>(&mut self, tlv_type: u8, value: &Option<T>) {
+        if let Some(value) = value {
+            self.item(tlv_type, value);
+        }
+    }
+
+    /// Writes one item per value, separated by empty items of the `separator` type.
+    pub fn list<T: Tlv8Item>(&mut self, tlv_type: u8, separator: u8, values: &[T]) {
+        for (i, value) in values.iter().enumerate() {
+            if i > 0 {
+                self.raw(separator, &[]);
+            }
+            self.item(tlv_type, value);
+        }
+    }
+
+    /// Writes the items of each value inline, one value after the other. If a `separator` is given, the values are
+    /// separated by empty items of its type.
+    pub fn entries<T: Tlv8Value>(&mut self, separator: Option<u8>, values: &[T]) {
+        for (i, value) in values.iter().enumerate() {
+            if let (Some(separator), true) = (separator, i > 0) {
+                self.raw(separator, &[]);
+            }
+            self.bytes.extend(value.encode());
+        }
+    }
+
+    /// Writes a single item with a raw value, split into fragments if necessary.
+    pub fn raw(&mut self, tlv_type: u8, value: &[u8]) {
+        if value.is_empty() {
+            self.bytes.extend(&[tlv_type, 0]);
+        }
+        for fragment in value.chunks(MAX_FRAGMENT_LEN) {
+            self.bytes.extend(&[tlv_type, fragment.len() as u8]);
+            self.bytes.extend(fragment);
+        }
+    }
+
+    /// Returns the TLV8 representation of the written items.
+    pub fn into_bytes(self) -> Vec<u8> { self.bytes }
+}
+
+/// Reads the items of a TLV8 payload. Fragmented items are merged and the order of the items is preserved.
+#[derive(Debug)]
+pub struct Tlv8Reader {
+    payload: tlv::Payload,
+}
+
+impl Tlv8Reader {
+    /// Parses the items of a TLV8 payload. Values of unfragmented items are slices of the given bytes.
+    pub fn new(bytes: Bytes) -> Result<Self> {
+        Ok(Self {
+            payload: tlv::decode(bytes)?,
+        })
+    }
+
+    /// Reads the first item of the given type.
+    pub fn item<T: Tlv8Item>(&self, tlv_type: u8) -> Result<T> {
+        self.optional(tlv_type)?
+            .ok_or_else(|| Error::InvalidTlv8(format!("missing item {:#04x}", tlv_type)))
+    }
+
+    /// Reads the first item of the given type if it's present.
+    pub fn optional<T: Tlv8Item>(&self, tlv_type: u8) -> Result<Option<T>> {
+        self.payload.get(tlv_type).map(|value| T::from_bytes(value)).transpose()
+    }
+
+    /// Reads all items of the given type in order.
+    pub fn list<T: Tlv8Item>(&self, tlv_type: u8) -> Result<Vec<T>> {
+        self.payload.get_all(tlv_type).map(|value| T::from_bytes(value)).collect()
+    }
+
+    /// Reads values whose items are written inline, one value after the other. If a `separator` is given, the values
+    /// are split at its items and every part containing an item of the given type is a value. Otherwise, every item of
+    /// the given type starts a new value.
+    pub fn entries<T: Tlv8Value>(&self, tlv_type: u8, separator: Option<u8>) -> Result<Vec<T>> {
+        if let Some(separator) = separator {
+            return self
+                .payload
+                .split(separator)
+                .filter(|entry| entry.get(tlv_type).is_some())
+                .map(|entry| {
+                    let mut writer = Tlv8Writer::new();
+                    for item in entry.items() {
+                        writer.raw(item.tlv_type, &item.value);
+                    }
+                    T::decode(writer.into_bytes().into())
+                })
+                .collect();
+        }
+
+        let mut entries = Vec::new();
+        let mut entry: Option<Tlv8Writer> = None;
+        for item in self.payload.items() {
+            if item.tlv_type == tlv_type {
+                if let Some(writer) = entry.take() {
+                    entries.push(T::decode(writer.into_bytes().into())?);
+                }
+                entry = Some(Tlv8Writer::new());
+            }
+            if let Some(ref mut writer) = entry {
+                writer.raw(item.tlv_type, &item.value);
+            }
+        }
+        if let Some(writer) = entry {
+            entries.push(T::decode(writer.into_bytes().into())?);
+        }
+
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_order() {
+        let display_order = DisplayOrder(vec![1, 2, 3]);
+        let bytes = display_order.encode();
+
+        assert_eq!(bytes, vec![
+            0x01, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x04, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x04,
+            0x03, 0x00, 0x00, 0x00,
+        ]);
+        assert_eq!(DisplayOrder::decode(bytes.into()).unwrap(), display_order);
+
+        // the default value of the characteristic is an empty display order
+        assert_eq!(DisplayOrder::decode(Bytes::new()).unwrap(), DisplayOrder::default());
+    }
+
+    #[test]
+    fn test_nested_round_trip() {
+        let configuration = SupportedVideoStreamConfiguration {
+            video_codec_configurations: vec![VideoCodecConfiguration {
+                codec_type: 0,
+                codec_parameters: VideoCodecParameters {
+                    profile_ids: vec![0, 1, 2],
+                    levels: vec![0, 1, 2],
+                    packetization_modes: vec![0],
+                    cvo_enabled: None,
+                    cvo_id: None,
+                },
+                attributes: vec![
+                    VideoAttributes {
+                        image_width: 1920,
+                        image_height: 1080,
+                        frame_rate: 30,
+                    },
+                    VideoAttributes {
+                        image_width: 1280,
+                        image_height: 720,
+                        frame_rate: 30,
+                    },
+                ],
+            }],
+        };
+
+        assert_eq!(
+            SupportedVideoStreamConfiguration::decode(configuration.encode().into()).unwrap(),
+            configuration
+        );
+    }
+
+    #[test]
+    fn test_value_transition_round_trip() {
+        let control = CharacteristicValueTransitionControl {
+            read_configuration: None,
+            update_configuration: Some(UpdateValueTransitionConfiguration {
+                configurations: vec![ValueTransitionConfiguration {
+                    characteristic_iid: 10,
+                    transition_parameters: Some(ValueTransitionParameters {
+                        transition_id: vec![0xAB; 16],
+                        start_time: 1_000,
+                    }),
+                    transition_curve_configuration: Some(TransitionCurveConfiguration {
+                        entries: vec![
+                            TransitionEntry {
+                                adjustment_factor: 0.5,
+                                value: 250.0,
+                                transition_offset: 0,
+                                duration: None,
+                            },
+                            TransitionEntry {
+                                adjustment_factor: 0.5,
+                                value: 300.0,
+                                transition_offset: 60_000,
+                                duration: Some(30_000),
+                            },
+                        ],
+                        adjustment_characteristic_iid: 9,
+                        adjustment_multiplier_range: TransitionAdjustmentMultiplierRange {
+                            minimum_adjustment_multiplier: 1,
+                            maximum_adjustment_multiplier: 100,
+                        },
+                    }),
+                    update_interval: Some(60_000),
+                    notify_interval_threshold: None,
+                }],
+            }),
+        };
+
+        assert_eq!(
+            CharacteristicValueTransitionControl::decode(control.encode().into()).unwrap(),
+            control
+        );
+
+        // a configuration with only the instance ID stops the transition
+        let bytes = vec![0x02, 0x05, 0x01, 0x03, 0x01, 0x01, 0x0A];
+        let stop = CharacteristicValueTransitionControl::decode(bytes.into()).unwrap();
+        let configuration = &stop.update_configuration.unwrap().configurations[0];
+        assert_eq!(configuration.characteristic_iid, 10);
+        assert_eq!(configuration.transition_parameters, None);
+    }
+
+    #[test]
+    fn test_entries() {
+        let configuration = SupportedButtonConfiguration {
+            buttons: vec![
+                SupportedButton {
+                    button_id: 1,
+                    button_type: 1,
+                },
+                SupportedButton {
+                    button_id: 2,
+                    button_type: 2,
+                },
+            ],
+        };
+        let bytes = configuration.encode();
+
+        assert_eq!(bytes, vec![
+            0x01, 0x01, 0x01, 0x02, 0x02, 0x01, 0x00, 0x01, 0x01, 0x02, 0x02, 0x02, 0x02, 0x00,
+        ]);
+        assert_eq!(SupportedButtonConfiguration::decode(bytes.into()).unwrap(), configuration);
+    }
+
+    #[test]
+    fn test_fragments() {
+        let salt = (0..300).map(|i| i as u8).collect::<Vec<u8>>();
+        let mut writer = Tlv8Writer::new();
+        writer.item(0x03, &salt);
+        let bytes = writer.into_bytes();
+
+        assert_eq!(bytes.len(), 304);
+        assert_eq!(Tlv8Reader::new(bytes.into()).unwrap().item::<Vec<u8>>(0x03).unwrap(), salt);
+    }
+
+    #[test]
+    fn test_invalid_payloads() {
+        assert!(Tlv8Reader::new(Bytes::from_static(&[0x01])).is_err());
+        assert!(Tlv8Reader::new(Bytes::from_static(&[0x01, 0x04, 0x01])).is_err());
+        assert!(DisplayOrder::decode(Bytes::from_static(&[0x01, 0x05, 0x01, 0x02, 0x03, 0x04, 0x05])).is_err());
+        assert!(StreamingStatus::decode(Bytes::new()).is_err());
+    }
+}
@@ -1,11 +1,26 @@
 use ed25519_dalek::Keypair as Ed25519Keypair;
 //use eui48::MacAddress;
+use log::{error, warn};
 use macaddr::MacAddr6 as MacAddress;
 use rand::{rngs::OsRng, Rng};
-use serde::{Deserialize, Serialize};
-use std::net::IpAddr;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    fmt,
+    io::ErrorKind,
+    net::{IpAddr, Ipv4Addr},
+    str::FromStr,
+    time::Duration,
+};
 
-use crate::{accessory::AccessoryCategory, BonjourFeatureFlag, BonjourStatusFlag, Pin};
+use crate::{
+    accessory::AccessoryCategory,
+    setup::{self, SetupFlag},
+    storage::Storage,
+    BonjourFeatureFlag,
+    BonjourStatusFlag,
+    Error,
+    Pin,
+};
 
 /// The `Config` struct is used to store configuration options for the HomeKit Accessory Server.
 ///
@@ -24,11 +39,26 @@ use crate::{accessory::AccessoryCategory, BonjourFeatureFlag, BonjourStatusFlag,
 /// ```
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
-    /// Socket IP address to serve on. Defaults to the IP of the system's first non-loopback network interface.
-    pub host: IpAddr,
+    /// Socket IP addresses to serve on. Defaults to the IPs picked by the `interface_policy`, which is the IP of the
+    /// system's first non-loopback network interface by default.
+    ///
+    /// The unspecified addresses `::` and `0.0.0.0` serve on every interface. On most systems, `::` is dual-stack and
+    /// accepts IPv4 connections as well, so it can't be combined with `0.0.0.0` on the same port. The mDNS responder
+    /// only announces the accessory on the interfaces these addresses belong to, unless one of them is unspecified.
+    #[serde(alias = "host", deserialize_with = "deserialize_hosts")]
+    pub hosts: Vec<IpAddr>,
+    /// Policy used to pick the network interfaces to serve on. Defaults to `InterfacePolicy::FirstNonLoopback`.
+    #[serde(default)]
+    pub interface_policy: InterfacePolicy,
+    /// Interval in which a running server checks the network interfaces for address changes. On a change, the `hosts`
+    /// are redetermined according to the `interface_policy`, the TCP listeners are rebound, the mDNS records are
+    /// refreshed and the config is persisted to the storage. Defaults to `None`, which disables the watcher.
+    #[serde(default)]
+    pub interface_watch_interval: Option<Duration>,
     /// Port to serve on. Defaults to `32000`.
     pub port: u16,
-    /// 8 digit pin used for pairing. Defaults to `11122333`.
+    /// 8 digit pin used for pairing. Generated randomly if not specified. Must persist across reboots, so a `Config`
+    /// using the random default has to be saved to the storage, e.g. via `Config::load_or_create`.
     ///
     /// The following pins are considered too easy and are therefore not allowed:
     /// - `12345678`
@@ -50,6 +80,12 @@ pub struct Config {
     /// Pairing Identifier. Must be a unique random number generated at every factory reset and must persist across
     /// reboots.
     pub device_id: MacAddress, // Bonjour: id
+    /// Setup ID of the accessory. Consists of 4 characters from `0-9` and `A-Z` and is generated randomly if not
+    /// specified. It's encoded into the setup URI and used to derive the setup hash, which lets controllers match a
+    /// scanned setup code to the announced accessory. Like the pin, it should be printed on the accessory and
+    /// therefore persists across factory resets.
+    #[serde(default = "setup::generate_setup_id")]
+    pub setup_id: String, // Bonjour: sh
     ///
     pub device_ed25519_keypair: Ed25519Keypair,
     /// Current configuration number. Is updated when an accessory, service, or characteristic is added or removed on
@@ -64,28 +100,117 @@ pub struct Config {
     /// Bonjour Status Flag. Defaults to `StatusFlag::NotPaired` and is changed to `StatusFlag::Zero` after a
     /// successful pairing.
     pub status_flag: BonjourStatusFlag, // Bonjour: sf
-    /// Bonjour Feature Flag. Currently only used to indicate MFi compliance.
+    /// Bonjour Feature Flag announced while no [`Authenticator`](crate::authenticator::Authenticator) is set. Once
+    /// one is set, the flag is derived from it instead.
     pub feature_flag: BonjourFeatureFlag, // Bonjour: ff
     /// Optional maximum number of paired controllers.
     pub max_peers: Option<usize>,
+    /// Number of verified sessions cached for pair resume, which lets controllers reconnect without a full pair
+    /// verify. Defaults to `8`. A size of `0` disables pair resume. Read once when the server is created.
+    #[serde(default = "default_session_cache_size")]
+    pub session_cache_size: usize,
+    /// Time for which a cached session can be resumed. Defaults to 1 hour.
+    #[serde(default = "default_session_cache_ttl")]
+    pub session_cache_ttl: Duration,
+    /// Maximum number of simultaneous connections. Once it's reached, the oldest connection that isn't verified yet is
+    /// closed to make room for a new one. If every connection is verified, the new connection is refused instead.
+    /// Defaults to `16`. The HAP specification requires accessories to support at least 8 simultaneous connections.
+    #[serde(default = "default_max_connections")]
+    pub max_connections: usize,
+    /// Time a connection has to finish pair verify in before it's closed. Pair setup has to finish in this time as
+    /// well if it's performed on the connection. Defaults to 2 minutes. `None` keeps unverified connections open.
+    #[serde(default = "default_pair_verify_timeout")]
+    pub pair_verify_timeout: Option<Duration>,
+    /// Time after which a connection the controller sent no request on is closed. Event notifications sent to the
+    /// controller don't count as activity. Defaults to `None`, which keeps idle connections open.
+    #[serde(default)]
+    pub idle_timeout: Option<Duration>,
+    /// Window in which the value changes of characteristics a connection is subscribed to are coalesced into a single
+    /// event message, keeping only the latest value of every characteristic. Defaults to `None`, which sends changes
+    /// right away and only coalesces the ones that queued up in the meantime.
+    #[serde(default)]
+    pub event_coalescing_window: Option<Duration>,
 }
 
 impl Config {
-    /// Redetermines the `host` field to the IP of the system's first non-loopback network interface.
-    pub fn redetermine_local_ip(&mut self) { self.host = get_local_ip(); }
+    /// Loads the `Config` from a `Storage`. On first boot, when there is no saved `Config` yet, the `Config` returned by
+    /// `create` is saved and returned instead. Leaving the `pin` and `setup_id` fields to their defaults there gives
+    /// every device a unique, persisted pairing code.
+    ///
+    /// Errors other than a missing `Config` are returned rather than replacing the saved `Config`, which would change
+    /// the pin and device ID of an accessory that's possibly already paired.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use hap::{accessory::AccessoryCategory, storage::FileStorage, Config, Result};
+    ///
+    /// async fn config() -> Result<Config> {
+    ///     let mut storage = FileStorage::current_dir().await?;
+    ///
+    ///     let config = Config::load_or_create(&mut storage, || Config {
+    ///         name: "Acme Lightbulb".into(),
+    ///         category: AccessoryCategory::Lightbulb,
+    ///         ..Default::default()
+    ///     })
+    ///     .await?;
+    ///
+    ///     println!("pairing code: {}", config.pin);
+    ///
+    ///     Ok(config)
+    /// }
+    /// ```
+    pub async fn load_or_create<S: Storage + ?Sized, F: FnOnce() -> Config>(
+        storage: &mut S,
+        create: F,
+    ) -> crate::Result<Config> {
+        match storage.load_config().await {
+            Ok(config) => Ok(config),
+            Err(Error::Io(e)) if e.kind() == ErrorKind::NotFound => {
+                let config = create();
+                storage.save_config(&config).await?;
+
+                Ok(config)
+            },
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Redetermines the `hosts` field according to the `interface_policy`. Does nothing if the policy is
+    /// `InterfacePolicy::Manual`.
+    pub fn redetermine_local_ip(&mut self) {
+        if self.interface_policy != InterfacePolicy::Manual {
+            self.hosts = get_local_ips(&self.interface_policy);
+        }
+    }
+
+    /// Regenerates the device ID and the Ed25519 keypair and resets the Bonjour status flag to `NotPaired`, as
+    /// required on a factory reset.
+    pub(crate) fn reset_device_identity(&mut self) {
+        self.device_id = generate_random_mac_address();
+        self.device_ed25519_keypair = generate_ed25519_keypair();
+        self.status_flag = BonjourStatusFlag::NotPaired;
+    }
+
+    /// Returns the `X-HM://` setup URI of the accessory, as encoded by HomeKit QR codes and NFC tags.
+    pub fn setup_uri(&self) -> crate::Result<String> {
+        setup::setup_uri(self.category, &self.pin, &[SetupFlag::Ip], &self.setup_id)
+    }
 
-    /// Derives mDNS TXT records from the `Config`.
-    pub(crate) fn txt_records(&self) -> [String; 8] {
+    /// Returns the setup hash of the accessory published in the `sh` TXT record.
+    pub fn setup_hash(&self) -> String { setup::setup_hash(&self.setup_id, &self.device_id) }
+
+    /// Derives mDNS TXT records from the `Config`, announcing the given Bonjour Feature Flag.
+    pub(crate) fn txt_records(&self, feature_flag: BonjourFeatureFlag) -> [String; 9] {
         [
             format!("c#={}", self.configuration_number),
-            format!("ff={}", self.feature_flag as u8),
+            format!("ff={}", feature_flag as u8),
             format!("id={}", self.device_id.to_string()),
             format!("md={}", self.name),
             format!("pv={}", self.protocol_version),
             format!("s#={}", self.state_number),
             format!("sf={}", self.status_flag as u8),
             format!("ci={}", self.category as u8),
-            // format!("sh={}", self.setup_hash as u8), setup hash seems to be still undocumented
+            format!("sh={}", self.setup_hash()),
         ]
     }
 }
@@ -93,11 +218,14 @@ impl Config {
 impl Default for Config {
     fn default() -> Config {
         Config {
-            host: get_local_ip(),
+            hosts: get_local_ips(&InterfacePolicy::default()),
+            interface_policy: InterfacePolicy::default(),
+            interface_watch_interval: None,
             port: 32000,
-            pin: Pin::new([1, 1, 1, 2, 2, 3, 3, 3]).unwrap(),
+            pin: Pin::random(),
             name: "Accessory".into(),
             device_id: generate_random_mac_address(),
+            setup_id: setup::generate_setup_id(),
             device_ed25519_keypair: generate_ed25519_keypair(),
             configuration_number: 1,
             state_number: 1,
@@ -106,10 +234,24 @@ impl Default for Config {
             status_flag: BonjourStatusFlag::NotPaired,
             feature_flag: BonjourFeatureFlag::Zero,
             max_peers: None,
+            session_cache_size: default_session_cache_size(),
+            session_cache_ttl: default_session_cache_ttl(),
+            max_connections: default_max_connections(),
+            pair_verify_timeout: default_pair_verify_timeout(),
+            idle_timeout: None,
+            event_coalescing_window: None,
         }
     }
 }
 
+fn default_session_cache_size() -> usize { 8 }
+
+fn default_session_cache_ttl() -> Duration { Duration::from_secs(60 * 60) }
+
+fn default_max_connections() -> usize { 16 }
+
+fn default_pair_verify_timeout() -> Option<Duration> { Some(Duration::from_secs(2 * 60)) }
+
 /// Generates a random MAC address.
 fn generate_random_mac_address() -> MacAddress {
     let mut csprng = OsRng {};
@@ -123,12 +265,174 @@ fn generate_ed25519_keypair() -> Ed25519Keypair {
     Ed25519Keypair::generate(&mut csprng)
 }
 
-/// Returns the IP of the system's first non-loopback network interface or defaults to `127.0.0.1`.
-fn get_local_ip() -> IpAddr {
-    for iface in get_if_addrs::get_if_addrs().unwrap() {
-        if !iface.is_loopback() {
-            return iface.ip();
+/// Policy used to pick the network interfaces the server binds on and announces the accessory on.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InterfacePolicy {
+    /// The IP of the system's first non-loopback network interface.
+    #[default]
+    FirstNonLoopback,
+    /// The IPs of all of the system's non-loopback network interfaces.
+    AllNonLoopback,
+    /// The IPs of the network interfaces with the given names, e.g. `eth0`.
+    Names(Vec<String>),
+    /// The IPs of the network interfaces that are within one of the given subnets.
+    Subnets(Vec<Subnet>),
+    /// The `hosts` field of the `Config` is set manually and never redetermined.
+    Manual,
+}
+
+impl InterfacePolicy {
+    /// Returns whether a network interface is picked by the policy.
+    fn matches(&self, iface: &if_addrs::Interface) -> bool {
+        match self {
+            InterfacePolicy::FirstNonLoopback | InterfacePolicy::AllNonLoopback => !iface.is_loopback(),
+            InterfacePolicy::Names(names) => names.iter().any(|name| name == &iface.name),
+            InterfacePolicy::Subnets(subnets) => subnets.iter().any(|subnet| subnet.contains(&iface.ip())),
+            InterfacePolicy::Manual => false,
+        }
+    }
+}
+
+/// An IP subnet in CIDR notation, e.g. `192.168.1.0/24` or `fd00::/8`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Subnet {
+    /// Network address of the subnet.
+    pub addr: IpAddr,
+    /// Length of the network prefix in bits.
+    pub prefix_len: u8,
+}
+
+impl Subnet {
+    /// Returns whether an IP is within the subnet.
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(addr), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix_len.min(32) as u32).unwrap_or(0);
+                u32::from(addr) & mask == u32::from(*ip) & mask
+            },
+            (IpAddr::V6(addr), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix_len.min(128) as u32).unwrap_or(0);
+                u128::from(addr) & mask == u128::from(*ip) & mask
+            },
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Subnet {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidSubnet(s.to_string());
+
+        let (addr, prefix_len) = s.split_once('/').ok_or_else(invalid)?;
+        let addr: IpAddr = addr.parse().map_err(|_| invalid())?;
+        let prefix_len: u8 = prefix_len.parse().map_err(|_| invalid())?;
+        let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+        if prefix_len > max_prefix_len {
+            return Err(invalid());
+        }
+
+        Ok(Subnet { addr, prefix_len })
+    }
+}
+
+impl fmt::Display for Subnet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "{}/{}", self.addr, self.prefix_len) }
+}
+
+impl Serialize for Subnet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Subnet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Deserializes the `hosts` field from either a list of IPs or a single IP, as stored by earlier versions.
+fn deserialize_hosts<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<IpAddr>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Hosts {
+        Single(IpAddr),
+        Multiple(Vec<IpAddr>),
+    }
+
+    Ok(match Hosts::deserialize(deserializer)? {
+        Hosts::Single(host) => vec![host],
+        Hosts::Multiple(hosts) => hosts,
+    })
+}
+
+/// Returns the IPs of the system's network interfaces picked by an `InterfacePolicy` or defaults to `127.0.0.1`.
+fn get_local_ips(policy: &InterfacePolicy) -> Vec<IpAddr> {
+    let ifaces = match if_addrs::get_if_addrs() {
+        Ok(ifaces) => ifaces,
+        Err(e) => {
+            error!("error determining the network interfaces: {:?}", e);
+            Vec::new()
+        },
+    };
+
+    let mut ips = Vec::new();
+    for iface in ifaces.iter().filter(|iface| policy.matches(iface)) {
+        if !ips.contains(&iface.ip()) {
+            ips.push(iface.ip());
         }
+        if policy == &InterfacePolicy::FirstNonLoopback {
+            break;
+        }
+    }
+
+    if ips.is_empty() {
+        warn!("no network interface matches {:?}; defaulting to 127.0.0.1", policy);
+        ips.push(IpAddr::V4(Ipv4Addr::LOCALHOST));
+    }
+
+    ips
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subnet() {
+        let subnet: Subnet = "192.168.1.0/24".parse().unwrap();
+        assert!(subnet.contains(&"192.168.1.42".parse().unwrap()));
+        assert!(!subnet.contains(&"192.168.2.42".parse().unwrap()));
+        assert!(!subnet.contains(&"fd00::1".parse().unwrap()));
+        assert_eq!(subnet.to_string(), "192.168.1.0/24");
+
+        let subnet: Subnet = "fd00::/8".parse().unwrap();
+        assert!(subnet.contains(&"fd12:3456::1".parse().unwrap()));
+        assert!(!subnet.contains(&"fe80::1".parse().unwrap()));
+
+        let subnet: Subnet = "0.0.0.0/0".parse().unwrap();
+        assert!(subnet.contains(&"10.0.0.1".parse().unwrap()));
+
+        assert!("192.168.1.0".parse::<Subnet>().is_err());
+        assert!("192.168.1.0/33".parse::<Subnet>().is_err());
+        assert!("fd00::/129".parse::<Subnet>().is_err());
+        assert!("eth0/24".parse::<Subnet>().is_err());
+    }
+
+    #[test]
+    fn test_deserialize_hosts() {
+        let config = Config::default();
+        let mut json = serde_json::to_value(&config).unwrap();
+        let map = json.as_object_mut().unwrap();
+        map.remove("hosts");
+        map.remove("interface_policy");
+        map.insert("host".into(), serde_json::json!("10.0.0.2"));
+
+        let config: Config = serde_json::from_value(json).unwrap();
+        assert_eq!(config.hosts, vec!["10.0.0.2".parse::<IpAddr>().unwrap()]);
+        assert_eq!(config.interface_policy, InterfacePolicy::FirstNonLoopback);
     }
-    "127.0.0.1".parse().unwrap()
 }
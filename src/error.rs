@@ -19,6 +19,14 @@ pub enum Error {
     ValueBelowMinValue,
     #[error("The value is above the `max_value` of the characteristic.")]
     ValueAboveMaxValue,
+    #[error("The value is not one of the `valid_values` of the characteristic.")]
+    ValueNotInValidValues,
+    #[error("The value is outside of the `valid_values_range` of the characteristic.")]
+    ValueOutsideValidValuesRange,
+    #[error("The value is longer than the `max_len` of the characteristic.")]
+    ValueAboveMaxLen,
+    #[error("The value is longer than the `max_data_len` of the characteristic.")]
+    ValueAboveMaxDataLen,
     #[error("The selected accessory is not present on the server.")]
     AccessoryNotFound,
     #[error("The provided accessory was already added to the server.")]
@@ -27,6 +35,12 @@ pub enum Error {
         "The provided value has an invalid data type for the characteristic. The characteristic's format is {0:?}."
     )]
     InvalidValue(Format),
+    #[error("Invalid setup ID `{0}`. Setup IDs consist of 4 characters from `0-9` and `A-Z`.")]
+    InvalidSetupId(String),
+    #[error("Invalid subnet `{0}`. Subnets have to be in CIDR notation, e.g. `192.168.1.0/24`.")]
+    InvalidSubnet(String),
+    #[error("Invalid TLV8 payload: {0}")]
+    InvalidTlv8(String),
     #[error("Invalid HapType string value: `{0}`.")]
     InvalidHapTypeString(String),
     #[error("Error on value read: {0}")]
@@ -35,6 +49,8 @@ pub enum Error {
     ValueOnUpdate(Box<dyn std::error::Error + Send + Sync>),
     #[error("Error interacting with the storage.")]
     Storage,
+    #[error("The authentication key is invalid.")]
+    InvalidAuthenticationKey,
 
     // converted errors
     #[error("IO Error: {0}")]
@@ -61,6 +77,12 @@ pub enum Error {
     ParseInt(#[from] num::ParseIntError),
     #[error("MPSC Send Error: {0}")]
     MpscSend(#[from] mpsc::SendError<()>),
+    #[cfg(feature = "qr")]
+    #[error("QR Code Error: {0}")]
+    QrCode(#[from] qrcode::types::QrError),
+    #[cfg(feature = "qr")]
+    #[error("Image Error: {0}")]
+    Image(#[from] image::ImageError),
 }
 
 impl From<aead::Error> for Error {
@@ -1,31 +1,109 @@
-use futures::future::{join_all, BoxFuture};
-use log::debug;
+use futures::stream::{self, BoxStream, StreamExt};
+use log::{debug, warn};
 use serde_json::Value;
-use std::fmt::Debug;
+use std::net::SocketAddr;
+use tokio::sync::broadcast::{self, error::RecvError};
 use uuid::Uuid;
 
-#[derive(Debug)]
+/// Number of events buffered for every subscriber. A subscriber falling further behind misses the oldest events.
+pub const EVENT_CAPACITY: usize = 256;
+
+/// A stream of the events emitted by the server. Dropping it unsubscribes from the events.
+pub type EventStream = BoxStream<'static, Event>;
+
+/// An event emitted by the server.
+#[derive(Debug, Clone, PartialEq)]
 pub enum Event {
+    /// The server started serving on its TCP listeners.
+    ServerStarted,
+    /// The server shut down.
+    ServerStopped,
+    /// A controller connected to the server.
+    ConnectionOpened { peer_addr: SocketAddr },
+    /// A connection to a controller was closed.
+    ConnectionClosed { peer_addr: SocketAddr },
+    /// A controller was paired.
     ControllerPaired { id: Uuid },
+    /// The pairing of a controller was removed.
     ControllerUnpaired { id: Uuid },
+    /// The value of a characteristic with the `Events` permission changed.
     CharacteristicValueChanged { aid: u64, iid: u64, value: Value },
 }
 
-#[derive(Default)]
+/// Broadcasts the events of the server to its subscribers via a bounded channel. Emitting an event never waits for the
+/// subscribers, so a slow subscriber can't stall the server.
+#[derive(Debug)]
 pub struct EventEmitter {
-    listeners: Vec<Box<dyn (Fn(&Event) -> BoxFuture<()>) + Send + Sync>>,
+    sender: broadcast::Sender<Event>,
 }
 
 impl EventEmitter {
-    pub fn new() -> EventEmitter { EventEmitter { listeners: vec![] } }
-
-    pub fn add_listener(&mut self, listener: Box<dyn (Fn(&Event) -> BoxFuture<()>) + Send + Sync>) {
-        self.listeners.push(listener);
+    pub(crate) fn new() -> EventEmitter {
+        let (sender, _) = broadcast::channel(EVENT_CAPACITY);
+        EventEmitter { sender }
     }
 
-    pub async fn emit(&self, event: &Event) {
+    /// Emits an event to every current subscriber.
+    pub(crate) fn emit(&self, event: Event) {
         debug!("emitting event: {:?}", event);
 
-        join_all(self.listeners.iter().map(|listener| listener(&event))).await;
+        // sending only fails if there are no subscribers
+        self.sender.send(event).ok();
+    }
+
+    /// Returns a receiver of the events emitted from now on, which reports missed events instead of skipping them.
+    pub(crate) fn receiver(&self) -> broadcast::Receiver<Event> { self.sender.subscribe() }
+
+    /// Subscribes to the events emitted from now on. Events a subscriber misses by falling more than
+    /// [`EVENT_CAPACITY`](EVENT_CAPACITY) events behind are skipped.
+    pub fn subscribe(&self) -> EventStream {
+        stream::unfold(self.receiver(), |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((event, receiver)),
+                    Err(RecvError::Lagged(count)) => warn!("event subscriber fell behind; skipping {} events", count),
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_subscribe() {
+        let event_emitter = EventEmitter::new();
+        let id = Uuid::parse_str("bc158b86-cabf-432d-aee4-422ef0e3f1d5").unwrap();
+
+        // events emitted without subscribers are dropped
+        event_emitter.emit(Event::ServerStarted);
+
+        let mut events = event_emitter.subscribe();
+        event_emitter.emit(Event::ControllerPaired { id });
+        assert_eq!(events.next().await, Some(Event::ControllerPaired { id }));
+
+        // subscribers falling behind skip the oldest events
+        for iid in 0..EVENT_CAPACITY as u64 + 1 {
+            event_emitter.emit(Event::CharacteristicValueChanged {
+                aid: 1,
+                iid,
+                value: Value::Null,
+            });
+        }
+        assert_eq!(
+            events.next().await,
+            Some(Event::CharacteristicValueChanged {
+                aid: 1,
+                iid: 1,
+                value: Value::Null,
+            })
+        );
+
+        drop(event_emitter);
+        assert_eq!(events.skip(EVENT_CAPACITY - 1).next().await, None);
     }
 }
@@ -1,10 +1,14 @@
+// lets the code generated by `hap-derive` refer to `::hap` from within this crate as well
+extern crate self as hap;
+
+pub use bytes;
 pub use ed25519_dalek::Keypair as Ed25519Keypair;
 pub use futures;
 pub use macaddr::MacAddr6 as MacAddress;
 pub use serde_json;
 
 pub use crate::{
-    config::Config,
+    config::{Config, InterfacePolicy, Subnet},
     error::Error,
     hap_type::HapType,
     pin::Pin,
@@ -13,7 +17,6 @@ pub use crate::{
 
 mod config;
 mod error;
-mod event;
 mod hap_type;
 mod pin;
 mod pointer;
@@ -22,12 +25,20 @@ mod transport;
 
 /// Definitions of HomeKit accessories.
 pub mod accessory;
+/// Representations of the means an accessory can authenticate itself with during pair setup.
+pub mod authenticator;
+/// Representations of service discovery backends announcing the server on the network.
+pub mod advertiser;
 /// Definitions of HomeKit characteristics.
 pub mod characteristic;
+/// Events emitted by the server, which applications can subscribe to.
+pub mod event;
 /// Representation of paired controllers.
 pub mod pairing;
 /// The HomeKit Accessory Server implementation.
 pub mod server;
+/// Generation of setup IDs, setup URIs and QR codes used for pairing.
+pub mod setup;
 /// Definitions of HomeKit services.
 pub mod service;
 /// Representations of persistent storage.
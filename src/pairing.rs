@@ -1,7 +1,18 @@
 use serde::{Deserialize, Serialize};
+use std::{
+    io::ErrorKind,
+    time::{Duration, SystemTime},
+};
 use uuid::Uuid;
 
-use crate::{Error, Result};
+use crate::{storage::Storage, Error, Result};
+
+/// Maximum number of unsuccessful pair setup attempts. Once it's reached, pair setup is refused until the attempts are
+/// reset, e.g. by a factory reset.
+pub const MAX_PAIR_SETUP_ATTEMPTS: u8 = 100;
+
+/// Upper bound of the backoff after an unsuccessful pair setup attempt.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(3600);
 
 /// A [`Pairing`](Pairing) represents a paired controller.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -34,10 +45,82 @@ impl Pairing {
     }
 }
 
+/// [`PairSetupAttempts`](PairSetupAttempts) keep track of the unsuccessful pair setup attempts of the accessory.
+/// They're persisted to the storage, so neither reconnecting nor restarting the accessory lifts a backoff or the
+/// refusal of pair setup after [`MAX_PAIR_SETUP_ATTEMPTS`](MAX_PAIR_SETUP_ATTEMPTS) attempts.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PairSetupAttempts {
+    /// Number of unsuccessful attempts since the last successful pairing or reset.
+    pub count: u8,
+    /// Time of the last unsuccessful attempt.
+    pub last_failure: Option<SystemTime>,
+}
+
+impl PairSetupAttempts {
+    /// Loads the [`PairSetupAttempts`](PairSetupAttempts) from a `Storage`. If none were saved yet, no attempts are
+    /// returned.
+    pub async fn load<S: Storage + ?Sized>(storage: &S) -> Result<PairSetupAttempts> {
+        match storage.load_pair_setup_attempts().await {
+            Ok(attempts) => Ok(attempts),
+            Err(Error::Io(e)) if e.kind() == ErrorKind::NotFound => Ok(PairSetupAttempts::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Records an unsuccessful attempt.
+    pub fn record_failure(&mut self, time: SystemTime) {
+        self.count = self.count.saturating_add(1);
+        self.last_failure = Some(time);
+    }
+
+    /// Returns whether the maximum number of unsuccessful attempts is reached.
+    pub fn is_exhausted(&self) -> bool { self.count >= MAX_PAIR_SETUP_ATTEMPTS }
+
+    /// Returns the backoff after the last unsuccessful attempt. It starts at 1 second and doubles with every attempt,
+    /// up to 1 hour.
+    pub fn backoff(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            count => Duration::from_secs(1 << (count - 1).min(12)).min(MAX_RETRY_DELAY),
+        }
+    }
+
+    /// Returns the time left until the next attempt is allowed, if the backoff hasn't passed yet.
+    pub fn retry_delay(&self, now: SystemTime) -> Option<Duration> {
+        let elapsed = now.duration_since(self.last_failure?).unwrap_or_default();
+
+        self.backoff().checked_sub(elapsed).filter(|delay| !delay.is_zero())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_pair_setup_attempts_backoff() {
+        let now = SystemTime::now();
+        let mut attempts = PairSetupAttempts::default();
+        assert_eq!(attempts.retry_delay(now), None);
+
+        attempts.record_failure(now);
+        assert_eq!(attempts.retry_delay(now), Some(Duration::from_secs(1)));
+
+        attempts.record_failure(now);
+        attempts.record_failure(now);
+        assert_eq!(attempts.retry_delay(now + Duration::from_secs(1)), Some(Duration::from_secs(3)));
+        assert_eq!(attempts.retry_delay(now + Duration::from_secs(4)), None);
+
+        for _ in 0..96 {
+            attempts.record_failure(now);
+        }
+        assert!(!attempts.is_exhausted());
+        assert_eq!(attempts.backoff(), MAX_RETRY_DELAY);
+
+        attempts.record_failure(now);
+        assert!(attempts.is_exhausted());
+    }
+
     #[test]
     fn test_pairing_from_bytes() {
         let pairing = Pairing {
@@ -1,4 +1,6 @@
+use rand::{rngs::OsRng, Rng};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 use crate::{Error, Result};
 
@@ -19,7 +21,8 @@ const INVALID_PINS: [[u8; 8]; 12] = [
 
 /// The `Pin` struct represents the server's 8 digit pin used for pairing.
 ///
-/// The pin consists of eight digits between 0 and 9 and defaults to `11122333`.
+/// The pin consists of eight digits between 0 and 9. It's displayed in the `XXX-XX-XXX` form HomeKit uses, e.g.
+/// `111-22-333`.
 ///
 /// The following pins are considered too easy and are therefore not allowed:
 /// - `00000000`
@@ -54,6 +57,23 @@ impl Pin {
         Ok(Pin { pin })
     }
 
+    /// Creates a random `Pin` using the OS random number generator. Pins that are considered too easy are skipped.
+    pub fn random() -> Self {
+        let mut csprng = OsRng {};
+        loop {
+            let mut pin = [0; 8];
+            for digit in pin.iter_mut() {
+                *digit = csprng.gen_range(0, 10);
+            }
+            if !INVALID_PINS.contains(&pin) {
+                return Pin { pin };
+            }
+        }
+    }
+
+    /// Returns the digits of the `Pin` as a number, e.g. `11122333`.
+    pub(crate) fn as_number(&self) -> u32 { self.pin.iter().fold(0, |acc, digit| acc * 10 + *digit as u32) }
+
     // TODO: fix UTF-8 encoding here
     // pub fn as_bytes(&self) -> [u8; 10] {
     //     [
@@ -71,9 +91,10 @@ impl Pin {
     // }
 }
 
-impl ToString for Pin {
-    fn to_string(&self) -> String {
-        format!(
+impl fmt::Display for Pin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
             "{}{}{}-{}{}-{}{}{}",
             &self.pin[0],
             &self.pin[1],
@@ -103,6 +124,15 @@ mod tests {
         assert_eq!(pin.to_string(), "111-22-333".to_string());
     }
 
+    #[test]
+    fn test_random() {
+        for _ in 0..100 {
+            let pin = Pin::random();
+            assert!(Pin::new(pin.pin).is_ok());
+            assert_eq!(pin.to_string().len(), 10);
+        }
+    }
+
     // #[test]
     // fn test_as_bytes() {
     //     let pin = Pin::new([1, 1, 1, 2, 2, 3, 3, 3]).unwrap();
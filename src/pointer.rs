@@ -1,12 +1,15 @@
 use futures::lock::Mutex;
-use std::sync::{Arc, RwLock};
+use std::{
+    sync::{atomic::AtomicBool, Arc, RwLock},
+    time::Instant,
+};
 use uuid::Uuid;
 
-use crate::{accessory, event, storage};
+use crate::{accessory, authenticator, event, storage};
 
 pub type ControllerId = Arc<RwLock<Option<Uuid>>>;
 
-pub type EventEmitter = Arc<Mutex<event::EventEmitter>>;
+pub type EventEmitter = Arc<event::EventEmitter>;
 
 pub type EventSubscriptions = Arc<Mutex<Vec<(u64, u64)>>>;
 
@@ -18,4 +21,20 @@ pub type Storage = Arc<Mutex<Box<dyn storage::Storage>>>;
 
 pub type Config = Arc<Mutex<crate::Config>>;
 
+pub type PreparedWrite = Arc<Mutex<Option<crate::transport::http::PreparedWrite>>>;
+
+pub type LastActivity = Arc<RwLock<Instant>>;
+
+pub type SessionEstablished = Arc<AtomicBool>;
+
+pub type ConnectionRegistry = Arc<Mutex<crate::transport::connection::ConnectionRegistry>>;
+
+pub type PairingCoordinator = Arc<Mutex<crate::transport::pairing_coordinator::PairingCoordinator>>;
+
+pub type SessionCache = Arc<Mutex<crate::transport::session_cache::SessionCache>>;
+
+pub type SessionSender = Arc<Mutex<Option<futures::channel::oneshot::Sender<crate::transport::tcp::Session>>>>;
+
+pub type Authenticator = Arc<Mutex<Option<Box<dyn authenticator::Authenticator>>>>;
+
 pub type MdnsResponder = Arc<Mutex<crate::transport::mdns::MdnsResponder>>;
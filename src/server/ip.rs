@@ -1,19 +1,29 @@
 use async_trait::async_trait;
 use futures::{
-    future::{BoxFuture, FutureExt},
+    future::{self, BoxFuture, FutureExt},
     lock::Mutex,
 };
 use log::{error, info};
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
 
 use crate::{
     accessory::HapAccessory,
+    authenticator::Authenticator,
+    advertiser::{Advertiser, MdnsAdvertiser},
     config::Config,
-    event::{Event, EventEmitter},
+    event::{Event, EventEmitter, EventStream},
+    pairing::PairSetupAttempts,
     pointer,
     server::Server,
     storage::{accessory_database::AccessoryDatabase, Storage},
-    transport::{http::server::Server as HttpServer, mdns::MdnsResponder},
+    transport::{
+        connection::{ConnectionInfo, ConnectionStats},
+        http::server::Server as HttpServer,
+        mdns::MdnsResponder,
+        session_cache::SessionCache,
+    },
     BonjourStatusFlag,
     Result,
 };
@@ -26,6 +36,10 @@ pub struct IpServer {
     accessory_database: pointer::AccessoryDatabase,
     http_server: HttpServer,
     mdns_responder: pointer::MdnsResponder,
+    authenticator: pointer::Authenticator,
+    event_emitter: pointer::EventEmitter,
+    connections: pointer::ConnectionRegistry,
+    session_cache: pointer::SessionCache,
     aid_cache: Arc<Mutex<Vec<u64>>>,
 }
 
@@ -86,12 +100,38 @@ impl IpServer {
     /// }
     /// ```
     pub async fn new<S: Storage + Send + Sync + 'static>(config: Config, storage: S) -> Result<Self> {
+        IpServer::with_advertiser(config, storage, MdnsAdvertiser::new()).await
+    }
+
+    /// Creates a new [`IpServer`](IpServer) announcing itself on the network via the given
+    /// [`Advertiser`](Advertiser) instead of the built-in [`MdnsAdvertiser`](MdnsAdvertiser).
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use hap::{advertiser::AvahiAdvertiser, server::IpServer, storage::FileStorage, Config, Result};
+    ///
+    /// async fn server() -> Result<IpServer> {
+    ///     let storage = FileStorage::current_dir().await?;
+    ///     let advertiser = AvahiAdvertiser::new("/etc/avahi/services/hap.service");
+    ///
+    ///     IpServer::with_advertiser(Config::default(), storage, advertiser).await
+    /// }
+    /// ```
+    pub async fn with_advertiser<S: Storage + Send + Sync + 'static, A: Advertiser + 'static>(
+        config: Config,
+        storage: S,
+        advertiser: A,
+    ) -> Result<Self> {
+        let session_cache = Arc::new(Mutex::new(SessionCache::new(
+            config.session_cache_size,
+            config.session_cache_ttl,
+        )));
+
         let config = Arc::new(Mutex::new(config));
         let storage: pointer::Storage = Arc::new(Mutex::new(Box::new(storage)));
 
         let config_ = config.clone();
         let storage_ = storage.clone();
-        let mut event_emitter = EventEmitter::new();
 
         let mut s = storage_.lock().await;
         if s.count_pairings().await? > 0 {
@@ -111,82 +151,46 @@ impl IpServer {
         }
         drop(s);
 
-        let mdns_responder = Arc::new(Mutex::new(MdnsResponder::new(config.clone()).await));
+        let authenticator = Arc::new(Mutex::new(None));
+        let mdns_responder = Arc::new(Mutex::new(MdnsResponder::new(
+            config.clone(),
+            authenticator.clone(),
+            Box::new(advertiser),
+        )));
         let mdns_responder_ = mdns_responder.clone();
 
-        event_emitter.add_listener(Box::new(move |event| {
-            let config_ = config_.clone();
-            let storage_ = storage_.clone();
-            let mdns_responder_ = mdns_responder_.clone();
-            async move {
-                match *event {
-                    Event::ControllerPaired { id } => {
-                        info!("controller {} paired", id);
-
-                        let pairing_count = storage_.lock().await.count_pairings().await;
-                        if let Ok(count) = pairing_count {
-                            if count > 0 {
-                                info!("1 or more controllers paired; setting Bonjour status flag to `Zero`");
-
-                                let mut c = config_.lock().await;
-                                c.status_flag = BonjourStatusFlag::Zero;
-
-                                storage_
-                                    .lock()
-                                    .await
-                                    .save_config(&c)
-                                    .await
-                                    .map_err(|e| error!("error saving the config: {:?}", e))
-                                    .ok();
-
-                                drop(c);
-
-                                mdns_responder_.lock().await.update_records().await;
-                            }
-                        }
-                    },
-                    Event::ControllerUnpaired { id } => {
-                        info!("controller {} unpaired", id);
-
-                        let pairing_count = storage_.lock().await.count_pairings().await;
-                        // TODO - `pairing_count` is an Err
-                        if let Ok(count) = pairing_count {
-                            if count == 0 {
-                                info!("0 controllers paired; setting Bonjour status flag to `Not Paired`");
-
-                                let mut c = config_.lock().await;
-                                c.status_flag = BonjourStatusFlag::NotPaired;
-
-                                storage_
-                                    .lock()
-                                    .await
-                                    .save_config(&c)
-                                    .await
-                                    .map_err(|e| error!("error saving the config: {:?}", e))
-                                    .ok();
-
-                                drop(c);
-
-                                mdns_responder_.lock().await.update_records().await;
-                            }
-                        }
-                    },
-                    _ => {},
+        let event_emitter = Arc::new(EventEmitter::new());
+        let mut events = event_emitter.receiver();
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(Event::ControllerPaired { id }) => info!("controller {} paired", id),
+                    Ok(Event::ControllerUnpaired { id }) => info!("controller {} unpaired", id),
+                    Ok(_) => continue,
+                    // pairings might have changed in the missed events
+                    Err(RecvError::Lagged(_)) => {},
+                    Err(RecvError::Closed) => break,
                 }
+
+                update_status_flag(&config_, &storage_, &mdns_responder_)
+                    .await
+                    .map_err(|e| error!("error updating the Bonjour status flag: {:?}", e))
+                    .ok();
             }
-            .boxed()
-        }));
+        });
 
-        let event_emitter = Arc::new(Mutex::new(event_emitter));
         let accessory_database = Arc::new(Mutex::new(AccessoryDatabase::new(event_emitter.clone())));
 
         let http_server = HttpServer::new(
             config.clone(),
             storage.clone(),
             accessory_database.clone(),
-            event_emitter,
+            event_emitter.clone(),
             mdns_responder.clone(),
+            session_cache.clone(),
+            authenticator.clone(),
         );
+        let connections = http_server.connections();
 
         let mut storage_lock = storage.lock().await;
         let aid_cache = Arc::new(Mutex::new(match storage_lock.load_aid_cache().await {
@@ -206,6 +210,10 @@ impl IpServer {
             accessory_database,
             http_server,
             mdns_responder,
+            authenticator,
+            event_emitter,
+            connections,
+            session_cache,
             aid_cache,
         };
 
@@ -216,13 +224,44 @@ impl IpServer {
 #[async_trait]
 impl Server for IpServer {
     fn run_handle(&self) -> BoxFuture<Result<()>> {
+        let http_server = self.http_server.clone();
         let http_handle = self.http_server.run_handle();
         let mdns_responder = self.mdns_responder.clone();
+        let config = self.config.clone();
+        let storage = self.storage.clone();
+        let aid_cache = self.aid_cache.clone();
 
         let handle = async move {
-            let mdns_handle = mdns_responder.lock().await.run_handle();
+            let mdns_handle = mdns_responder.lock().await.run_handle().await?;
+            let server_handle = future::try_join(http_handle, mdns_handle.map(|_| Ok(())));
+
+            let interface_watch_interval = config.lock().await.interface_watch_interval;
+            match interface_watch_interval {
+                Some(interval) => {
+                    let watcher = watch_interfaces(
+                        interval,
+                        config.clone(),
+                        storage.clone(),
+                        http_server,
+                        mdns_responder.clone(),
+                    );
+                    // the watcher runs until the server is shut down
+                    match future::select(server_handle, Box::pin(watcher)).await {
+                        future::Either::Left((res, _)) => res?,
+                        future::Either::Right((_, server_handle)) => server_handle.await?,
+                    };
+                },
+                None => {
+                    server_handle.await?;
+                },
+            }
+
+            // the server was shut down, so persist its state before finishing
+            let mut storage = storage.lock().await;
+            storage.save_config(&*config.lock().await).await?;
+            storage.save_aid_cache(&aid_cache.lock().await).await?;
 
-            futures::try_join!(http_handle, mdns_handle.map(|_| Ok(())))?;
+            info!("server shut down");
 
             Ok(())
         }
@@ -231,6 +270,14 @@ impl Server for IpServer {
         Box::pin(handle)
     }
 
+    async fn shutdown(&self) -> Result<()> {
+        info!("shutting down server");
+
+        self.http_server.shutdown().await;
+
+        Ok(())
+    }
+
     fn config_pointer(&self) -> pointer::Config { self.config.clone() }
 
     fn storage_pointer(&self) -> pointer::Storage { self.storage.clone() }
@@ -278,9 +325,255 @@ impl Server for IpServer {
         Ok(())
     }
 
-    // async fn factory_reset(&mut self) -> Result<()> {
-    //     unimplemented!();
+    async fn set_authenticator<A: Authenticator + 'static>(&self, authenticator: A) -> Result<()> {
+        *self.authenticator.lock().await = Some(Box::new(authenticator));
+
+        self.mdns_responder.lock().await.update_records().await
+    }
+
+    async fn pair_setup_attempts(&self) -> Result<PairSetupAttempts> {
+        PairSetupAttempts::load(self.storage.lock().await.as_ref()).await
+    }
+
+    async fn reset_pair_setup_attempts(&self) -> Result<()> {
+        info!("resetting pair setup attempts");
+
+        self.storage
+            .lock()
+            .await
+            .save_pair_setup_attempts(&PairSetupAttempts::default())
+            .await
+    }
+
+    async fn remove_pairing(&self, id: &Uuid) -> Result<()> {
+        info!("removing pairing {}", id);
+
+        self.http_server.remove_pairing(id).await
+    }
+
+    async fn connections(&self) -> Vec<ConnectionInfo> { self.connections.lock().await.connections().await }
+
+    async fn connection_stats(&self) -> ConnectionStats { self.connections.lock().await.stats() }
+
+    fn events(&self) -> EventStream { self.event_emitter.subscribe() }
+
+    async fn factory_reset(&self) -> Result<()> {
+        info!("performing factory reset");
+
+        // the storage isn't held across the steps, as the config and the aid cache are locked before it elsewhere
+        let mut storage = self.storage.lock().await;
+        for pairing in storage.list_pairings().await? {
+            storage.delete_pairing(&pairing.id).await?;
+            self.event_emitter.emit(Event::ControllerUnpaired { id: pairing.id });
+        }
+        storage.save_pair_setup_attempts(&PairSetupAttempts::default()).await?;
+        drop(storage);
+
+        self.session_cache.lock().await.clear();
+
+        let mut config = self.config.lock().await;
+        config.reset_device_identity();
+        self.storage.lock().await.save_config(&config).await?;
+        drop(config);
+
+        let mut aid_cache = self.aid_cache.lock().await;
+        aid_cache.clear();
+        for accessory in self.accessory_database.lock().await.accessories.iter() {
+            aid_cache.push(accessory.lock().await.get_id());
+        }
+        self.storage.lock().await.save_aid_cache(&aid_cache).await?;
+        drop(aid_cache);
+
+        self.http_server.close_connections().await;
+        self.mdns_responder.lock().await.update_records().await?;
+
+        Ok(())
+    }
+}
+
+/// Sets the Bonjour status flag according to whether any controllers are paired, and updates the mDNS records if it
+/// changed.
+async fn update_status_flag(
+    config: &pointer::Config,
+    storage: &pointer::Storage,
+    mdns_responder: &pointer::MdnsResponder,
+) -> Result<()> {
+    let status_flag = match storage.lock().await.count_pairings().await? {
+        0 => BonjourStatusFlag::NotPaired,
+        _ => BonjourStatusFlag::Zero,
+    };
+
+    let mut c = config.lock().await;
+    if c.status_flag == status_flag {
+        return Ok(());
+    }
+    match status_flag {
+        BonjourStatusFlag::NotPaired => info!("0 controllers paired; setting Bonjour status flag to `Not Paired`"),
+        _ => info!("1 or more controllers paired; setting Bonjour status flag to `Zero`"),
+    }
+    c.status_flag = status_flag;
+    storage.lock().await.save_config(&c).await?;
+    drop(c);
+
+    mdns_responder.lock().await.update_records().await
+}
+
+/// Periodically redetermines the `hosts` of the `Config`. On a change, the TCP listeners are rebound, the mDNS
+/// responder is rebuilt for the new addresses and the `Config` is persisted to the storage.
+async fn watch_interfaces(
+    interval: Duration,
+    config: pointer::Config,
+    storage: pointer::Storage,
+    http_server: HttpServer,
+    mdns_responder: pointer::MdnsResponder,
+) {
+    loop {
+        tokio::time::sleep(interval).await;
+
+        let mut c = config.lock().await;
+        let previous_hosts = c.hosts.clone();
+        c.redetermine_local_ip();
+        if c.hosts.len() == previous_hosts.len() && c.hosts.iter().all(|host| previous_hosts.contains(host)) {
+            continue;
+        }
+
+        info!("network addresses changed from {:?} to {:?}", &previous_hosts, &c.hosts);
+
+        storage
+            .lock()
+            .await
+            .save_config(&c)
+            .await
+            .map_err(|e| error!("error saving the config: {:?}", e))
+            .ok();
+
+        drop(c);
+
+        http_server.rebind().await;
+
+        match mdns_responder.lock().await.rebuild().await {
+            Ok(mdns_task) => {
+                tokio::spawn(mdns_task);
+            },
+            Err(e) => error!("error rebuilding the mDNS responder: {:?}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::StreamExt;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use crate::{
+        accessory::{lightbulb::LightbulbAccessory, AccessoryInformation},
+        advertiser::MemoryAdvertiser,
+        pairing::{Pairing, Permissions},
+        storage::FileStorage,
+        transport::session_cache::SESSION_ID_LEN,
+        BonjourFeatureFlag,
+    };
+
+    async fn server(name: &str) -> IpServer { server_with_advertiser(name, MemoryAdvertiser::new()).await }
+
+    async fn server_with_advertiser(name: &str, advertiser: MemoryAdvertiser) -> IpServer {
+        let mut temp_dir = std::env::temp_dir();
+        temp_dir.push(name);
+
+        let config = Config {
+            hosts: vec![IpAddr::V4(Ipv4Addr::LOCALHOST)],
+            port: 0,
+            ..Default::default()
+        };
+        let storage = FileStorage::new(&temp_dir).await.unwrap();
+
+        IpServer::with_advertiser(config, storage, advertiser).await.unwrap()
+    }
+
+    struct HardwareAuthenticator;
+
+    #[async_trait]
+    impl Authenticator for HardwareAuthenticator {
+        fn feature_flag(&self) -> BonjourFeatureFlag { BonjourFeatureFlag::SupportsHardwareAuthentication }
+
+        async fn certificate(&mut self) -> Result<Vec<u8>> { Ok(vec![]) }
+
+        async fn sign(&mut self, _: &[u8]) -> Result<Vec<u8>> { Ok(vec![]) }
+    }
+
+    #[tokio::test]
+    async fn test_set_authenticator() {
+        let advertiser = MemoryAdvertiser::new();
+        let server = server_with_advertiser("hap-set-authenticator", advertiser.clone()).await;
+        let feature_flag = |advertiser: &MemoryAdvertiser| {
+            advertiser
+                .record()
+                .unwrap()
+                .txt_records
+                .into_iter()
+                .find(|r| r.starts_with("ff="))
+                .unwrap()
+        };
+
+        server.mdns_responder.lock().await.update_records().await.unwrap();
+        assert_eq!(feature_flag(&advertiser), "ff=0");
+
+        // the feature flag is derived from the authenticator and never persisted
+        server.set_authenticator(HardwareAuthenticator).await.unwrap();
+        assert_eq!(feature_flag(&advertiser), "ff=1");
+        assert_eq!(server.config.lock().await.feature_flag, BonjourFeatureFlag::Zero);
+        let stored_config = server.storage.lock().await.load_config().await.unwrap();
+        assert_eq!(stored_config.feature_flag, BonjourFeatureFlag::Zero);
+    }
+
+    #[tokio::test]
+    async fn test_factory_reset() {
+        let server = server("hap-factory-reset").await;
+        let mut events = server.events();
+
+        let id = Uuid::parse_str("bc158b86-cabf-432d-aee4-422ef0e3f1d5").unwrap();
+        let pairing = Pairing::new(id, Permissions::Admin, [0; 32]);
+        server.storage.lock().await.save_pairing(&pairing).await.unwrap();
+        server.session_cache.lock().await.insert([1; SESSION_ID_LEN], id, [2; 32]);
+        let device_id = server.config.lock().await.device_id;
+
+        server.factory_reset().await.unwrap();
+
+        assert_eq!(events.next().await, Some(Event::ControllerUnpaired { id }));
+        assert_eq!(server.storage.lock().await.count_pairings().await.unwrap(), 0);
+        assert_eq!(server.session_cache.lock().await.get(&[1; SESSION_ID_LEN]), None);
+        assert_ne!(server.config.lock().await.device_id, device_id);
+    }
 
-    //     Ok(())
-    // }
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_factory_reset_concurrently() {
+        let server = server("hap-factory-reset-concurrently").await;
+
+        // resetting while accessories are added and the status flag is updated must not deadlock
+        let server_ = server.clone();
+        let resets = tokio::spawn(async move {
+            for _ in 0..50 {
+                server_.factory_reset().await.unwrap();
+            }
+        });
+        let server_ = server.clone();
+        let additions = tokio::spawn(async move {
+            for aid in 1..=50 {
+                let lightbulb = LightbulbAccessory::new(aid, AccessoryInformation::default()).unwrap();
+                server_.add_accessory(lightbulb).await.unwrap();
+                update_status_flag(&server_.config, &server_.storage, &server_.mdns_responder)
+                    .await
+                    .unwrap();
+            }
+        });
+        let (resets, additions) = tokio::time::timeout(Duration::from_secs(10), future::join(resets, additions))
+            .await
+            .expect("factory reset deadlocked");
+        resets.unwrap();
+        additions.unwrap();
+
+        assert_eq!(server.aid_cache.lock().await.len(), 50);
+    }
 }
@@ -1,7 +1,16 @@
 use async_trait::async_trait;
 use futures::future::BoxFuture;
+use uuid::Uuid;
 
-use crate::{accessory::HapAccessory, pointer, Result};
+use crate::{
+    accessory::HapAccessory,
+    authenticator::Authenticator,
+    event::EventStream,
+    pairing::PairSetupAttempts,
+    pointer,
+    Result,
+};
+pub use crate::transport::connection::{ConnectionInfo, ConnectionStats};
 pub use ip::IpServer;
 
 mod ip;
@@ -10,8 +19,12 @@ mod ip;
 /// [`IpServer`](IpServer).
 #[async_trait]
 pub trait Server {
-    /// Returns a [`BoxFuture`](BoxFuture) run handle to the server that can be passed to an executor.
+    /// Returns a [`BoxFuture`](BoxFuture) run handle to the server that can be passed to an executor. The handle runs
+    /// until [`shutdown`](Server::shutdown) is called and can be recreated afterwards to restart the server.
     fn run_handle(&self) -> BoxFuture<Result<()>>;
+    /// Gracefully shuts a running server down. The server stops accepting connections, sends mDNS goodbye packets,
+    /// closes all open sessions and persists its state to the storage before the run handle finishes.
+    async fn shutdown(&self) -> Result<()>;
     /// Returns a pointer to the [`Config`](crate::Config) of the server.
     fn config_pointer(&self) -> pointer::Config;
     /// Returns a pointer to the [`Storage`](crate::storage::Storage) of the server.
@@ -20,7 +33,29 @@ pub trait Server {
     async fn add_accessory<A: HapAccessory + 'static>(&self, accessory: A) -> Result<pointer::Accessory>;
     /// Takes a pointer to an accessory and removes it from the server.
     async fn remove_accessory(&self, accessory: &pointer::Accessory) -> Result<()>;
-    // /// Every accessory must support a manufacturer-defined mechanism to restore itself to a “factory reset” state
-    // where /// all pairing information is erased and restored to factory default settings. This method is doing
-    // just that. async fn factory_reset(&mut self) -> Result<()>;
+    /// Sets the [`Authenticator`](Authenticator) the accessory proves its authenticity to controllers with during pair
+    /// setup, and announces the kind of authentication it performs via the Bonjour Feature Flag.
+    async fn set_authenticator<A: Authenticator + 'static>(&self, authenticator: A) -> Result<()>;
+    /// Returns the unsuccessful pair setup attempts counted by the server. Controllers have to back off after every
+    /// unsuccessful attempt, and after [`MAX_PAIR_SETUP_ATTEMPTS`](crate::pairing::MAX_PAIR_SETUP_ATTEMPTS) of them,
+    /// pair setup is refused until the attempts are reset.
+    async fn pair_setup_attempts(&self) -> Result<PairSetupAttempts>;
+    /// Resets the unsuccessful pair setup attempts, lifting both the backoff and the refusal of pair setup.
+    async fn reset_pair_setup_attempts(&self) -> Result<()>;
+    /// Removes the pairing of a controller, closing its connections and dropping its event subscriptions. If it was the
+    /// last admin pairing, all remaining pairings are removed as well.
+    async fn remove_pairing(&self, id: &Uuid) -> Result<()>;
+    /// Returns information about the live connections of the server, i.e. their peer addresses, the IDs of the
+    /// controllers verified on them, the times they were accepted at and the characteristics they're subscribed to.
+    async fn connections(&self) -> Vec<ConnectionInfo>;
+    /// Returns counters of the connections of the server and of the connection policies configured in the
+    /// [`Config`](crate::Config) applied to them, i.e. how many connections were refused, evicted or timed out.
+    async fn connection_stats(&self) -> ConnectionStats;
+    /// Returns a [`Stream`](futures::Stream) of the [`Event`](crate::event::Event)s emitted by the server from now on,
+    /// e.g. controllers being paired, connections being opened and closed or characteristic values changing. Dropping
+    /// the stream unsubscribes from the events.
+    fn events(&self) -> EventStream;
+    /// Every accessory must support a manufacturer-defined mechanism to restore itself to a “factory reset” state where
+    /// all pairing information is erased and restored to factory default settings. This method is doing just that.
+    async fn factory_reset(&self) -> Result<()>;
 }
@@ -0,0 +1,154 @@
+use rand::{rngs::OsRng, Rng};
+use sha2::{Digest, Sha512};
+
+use crate::{accessory::AccessoryCategory, Error, MacAddress, Pin, Result};
+
+const SETUP_ID_CHARS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// Flags of the setup payload indicating how an accessory can be paired.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SetupFlag {
+    /// The accessory supports pairing via NFC.
+    Nfc = 0x01,
+    /// The accessory supports the IP transport.
+    Ip = 0x02,
+    /// The accessory supports the Bluetooth LE transport.
+    BluetoothLe = 0x04,
+    /// The accessory supports Wireless Accessory Configuration (WAC) to join a Wi-Fi network.
+    WirelessAccessoryConfiguration = 0x08,
+}
+
+/// Generates a random setup ID.
+pub fn generate_setup_id() -> String {
+    let mut csprng = OsRng {};
+    (0..4)
+        .map(|_| SETUP_ID_CHARS[csprng.gen_range(0, SETUP_ID_CHARS.len())] as char)
+        .collect()
+}
+
+/// Builds the `X-HM://` setup URI encoded by HomeKit QR codes and NFC tags.
+///
+/// # Examples
+/// ```
+/// use hap::{accessory::AccessoryCategory, setup::{setup_uri, SetupFlag}, Pin};
+///
+/// let pin = Pin::new([0, 3, 1, 4, 5, 1, 5, 4]).unwrap();
+/// let uri = setup_uri(AccessoryCategory::Lightbulb, &pin, &[SetupFlag::Ip], "1QJ8").unwrap();
+///
+/// assert_eq!(uri, "X-HM://00522H1VM1QJ8");
+/// ```
+pub fn setup_uri(category: AccessoryCategory, pin: &Pin, flags: &[SetupFlag], setup_id: &str) -> Result<String> {
+    validate_setup_id(setup_id)?;
+
+    // the payload consists of the setup code in bits 0 to 26, the flags in bits 27 to 30 and the category in bits 31
+    // to 38; version and reserved bits are 0
+    let flags = flags.iter().fold(0, |acc, flag| acc | *flag as u64);
+    let payload = pin.as_number() as u64 | flags << 27 | (category as u64 & 0xff) << 31;
+
+    Ok(format!("X-HM://{:0>9}{}", to_base36(payload), setup_id))
+}
+
+/// Derives the setup hash published in the `sh` TXT record, the Base64 encoded first 4 bytes of the SHA-512 hash of
+/// the setup ID and the device ID.
+pub fn setup_hash(setup_id: &str, device_id: &MacAddress) -> String {
+    let mut hasher = Sha512::new();
+    hasher.update(setup_id.as_bytes());
+    hasher.update(device_id.to_string().as_bytes());
+
+    base64::encode(&hasher.finalize()[..4])
+}
+
+/// Renders a setup URI as a QR code made of Unicode block characters, e.g. to print it to a terminal.
+#[cfg(feature = "qr")]
+pub fn qr_code_unicode(setup_uri: &str) -> Result<String> {
+    use qrcode::{render::unicode::Dense1x2, QrCode};
+
+    let code = QrCode::new(setup_uri.as_bytes())?;
+
+    // invert the colors so the code reads on dark terminal backgrounds
+    Ok(code
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build())
+}
+
+/// Renders a setup URI as a QR code in SVG format.
+#[cfg(feature = "qr")]
+pub fn qr_code_svg(setup_uri: &str) -> Result<String> {
+    use qrcode::{render::svg, QrCode};
+
+    let code = QrCode::new(setup_uri.as_bytes())?;
+
+    Ok(code.render::<svg::Color>().min_dimensions(200, 200).build())
+}
+
+/// Renders a setup URI as a QR code in PNG format.
+#[cfg(feature = "qr")]
+pub fn qr_code_png(setup_uri: &str) -> Result<Vec<u8>> {
+    use image::{DynamicImage, ImageOutputFormat, Luma};
+    use qrcode::QrCode;
+
+    let code = QrCode::new(setup_uri.as_bytes())?;
+    let image = code.render::<Luma<u8>>().min_dimensions(200, 200).build();
+
+    let mut png = Vec::new();
+    DynamicImage::ImageLuma8(image).write_to(&mut png, ImageOutputFormat::Png)?;
+
+    Ok(png)
+}
+
+/// Checks that a setup ID consists of 4 characters from `0-9` and `A-Z`.
+fn validate_setup_id(setup_id: &str) -> Result<()> {
+    if setup_id.len() != 4 || !setup_id.bytes().all(|c| SETUP_ID_CHARS.contains(&c)) {
+        return Err(Error::InvalidSetupId(setup_id.to_string()));
+    }
+
+    Ok(())
+}
+
+/// Encodes a number in upper case base 36.
+fn to_base36(mut n: u64) -> String {
+    let mut digits = Vec::new();
+    loop {
+        digits.push(SETUP_ID_CHARS[(n % 36) as usize]);
+        n /= 36;
+        if n == 0 {
+            break;
+        }
+    }
+    digits.reverse();
+
+    String::from_utf8(digits).expect("base 36 digits are valid UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_setup_uri() {
+        let pin = Pin::new([0, 3, 1, 4, 5, 1, 5, 4]).unwrap();
+
+        assert_eq!(
+            setup_uri(AccessoryCategory::Lightbulb, &pin, &[SetupFlag::Ip], "1QJ8").unwrap(),
+            "X-HM://00522H1VM1QJ8"
+        );
+        assert!(setup_uri(AccessoryCategory::Lightbulb, &pin, &[SetupFlag::Ip], "1qj8").is_err());
+        assert!(setup_uri(AccessoryCategory::Lightbulb, &pin, &[SetupFlag::Ip], "1QJ").is_err());
+    }
+
+    #[test]
+    fn test_setup_hash() {
+        let device_id = MacAddress::new(0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF);
+
+        assert_eq!(setup_hash("1QJ8", &device_id), "Jyv0aQ==");
+    }
+
+    #[test]
+    fn test_generate_setup_id() {
+        let setup_id = generate_setup_id();
+
+        assert!(validate_setup_id(&setup_id).is_ok());
+    }
+}
@@ -63,7 +63,8 @@ impl AccessoryDatabase {
         Err(Error::AccessoryNotFound)
     }
 
-    /// Reads the value of a characteristic.
+    /// Reads the value of a characteristic. The `ev` field is only read if the event subscriptions of the connection
+    /// are passed.
     pub(crate) async fn read_characteristic(
         &self,
         aid: u64,
@@ -71,7 +72,7 @@ impl AccessoryDatabase {
         meta: bool,
         perms: bool,
         hap_type: bool,
-        ev: bool,
+        ev: Option<&pointer::EventSubscriptions>,
     ) -> Result<ReadResponseObject> {
         let mut result_object = ReadResponseObject {
             iid,
@@ -107,13 +108,16 @@ impl AccessoryDatabase {
                                     result_object.max_len = characteristic.get_max_len();
                                 }
                                 if perms {
-                                    result_object.perms = Some(characteristic_perms);
+                                    result_object.perms = Some(characteristic_perms.clone());
                                 }
                                 if hap_type {
                                     result_object.hap_type = Some(characteristic.get_type());
                                 }
-                                if ev {
-                                    result_object.ev = characteristic.get_event_notifications();
+                                if let Some(event_subscriptions) = ev {
+                                    result_object.ev = match characteristic_perms.contains(&Perm::Events) {
+                                        true => Some(event_subscriptions.lock().await.contains(&(aid, iid))),
+                                        false => characteristic.get_event_notifications(),
+                                    };
                                 }
                             } else {
                                 result_object.status = Some(Status::WriteOnlyCharacteristic as i32);
@@ -128,16 +132,19 @@ impl AccessoryDatabase {
         Ok(result_object)
     }
 
-    /// Writes the value of a characteristic.
+    /// Writes the value of a characteristic. `timed` indicates whether the write is executed as part of a valid timed
+    /// write transaction, which is required for characteristics with the `TimedWrite` permission.
     pub(crate) async fn write_characteristic(
         &mut self,
         write_object: WriteObject,
         event_subscriptions: &pointer::EventSubscriptions,
+        timed: bool,
     ) -> Result<WriteResponseObject> {
         let mut result_object = WriteResponseObject {
             aid: write_object.aid,
             iid: write_object.iid,
             status: 0,
+            value: None,
         };
 
         'l: for accessory in self.accessories.iter_mut() {
@@ -149,7 +156,6 @@ impl AccessoryDatabase {
                             let characteristic_perms = characteristic.get_perms();
                             if let Some(ev) = write_object.ev {
                                 if characteristic_perms.contains(&Perm::Events) {
-                                    characteristic.set_event_notifications(Some(ev));
                                     let subscription = (write_object.aid, write_object.iid);
                                     let mut es = event_subscriptions.lock().await;
                                     let pos = es.iter().position(|&s| s == subscription);
@@ -167,10 +173,33 @@ impl AccessoryDatabase {
                                 }
                             }
                             if let Some(value) = write_object.value {
-                                if characteristic_perms.contains(&Perm::PairedWrite) {
-                                    characteristic.set_value(value).await?;
-                                } else {
+                                if !characteristic_perms.contains(&Perm::PairedWrite) {
                                     result_object.status = Status::ReadOnlyCharacteristic as i32;
+                                } else if (characteristic_perms.contains(&Perm::TimedWrite) && !timed)
+                                    || (write_object.response == Some(true)
+                                        && !characteristic_perms.contains(&Perm::WriteResponse))
+                                {
+                                    result_object.status = Status::InvalidValueInRequest as i32;
+                                } else {
+                                    match characteristic.set_value_with_response(value).await {
+                                        Ok(response) =>
+                                            if write_object.response == Some(true) {
+                                                result_object.value = Some(response);
+                                            },
+                                        Err(
+                                            e @ Error::InvalidValue(_)
+                                            | e @ Error::ValueBelowMinValue
+                                            | e @ Error::ValueAboveMaxValue
+                                            | e @ Error::ValueNotInValidValues
+                                            | e @ Error::ValueOutsideValidValuesRange
+                                            | e @ Error::ValueAboveMaxLen
+                                            | e @ Error::ValueAboveMaxDataLen,
+                                        ) => {
+                                            debug!("rejecting invalid characteristic value: {:?}", e);
+                                            result_object.status = Status::InvalidValueInRequest as i32;
+                                        },
+                                        Err(e) => return Err(e),
+                                    }
                                 }
                             }
                             break 'l;
@@ -183,11 +212,20 @@ impl AccessoryDatabase {
         Ok(result_object)
     }
 
-    pub(crate) async fn as_serialized_json(&self) -> Result<Vec<u8>> {
+    /// Serializes the accessories to JSON. The `ev` fields of characteristics supporting event notifications reflect
+    /// the given subscriptions of the requesting controller.
+    pub(crate) async fn as_serialized_json(
+        &self,
+        event_subscriptions: &pointer::EventSubscriptions,
+    ) -> Result<Vec<u8>> {
+        let event_subscriptions = event_subscriptions.lock().await;
+
         let mut accessory_values = Vec::new();
         for accessory in &self.accessories {
             let a = accessory.lock().await;
-            accessory_values.push(serde_json::to_value(&*a)?);
+            let mut accessory_value = serde_json::to_value(&*a)?;
+            set_subscription_state(a.get_id(), &mut accessory_value, &event_subscriptions);
+            accessory_values.push(accessory_value);
         }
 
         let json = json!({ "accessories": accessory_values });
@@ -198,10 +236,107 @@ impl AccessoryDatabase {
     }
 }
 
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
+/// Sets the `ev` fields of the serialized characteristics of an accessory supporting event notifications to whether
+/// they are part of the given subscriptions.
+fn set_subscription_state(aid: u64, accessory_value: &mut serde_json::Value, event_subscriptions: &[(u64, u64)]) {
+    let services = accessory_value.get_mut("services").and_then(|s| s.as_array_mut());
+    for service in services.into_iter().flatten() {
+        let characteristics = service.get_mut("characteristics").and_then(|c| c.as_array_mut());
+        for characteristic in characteristics.into_iter().flatten() {
+            let supports_events = characteristic
+                .get("perms")
+                .and_then(|p| p.as_array())
+                .is_some_and(|p| p.iter().any(|p| p.as_str() == Some("ev")));
+            let iid = match characteristic.get("iid").and_then(|i| i.as_u64()) {
+                Some(iid) if supports_events => iid,
+                _ => continue,
+            };
+            characteristic["ev"] = json!(event_subscriptions.contains(&(aid, iid)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::StreamExt;
+
+    use crate::{
+        accessory::{lightbulb::LightbulbAccessory, AccessoryInformation},
+        characteristic::HapCharacteristic,
+        event::{Event, EventEmitter},
+    };
 
-//     #[test]
-//     fn test_json_serialization() {} // TODO: test it
-// }
+    fn write_object(aid: u64, iid: u64, ev: Option<bool>, value: Option<serde_json::Value>) -> WriteObject {
+        WriteObject {
+            iid,
+            aid,
+            ev,
+            value,
+            auth_data: None,
+            remote: None,
+            response: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_per_controller_subscriptions() {
+        let event_emitter = Arc::new(EventEmitter::new());
+        let mut events = event_emitter.subscribe();
+
+        let mut accessory_database = AccessoryDatabase::new(event_emitter);
+        let lightbulb = LightbulbAccessory::new(1, AccessoryInformation::default()).unwrap();
+        let iid = lightbulb.lightbulb.power_state.get_id();
+        accessory_database.add_accessory(Box::new(lightbulb)).unwrap();
+
+        let subscriptions_a: pointer::EventSubscriptions = Arc::new(Mutex::new(vec![]));
+        let subscriptions_b: pointer::EventSubscriptions = Arc::new(Mutex::new(vec![]));
+
+        // one controller unsubscribing doesn't affect the other one
+        accessory_database
+            .write_characteristic(write_object(1, iid, Some(true), None), &subscriptions_a, false)
+            .await
+            .unwrap();
+        accessory_database
+            .write_characteristic(write_object(1, iid, Some(false), None), &subscriptions_b, false)
+            .await
+            .unwrap();
+
+        let read_a = accessory_database
+            .read_characteristic(1, iid, false, false, false, Some(&subscriptions_a))
+            .await
+            .unwrap();
+        let read_b = accessory_database
+            .read_characteristic(1, iid, false, false, false, Some(&subscriptions_b))
+            .await
+            .unwrap();
+        assert_eq!(read_a.ev, Some(true));
+        assert_eq!(read_b.ev, Some(false));
+
+        let json: serde_json::Value =
+            serde_json::from_slice(&accessory_database.as_serialized_json(&subscriptions_a).await.unwrap()).unwrap();
+        let power_state = json["accessories"][0]["services"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .flat_map(|s| s["characteristics"].as_array().unwrap())
+            .find(|c| c["iid"] == json!(iid))
+            .unwrap();
+        assert_eq!(power_state["ev"], json!(true));
+
+        // value changes are emitted no matter which controller caused them
+        accessory_database
+            .write_characteristic(write_object(1, iid, None, Some(json!(true))), &subscriptions_b, false)
+            .await
+            .unwrap();
+        assert_eq!(
+            events.next().await,
+            Some(Event::CharacteristicValueChanged {
+                aid: 1,
+                iid,
+                value: json!(true),
+            })
+        );
+    }
+}
@@ -11,7 +11,13 @@ use std::{
 use tokio::task::spawn_blocking;
 use uuid::Uuid;
 
-use crate::{pairing::Pairing, storage::Storage, Config, Error, Result};
+use crate::{
+    pairing::{PairSetupAttempts, Pairing},
+    storage::Storage,
+    Config,
+    Error,
+    Result,
+};
 
 /// [`FileStorage`](FileStorage) is an implementor of the [`Storage`](Storage) trait that stores data to the file
 /// system.
@@ -103,6 +109,7 @@ impl FileStorage {
         let mut writer = self.get_writer(key).await?;
         spawn_blocking(move || -> Result<()> {
             writer.write_all(&value)?;
+            writer.flush()?;
 
             Ok(())
         })
@@ -218,6 +225,22 @@ impl Storage for FileStorage {
         Ok(count)
     }
 
+    async fn load_pair_setup_attempts(&self) -> Result<PairSetupAttempts> {
+        let attempts_bytes = self.read_bytes("pair_setup_attempts.json").await?;
+        let attempts = serde_json::from_slice(&attempts_bytes)?;
+
+        debug!("loaded pair setup attempts: {:?}", &attempts);
+
+        Ok(attempts)
+    }
+
+    async fn save_pair_setup_attempts(&mut self, attempts: &PairSetupAttempts) -> Result<()> {
+        let attempts_bytes = serde_json::to_vec(attempts)?;
+        self.write_bytes("pair_setup_attempts.json", attempts_bytes).await
+    }
+
+    async fn delete_pair_setup_attempts(&mut self) -> Result<()> { self.remove_file("pair_setup_attempts.json").await }
+
     async fn load_bytes(&self, key: &str) -> Result<Vec<u8>> {
         let bytes = self.read_bytes(&format!("misc/{}", key)).await?;
 
@@ -249,7 +272,9 @@ mod tests {
 
         // Config can't derive PartialEq
         let config_eq = |a: &Config, b: &Config| {
-            assert_eq!(a.host, b.host);
+            assert_eq!(a.hosts, b.hosts);
+            assert_eq!(a.interface_policy, b.interface_policy);
+            assert_eq!(a.setup_id, b.setup_id);
             assert_eq!(a.port, b.port);
             assert_eq!(a.pin, b.pin);
             assert_eq!(a.name, b.name);
@@ -261,6 +286,12 @@ mod tests {
             assert_eq!(a.status_flag, b.status_flag);
             assert_eq!(a.feature_flag, b.feature_flag);
             assert_eq!(a.max_peers, b.max_peers);
+            assert_eq!(a.session_cache_size, b.session_cache_size);
+            assert_eq!(a.session_cache_ttl, b.session_cache_ttl);
+            assert_eq!(a.max_connections, b.max_connections);
+            assert_eq!(a.pair_verify_timeout, b.pair_verify_timeout);
+            assert_eq!(a.idle_timeout, b.idle_timeout);
+            assert_eq!(a.event_coalescing_window, b.event_coalescing_window);
         };
 
         storage.save_config(&config).await.unwrap();
@@ -283,6 +314,29 @@ mod tests {
         assert!(saved_config.is_err());
     }
 
+    #[tokio::test]
+    async fn test_config_load_or_create() {
+        let mut temp_dir = std::env::temp_dir();
+        temp_dir.push("hap-first-boot");
+
+        let mut storage = FileStorage::new(&temp_dir).await.unwrap();
+        storage.delete_config().await.ok();
+
+        // on first boot, a config with a random pin should be created and saved
+        let created_config = Config::load_or_create(&mut storage, Config::default).await.unwrap();
+        let saved_config = storage.load_config().await.unwrap();
+        assert_eq!(saved_config.pin, created_config.pin);
+
+        // on later boots, the saved config should be loaded
+        let loaded_config = Config::load_or_create(&mut storage, || panic!("config should be loaded"))
+            .await
+            .unwrap();
+        assert_eq!(loaded_config.pin, created_config.pin);
+        assert_eq!(loaded_config.setup_id, created_config.setup_id);
+
+        storage.delete_config().await.unwrap();
+    }
+
     #[tokio::test]
     async fn test_aid_cache_storage() {
         let mut aid_cache = vec![1, 2, 3, 4];
@@ -312,6 +366,28 @@ mod tests {
         assert!(saved_aid_cache.is_err());
     }
 
+    #[tokio::test]
+    async fn test_pair_setup_attempts_storage() {
+        let mut attempts = PairSetupAttempts::default();
+        attempts.record_failure(std::time::SystemTime::now());
+
+        let mut temp_dir = std::env::temp_dir();
+        temp_dir.push("hap-pair-setup-attempts");
+
+        let mut storage = FileStorage::new(&temp_dir).await.unwrap();
+        storage.delete_pair_setup_attempts().await.ok();
+
+        // without saved attempts, there should be none
+        assert_eq!(PairSetupAttempts::load(&storage).await.unwrap(), PairSetupAttempts::default());
+
+        storage.save_pair_setup_attempts(&attempts).await.unwrap();
+
+        // the attempts should be correctly saved
+        assert_eq!(PairSetupAttempts::load(&storage).await.unwrap(), attempts);
+
+        storage.delete_pair_setup_attempts().await.unwrap();
+    }
+
     /// Ensure we can correctly create, read, list and delete [`Pairing`](Pairing)s.
     #[tokio::test]
     async fn test_pairing_storage() {
@@ -1,7 +1,11 @@
 use async_trait::async_trait;
 use uuid::Uuid;
 
-use crate::{pairing::Pairing, Config, Result};
+use crate::{
+    pairing::{PairSetupAttempts, Pairing},
+    Config,
+    Result,
+};
 
 /// [`Storage`](Storage) is implemented by the persistent data storage methods HAP supports. Currently, that's just
 /// [`FileStorage`](crate::storage::FileStorage).
@@ -29,6 +33,12 @@ pub trait Storage: Send + Sync {
     async fn list_pairings(&self) -> Result<Vec<Pairing>>;
     /// Returns the count of [`Pairing`](Pairing)s stored on the [`Storage`](Storage).
     async fn count_pairings(&self) -> Result<usize>;
+    /// Loads the [`PairSetupAttempts`](PairSetupAttempts) from the [`Storage`](Storage).
+    async fn load_pair_setup_attempts(&self) -> Result<PairSetupAttempts>;
+    /// Saves the [`PairSetupAttempts`](PairSetupAttempts) to the [`Storage`](Storage).
+    async fn save_pair_setup_attempts(&mut self, attempts: &PairSetupAttempts) -> Result<()>;
+    /// Deletes the [`PairSetupAttempts`](PairSetupAttempts) from the [`Storage`](Storage).
+    async fn delete_pair_setup_attempts(&mut self) -> Result<()>;
     /// Loads arbitrary bytes from the [`Storage`](Storage).
     async fn load_bytes(&self, key: &str) -> Result<Vec<u8>>;
     /// Saves arbitrary bytes to the [`Storage`](Storage).
@@ -1,75 +1,115 @@
-use std::{cell, collections::HashMap, io, str};
+use std::{cell, io, str, time::Duration};
 
 use byteorder::{LittleEndian, WriteBytesExt};
+use bytes::{Bytes, BytesMut};
 use log::error;
 use srp::types::SrpAuthError;
 use thiserror::Error;
 
-use crate::{error, pairing::Permissions};
+use crate::{
+    characteristic::tlv8::{Tlv8Value, Tlv8Writer},
+    error,
+    pairing::Permissions,
+    Result,
+};
 
 /// Encodes a `Vec<(u8, Vec<u8>)>` in the format `(<Type>, <Value>)` to a `Vec<u8>` of concatenated TLVs.
 pub fn encode(tlvs: Vec<(u8, Vec<u8>)>) -> Vec<u8> {
-    let mut vec: Vec<u8> = Vec::new();
+    let mut writer = Tlv8Writer::new();
     for (t, v) in tlvs {
-        let length = v.len();
-        if length <= 255 {
-            vec.push(t);
-            vec.push(length as u8);
-            for byte in v {
-                vec.push(byte);
-            }
-        } else {
-            let mut l = length;
-            let mut p = 0;
-            while l > 255 {
-                vec.push(t);
-                vec.push(255);
-                for byte in &v[p..(p + 255)] {
-                    vec.push(*byte);
-                }
-                l -= 255;
-                p += 255;
-            }
-            if l > 0 {
-                vec.push(t);
-                vec.push(l as u8);
-                for byte in &v[p..(p + l)] {
-                    vec.push(*byte);
-                }
-            }
-        }
+        writer.raw(t, &v);
     }
-    vec
+
+    writer.into_bytes()
+}
+
+/// Maximum length of a single TLV fragment. Longer values are split into consecutive fragments of the same type.
+pub(crate) const MAX_FRAGMENT_LEN: usize = 255;
+
+/// A single decoded TLV item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Item {
+    pub tlv_type: u8,
+    pub value: Bytes,
+}
+
+/// A decoded TLV payload. The items are kept in the order they were received in, including repeated items and
+/// separators.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Payload {
+    items: Vec<Item>,
 }
 
-/// Decodes a slice of concatenated TLVs to a `HashMap<u8, Vec<u8>>` in the format `<Type, Value>`.
-pub fn decode(tlv: &[u8]) -> HashMap<u8, Vec<u8>> {
-    let mut hm = HashMap::new();
-    let mut buf: Vec<u8> = Vec::new();
+impl Payload {
+    /// Returns the items of the payload in order.
+    pub fn items(&self) -> &[Item] { &self.items }
+
+    /// Returns the items of the payload in order, consuming the payload.
+    pub fn into_items(self) -> Vec<Item> { self.items }
+
+    /// Returns the value of the first item of the given type.
+    pub fn get(&self, tlv_type: u8) -> Option<&Bytes> {
+        self.items.iter().find(|i| i.tlv_type == tlv_type).map(|i| &i.value)
+    }
+
+    /// Returns the first byte of the value of the first item of the given type, e.g. of a `State` or `Method` item.
+    pub fn get_u8(&self, tlv_type: u8) -> Option<u8> { self.get(tlv_type).and_then(|v| v.first().copied()) }
+
+    /// Returns the value of the first item of the given type as a little-endian integer of up to 4 bytes, e.g. of a
+    /// `Flags` item.
+    pub fn get_u32(&self, tlv_type: u8) -> Option<u32> {
+        self.get(tlv_type)
+            .filter(|v| v.len() <= 4)
+            .map(|v| v.iter().rev().fold(0, |acc, b| (acc << 8) | *b as u32))
+    }
+
+    /// Returns the values of all items of the given type in order.
+    pub fn get_all(&self, tlv_type: u8) -> impl Iterator<Item = &Bytes> {
+        self.items.iter().filter(move |i| i.tlv_type == tlv_type).map(|i| &i.value)
+    }
+
+    /// Splits a separated list, e.g. the pairings of a list pairings response separated by `Separator` items, into its
+    /// entries. The separator items themselves are dropped.
+    pub fn split(&self, separator: u8) -> impl Iterator<Item = Payload> + '_ {
+        self.items.split(move |i| i.tlv_type == separator).map(|items| Payload {
+            items: items.to_vec(),
+        })
+    }
+}
+
+/// Decodes a buffer of concatenated TLVs to a [`Payload`](Payload). Values of unfragmented items are slices of the
+/// buffer, fragmented items are merged into a single item. Truncated items result in an error.
+pub fn decode(tlv: Bytes) -> Result<Payload> {
+    let mut items: Vec<Item> = Vec::new();
+    let mut fragmented = false;
     let mut p = 0;
-    let mut pt = 0;
     while p < tlv.len() {
+        if p + 2 > tlv.len() {
+            return Err(error::Error::InvalidTlv8(format!("truncated item header at offset {}", p)));
+        }
         let t = tlv[p];
-        let l = tlv[p + 1];
-        if l < 255 {
-            if t != pt && !buf.is_empty() {
-                hm.insert(t, buf.clone());
-                buf.clear();
-            }
-            buf.extend_from_slice(&tlv[p + 2..p + 2 + l as usize]);
-            hm.insert(t, buf.clone());
-            buf.clear();
-        } else {
-            buf.extend_from_slice(&tlv[p + 2..p + 2 + l as usize]);
+        let l = tlv[p + 1] as usize;
+        if p + 2 + l > tlv.len() {
+            return Err(error::Error::InvalidTlv8(format!("truncated value of item {:#04x} at offset {}", t, p)));
         }
-        pt = t;
-        p = p + 2 + l as usize;
-    }
-    if !buf.is_empty() {
-        hm.insert(pt, buf.clone());
-        buf.clear();
+        let value = tlv.slice(p + 2..p + 2 + l);
+
+        // a fragment of 255 bytes is continued by the next item if that has the same type
+        match items.last_mut() {
+            Some(last) if fragmented && last.tlv_type == t => {
+                let mut merged = BytesMut::with_capacity(last.value.len() + l);
+                merged.extend_from_slice(&last.value);
+                merged.extend_from_slice(&value);
+                last.value = merged.freeze();
+            },
+            _ => items.push(Item { tlv_type: t, value }),
+        }
+
+        fragmented = l == MAX_FRAGMENT_LEN;
+        p += 2 + l;
     }
-    hm
+
+    Ok(Payload { items })
 }
 
 /// `Encodable` is implemented by types that can be encoded to a to a `Vec<u8>` of concatenated
@@ -95,6 +135,8 @@ pub enum Type {
     Permissions = 0x0B,
     FragmentData = 0x0C,
     FragmentLast = 0x0D,
+    /// Session ID of a session to resume (8 bytes).
+    SessionId = 0x0E,
     /// Pairing Type Flags (32 bit unsigned integer).
     Flags = 0x13,
     Separator = 0xFF,
@@ -118,6 +160,7 @@ pub enum Value {
     Permissions(Permissions),
     FragmentData(Vec<u8>),
     FragmentLast(Vec<u8>),
+    SessionId(Vec<u8>),
     Flags(u32),
     Separator,
 }
@@ -145,6 +188,7 @@ impl Value {
             Value::Permissions(permissions) => (Type::Permissions as u8, vec![permissions.as_byte()]),
             Value::FragmentData(fragment_data) => (Type::FragmentData as u8, fragment_data),
             Value::FragmentLast(fragment_last) => (Type::FragmentLast as u8, fragment_last),
+            Value::SessionId(session_id) => (Type::SessionId as u8, session_id),
             Value::Flags(flags) => {
                 let mut vec: Vec<u8> = Vec::new();
                 vec.write_u32::<LittleEndian>(flags).unwrap();
@@ -158,11 +202,22 @@ impl Value {
 #[allow(dead_code)]
 #[derive(Debug, Copy, Clone)]
 pub enum Method {
-    PairSetup = 1,
+    PairSetup = 0,
+    PairSetupWithAuth = 1,
     PairVerify = 2,
     AddPairing = 3,
     RemovePairing = 4,
     ListPairings = 5,
+    PairResume = 6,
+}
+
+/// Pairing Type Flags a controller can request in pair setup M1.
+#[derive(Debug, Copy, Clone)]
+pub enum PairingFlag {
+    /// Transient pair setup, which only establishes a session and doesn't add a pairing.
+    Transient = 0x10,
+    /// Split pair setup, which reuses the setup code verifier of a preceding transient pair setup.
+    Split = 0x0100_0000,
 }
 
 #[allow(dead_code)]
@@ -254,6 +309,10 @@ impl From<ed25519_dalek::SignatureError> for Error {
     }
 }
 
+impl<T: Tlv8Value> Encodable for T {
+    fn encode(self) -> Vec<u8> { Tlv8Value::encode(&self) }
+}
+
 pub type Container = Vec<Value>;
 
 impl Encodable for Container {
@@ -263,12 +322,162 @@ impl Encodable for Container {
 pub struct ErrorContainer {
     step: u8,
     error: Error,
+    retry_delay: Option<Duration>,
 }
 
 impl ErrorContainer {
-    pub fn new(step: u8, error: Error) -> ErrorContainer { ErrorContainer { step, error } }
+    pub fn new(step: u8, error: Error) -> ErrorContainer {
+        ErrorContainer {
+            step,
+            error,
+            retry_delay: None,
+        }
+    }
+
+    /// Creates a `Backoff` error telling the controller to wait for the given delay before retrying.
+    pub fn backoff(step: u8, retry_delay: Duration) -> ErrorContainer {
+        ErrorContainer {
+            step,
+            error: Error::Backoff,
+            retry_delay: Some(retry_delay),
+        }
+    }
 }
 
 impl Encodable for ErrorContainer {
-    fn encode(self) -> Vec<u8> { vec![Value::State(self.step), Value::Error(self.error)].encode() }
+    fn encode(self) -> Vec<u8> {
+        let mut container = vec![Value::State(self.step), Value::Error(self.error)];
+        if let Some(retry_delay) = self.retry_delay {
+            // the retry delay is given in whole seconds, so it's rounded up to not let the controller retry too early
+            let seconds = retry_delay.as_secs() + (retry_delay.subsec_nanos() > 0) as u64;
+            container.push(Value::RetryDelay(seconds as usize));
+        }
+
+        container.encode()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_flags() {
+        // integers are encoded little-endian with as few bytes as possible
+        let payload = decode(Bytes::from_static(&[0x13, 0x01, 0x10, 0x00, 0x01, 0x01])).unwrap();
+        assert_eq!(payload.get_u32(Type::Flags as u8), Some(PairingFlag::Transient as u32));
+        assert_eq!(payload.get_u32(Type::Method as u8), Some(Method::PairSetupWithAuth as u32));
+
+        let payload = decode(Bytes::from_static(&[0x13, 0x04, 0x10, 0x00, 0x00, 0x01])).unwrap();
+        assert_eq!(
+            payload.get_u32(Type::Flags as u8),
+            Some(PairingFlag::Transient as u32 | PairingFlag::Split as u32)
+        );
+    }
+
+    #[test]
+    fn test_decode_ordered() {
+        let payload = decode(Bytes::from_static(&[
+            0x06, 0x01, 0x01, 0x01, 0x02, 0x61, 0x62, 0xFF, 0x00, 0x01, 0x01, 0x63,
+        ]))
+        .unwrap();
+
+        assert_eq!(payload.get_u8(Type::State as u8), Some(1));
+        assert_eq!(
+            payload.get_all(Type::Identifier as u8).collect::<Vec<_>>(),
+            vec![&Bytes::from_static(b"ab"), &Bytes::from_static(b"c")]
+        );
+
+        let types = payload.items().iter().map(|i| i.tlv_type).collect::<Vec<_>>();
+        assert_eq!(types, vec![0x06, 0x01, 0xFF, 0x01]);
+    }
+
+    #[test]
+    fn test_split() {
+        let mut tlv = Vec::new();
+        for (i, identifier) in ["a", "b", "c"].iter().enumerate() {
+            if i > 0 {
+                tlv.extend(encode(vec![(Type::Separator as u8, vec![])]));
+            }
+            tlv.extend(encode(vec![
+                (Type::Identifier as u8, identifier.as_bytes().to_vec()),
+                (Type::Permissions as u8, vec![i as u8]),
+            ]));
+        }
+
+        let entries = decode(Bytes::from(tlv)).unwrap().split(Type::Separator as u8).collect::<Vec<_>>();
+        assert_eq!(entries.len(), 3);
+        for (i, entry) in entries.iter().enumerate() {
+            assert_eq!(entry.items().len(), 2);
+            assert_eq!(entry.get(Type::Identifier as u8), Some(&Bytes::from(["a", "b", "c"][i])));
+            assert_eq!(entry.get_u8(Type::Permissions as u8), Some(i as u8));
+        }
+
+        // a payload without separators is a single entry
+        let entries = decode(Bytes::from_static(&[0x01, 0x01, 0x61])).unwrap().split(0x00).collect::<Vec<_>>();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].get(Type::Identifier as u8), Some(&Bytes::from_static(b"a")));
+    }
+
+    #[test]
+    fn test_decode_fragments() {
+        let certificate = (0..600).map(|i| i as u8).collect::<Vec<u8>>();
+        let mut tlv = encode(vec![(Type::Certificate as u8, certificate.clone()), (Type::State as u8, vec![2])]);
+        tlv.extend(encode(vec![(Type::Signature as u8, vec![0; 255]), (Type::Signature as u8, vec![1])]));
+
+        let payload = decode(Bytes::from(tlv)).unwrap();
+
+        assert_eq!(payload.items().len(), 3);
+        assert_eq!(payload.get(Type::Certificate as u8).unwrap()[..], certificate[..]);
+        assert_eq!(payload.get_u8(Type::State as u8), Some(2));
+        assert_eq!(payload.get(Type::Signature as u8).unwrap().len(), 256);
+    }
+
+    #[test]
+    fn test_derive() {
+        #[derive(Debug, PartialEq, crate::characteristic::tlv8::Tlv8)]
+        struct AddPairing {
+            #[tlv(type = Type::State)]
+            state: u8,
+            #[tlv(type = Type::Identifier)]
+            identifier: String,
+            #[tlv(type = Type::Permissions)]
+            permissions: u8,
+            #[tlv(type = Type::Flags)]
+            flags: Option<u32>,
+        }
+
+        let add_pairing = AddPairing {
+            state: 1,
+            identifier: "controller".into(),
+            permissions: 1,
+            flags: None,
+        };
+        let bytes = vec![
+            Value::State(1),
+            Value::Identifier("controller".into()),
+            Value::Permissions(Permissions::Admin),
+        ]
+        .encode();
+
+        assert_eq!(Tlv8Value::encode(&add_pairing), bytes);
+        assert_eq!(AddPairing::decode(bytes.into()).unwrap(), add_pairing);
+    }
+
+    #[test]
+    fn test_decode_truncated() {
+        assert!(decode(Bytes::from_static(&[0x06])).is_err());
+        assert!(decode(Bytes::from_static(&[0x06, 0x02, 0x01])).is_err());
+        assert!(decode(Bytes::from_static(&[0x06, 0x01, 0x01, 0x03, 0x20, 0x00])).is_err());
+        assert_eq!(decode(Bytes::new()).unwrap().items().len(), 0);
+    }
+
+    #[test]
+    fn test_error_container_retry_delay() {
+        let payload = decode(ErrorContainer::backoff(2, Duration::from_millis(1500)).encode().into()).unwrap();
+
+        assert_eq!(payload.get_u8(Type::State as u8), Some(2));
+        assert_eq!(payload.get_u8(Type::Error as u8), Some(Error::Backoff as u8));
+        assert_eq!(payload.get(Type::RetryDelay as u8), Some(&Bytes::from_static(&[0x02, 0x00])));
+    }
 }
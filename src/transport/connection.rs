@@ -0,0 +1,489 @@
+use futures::future;
+use log::{debug, info};
+use std::{
+    net::SocketAddr,
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::{
+    task::JoinHandle,
+    time::{sleep, sleep_until},
+};
+use uuid::Uuid;
+
+use crate::{event::Event, pointer};
+
+/// Information about a live connection of the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionInfo {
+    /// Address of the peer of the connection.
+    pub peer_addr: SocketAddr,
+    /// ID of the controller verified on the connection, if it's verified.
+    pub controller_id: Option<Uuid>,
+    /// Time the connection was accepted at.
+    pub connected_at: SystemTime,
+    /// The `(aid, iid)` pairs of the characteristics the connection is subscribed to.
+    pub event_subscriptions: Vec<(u64, u64)>,
+}
+
+/// Counters of the connections of the server and of the connection policies applied to them.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionStats {
+    /// Number of connections accepted.
+    pub accepted: u64,
+    /// Number of connections refused because the connection limit was reached and every connection had an established
+    /// session.
+    pub refused: u64,
+    /// Number of connections without an established session closed to make room for new ones.
+    pub evicted: u64,
+    /// Number of connections closed because they didn't establish a session in time.
+    pub pair_verify_timeouts: u64,
+    /// Number of connections closed because they were idle for too long.
+    pub idle_timeouts: u64,
+}
+
+/// A timeout a connection is closed after.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timeout {
+    /// The connection didn't establish a session via pair verify, pair resume or transient pair setup in time.
+    PairVerify,
+    /// The controller didn't send a request for too long.
+    Idle,
+}
+
+/// Handles to the state of a connection, shared by the task driving it, its HTTP handlers and the `ConnectionRegistry`.
+#[derive(Clone)]
+pub struct ConnectionState {
+    /// ID of the connection.
+    pub id: u64,
+    /// Address of the peer of the connection.
+    pub peer_addr: SocketAddr,
+    /// ID of the controller verified on the connection, if it's verified.
+    pub controller_id: pointer::ControllerId,
+    /// Whether a session was established on the connection.
+    pub session_established: pointer::SessionEstablished,
+    /// Time the last request was received on the connection.
+    pub last_activity: pointer::LastActivity,
+    /// The `(aid, iid)` pairs of the characteristics the connection is subscribed to.
+    pub event_subscriptions: pointer::EventSubscriptions,
+}
+
+/// A TCP connection to a controller, represented by the task driving it.
+struct Connection {
+    id: u64,
+    peer_addr: SocketAddr,
+    connected_at: SystemTime,
+    controller_id: pointer::ControllerId,
+    session_established: pointer::SessionEstablished,
+    event_subscriptions: pointer::EventSubscriptions,
+    notifier: JoinHandle<()>,
+    task: JoinHandle<()>,
+}
+
+/// Keeps track of the live connections of the server so they can be torn down. Removing a connection from the registry
+/// also stops the task sending its event notifications, drops its event subscriptions and ends a pair setup it was
+/// performing. Opened and closed connections are announced on the `EventEmitter`.
+pub struct ConnectionRegistry {
+    next_id: u64,
+    connections: Vec<Connection>,
+    event_emitter: pointer::EventEmitter,
+    pairing_coordinator: pointer::PairingCoordinator,
+    stats: ConnectionStats,
+}
+
+impl ConnectionRegistry {
+    /// Creates a new `ConnectionRegistry`.
+    pub fn new(event_emitter: pointer::EventEmitter, pairing_coordinator: pointer::PairingCoordinator) -> Self {
+        ConnectionRegistry {
+            next_id: 0,
+            connections: Vec::new(),
+            event_emitter,
+            pairing_coordinator,
+            stats: ConnectionStats::default(),
+        }
+    }
+
+    /// Returns the ID the next registered connection will get.
+    pub fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    /// Registers the task driving a connection, along with the state of the connection and the task sending its event
+    /// notifications.
+    pub fn add(&mut self, state: ConnectionState, notifier: JoinHandle<()>, task: JoinHandle<()>) {
+        let peer_addr = state.peer_addr;
+        self.connections.push(Connection {
+            id: state.id,
+            peer_addr,
+            connected_at: SystemTime::now(),
+            controller_id: state.controller_id,
+            session_established: state.session_established,
+            event_subscriptions: state.event_subscriptions,
+            notifier,
+            task,
+        });
+        self.stats.accepted += 1;
+        self.event_emitter.emit(Event::ConnectionOpened { peer_addr });
+    }
+
+    /// Makes room for a new connection from `peer_addr` if `max_connections` are live, by closing the oldest
+    /// connections without an established session. Returns `false` if the new connection has to be refused because a
+    /// session was established on every live connection.
+    pub async fn make_room(&mut self, max_connections: usize, peer_addr: SocketAddr) -> bool {
+        while self.connections.len() >= max_connections {
+            let unestablished = self
+                .connections
+                .iter()
+                .position(|c| !c.session_established.load(Ordering::SeqCst));
+            match unestablished {
+                Some(position) => {
+                    let connection = self.connections.remove(position);
+                    info!(
+                        "connection limit of {} reached; closing sessionless connection {} from {} to make room for {}",
+                        max_connections, connection.id, connection.peer_addr, peer_addr,
+                    );
+                    connection.task.abort();
+                    self.clean_up(&connection).await;
+                    self.stats.evicted += 1;
+                },
+                None => {
+                    info!("connection limit of {} reached; refusing connection from {}", max_connections, peer_addr);
+                    self.stats.refused += 1;
+                    return false;
+                },
+            }
+        }
+
+        true
+    }
+
+    /// Removes a connection that was closed after a `Timeout` from the registry.
+    pub async fn time_out(&mut self, id: u64, timeout: Timeout) {
+        match timeout {
+            Timeout::PairVerify => {
+                info!("closing connection {} as it didn't finish pair verify in time", id);
+                self.stats.pair_verify_timeouts += 1;
+            },
+            Timeout::Idle => {
+                info!("closing connection {} as it was idle for too long", id);
+                self.stats.idle_timeouts += 1;
+            },
+        }
+        self.remove(id).await;
+    }
+
+    /// Returns the counters of the connections and the connection policies applied to them.
+    pub fn stats(&self) -> ConnectionStats { self.stats }
+
+    /// Removes a connection from the registry without closing it. Called once the connection has ended by itself.
+    pub async fn remove(&mut self, id: u64) {
+        if let Some(position) = self.connections.iter().position(|c| c.id == id) {
+            let connection = self.connections.remove(position);
+            self.clean_up(&connection).await;
+        }
+    }
+
+    /// Returns information about every live connection.
+    pub async fn connections(&self) -> Vec<ConnectionInfo> {
+        let mut infos = Vec::with_capacity(self.connections.len());
+        for connection in &self.connections {
+            let controller_id = *connection.controller_id.read().expect("reading controller_id");
+            infos.push(ConnectionInfo {
+                peer_addr: connection.peer_addr,
+                controller_id,
+                connected_at: connection.connected_at,
+                event_subscriptions: connection.event_subscriptions.lock().await.clone(),
+            });
+        }
+        infos
+    }
+
+    /// Tears down the connections of a controller, e.g. after its pairing was removed. The event subscriptions of the
+    /// connections are dropped and the connections are no longer considered verified.
+    ///
+    /// Every connection but the `current` one, i.e. the one the removal was requested on, is closed right away. The
+    /// current connection is left open so the response to the request can still be sent on it.
+    pub async fn close_controller(&mut self, controller_id: &Uuid, current: Option<&pointer::ControllerId>) {
+        let mut closed = Vec::new();
+        for connection in &self.connections {
+            if *connection.controller_id.read().expect("reading controller_id") != Some(*controller_id) {
+                continue;
+            }
+
+            connection.event_subscriptions.lock().await.clear();
+            *connection.controller_id.write().expect("setting controller_id") = None;
+
+            if current.map(|c| Arc::ptr_eq(c, &connection.controller_id)) != Some(true) {
+                debug!("closing connection {} of controller {}", connection.id, controller_id);
+                connection.task.abort();
+                closed.push(connection.id);
+            }
+        }
+
+        for id in closed {
+            self.remove(id).await;
+        }
+    }
+
+    /// Closes every live connection by aborting the task driving it, which drops the underlying TCP stream.
+    pub async fn close_all(&mut self) {
+        for connection in std::mem::take(&mut self.connections) {
+            debug!("closing connection {}", connection.id);
+            connection.task.abort();
+            self.clean_up(&connection).await;
+        }
+    }
+
+    /// Stops the event notifications, drops the event subscriptions and ends the pair setup of a connection that's
+    /// gone, so the pair setup doesn't block other controllers until it times out. The task driving an aborted
+    /// connection never gets to do that itself.
+    async fn clean_up(&self, connection: &Connection) {
+        connection.notifier.abort();
+        connection.event_subscriptions.lock().await.clear();
+        self.pairing_coordinator.lock().await.end_pair_setup(connection.id);
+        self.event_emitter.emit(Event::ConnectionClosed {
+            peer_addr: connection.peer_addr,
+        });
+    }
+}
+
+/// Resolves once a connection has to be closed, either because no session was established on it within the
+/// `pair_verify_timeout`, or because no request was received on it within the `idle_timeout`. Never resolves if both
+/// timeouts are `None`.
+pub async fn watchdog(
+    pair_verify_timeout: Option<Duration>,
+    idle_timeout: Option<Duration>,
+    session_established: pointer::SessionEstablished,
+    last_activity: pointer::LastActivity,
+) -> Timeout {
+    if let Some(pair_verify_timeout) = pair_verify_timeout {
+        sleep(pair_verify_timeout).await;
+        if !session_established.load(Ordering::SeqCst) {
+            return Timeout::PairVerify;
+        }
+    }
+
+    let idle_timeout = match idle_timeout {
+        Some(idle_timeout) => idle_timeout,
+        None => return future::pending().await,
+    };
+    loop {
+        let deadline = *last_activity.read().expect("reading last_activity") + idle_timeout;
+        if deadline <= Instant::now() {
+            return Timeout::Idle;
+        }
+        sleep_until(deadline.into()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::{channel::oneshot, lock::Mutex, StreamExt};
+    use std::{
+        net::{IpAddr, Ipv4Addr},
+        sync::{atomic::AtomicBool, RwLock},
+    };
+
+    use crate::{event::EventEmitter, storage::FileStorage, transport::pairing_coordinator::PairingCoordinator};
+
+    fn peer_addr(port: u16) -> SocketAddr { SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2)), port) }
+
+    fn connection_state(id: u64, port: u16, controller_id: Option<Uuid>, session_established: bool) -> ConnectionState {
+        ConnectionState {
+            id,
+            peer_addr: peer_addr(port),
+            controller_id: Arc::new(RwLock::new(controller_id)),
+            session_established: Arc::new(AtomicBool::new(session_established)),
+            last_activity: Arc::new(RwLock::new(Instant::now())),
+            event_subscriptions: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    async fn pairing_coordinator() -> pointer::PairingCoordinator {
+        let mut temp_dir = std::env::temp_dir();
+        temp_dir.push("hap-connection-registry");
+
+        let storage: pointer::Storage = Arc::new(Mutex::new(Box::new(FileStorage::new(&temp_dir).await.unwrap())));
+        Arc::new(Mutex::new(PairingCoordinator::new(storage)))
+    }
+
+    #[tokio::test]
+    async fn test_close_controller() {
+        let controller = Uuid::parse_str("bc158b86-cabf-432d-aee4-422ef0e3f1d5").unwrap();
+        let other_controller = Uuid::parse_str("8a2f9b0e-3c5d-4c1a-9f1e-7d2b6a4c3e10").unwrap();
+
+        let mut registry = ConnectionRegistry::new(Arc::new(EventEmitter::new()), pairing_coordinator().await);
+        let mut connections = Vec::new();
+        for controller_id in [controller, controller, other_controller] {
+            let state = connection_state(registry.next_id(), 0, Some(controller_id), true);
+            state.event_subscriptions.lock().await.push((1, 10));
+            connections.push((state.controller_id.clone(), state.event_subscriptions.clone()));
+            let notifier = tokio::spawn(futures::future::pending());
+            let task = tokio::spawn(futures::future::pending());
+            registry.add(state, notifier, task);
+        }
+
+        registry.close_controller(&controller, Some(&connections[0].0)).await;
+
+        // the current connection stays open, but is no longer verified and has no subscriptions
+        assert_eq!(registry.connections.iter().map(|c| c.id).collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(*connections[0].0.read().unwrap(), None);
+        assert!(connections[0].1.lock().await.is_empty());
+        assert_eq!(*connections[1].0.read().unwrap(), None);
+        assert!(connections[1].1.lock().await.is_empty());
+
+        // connections of other controllers are left alone
+        assert_eq!(*connections[2].0.read().unwrap(), Some(other_controller));
+        assert_eq!(connections[2].1.lock().await.len(), 1);
+
+        registry.close_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_clean_up_on_removal() {
+        let controller = Uuid::parse_str("bc158b86-cabf-432d-aee4-422ef0e3f1d5").unwrap();
+        let event_emitter: pointer::EventEmitter = Arc::new(EventEmitter::new());
+        let mut events = event_emitter.subscribe();
+        let mut registry = ConnectionRegistry::new(event_emitter, pairing_coordinator().await);
+
+        let mut connections = Vec::new();
+        for (port, controller_id) in [(50001, Some(controller)), (50002, None)] {
+            let state = connection_state(registry.next_id(), port, controller_id, controller_id.is_some());
+            state.event_subscriptions.lock().await.push((1, 10));
+            // the notifier drops the sender once it's stopped
+            let (sender, notifier_stopped) = oneshot::channel::<()>();
+            let notifier = tokio::spawn(async move {
+                let _sender = sender;
+                futures::future::pending::<()>().await
+            });
+            let task = tokio::spawn(futures::future::pending());
+            connections.push((state.id, state.event_subscriptions.clone(), notifier_stopped));
+            registry.add(state, notifier, task);
+            assert_eq!(events.next().await, Some(Event::ConnectionOpened {
+                peer_addr: peer_addr(port),
+            }));
+        }
+
+        let infos = registry.connections().await;
+        assert_eq!(infos.len(), 2);
+        assert_eq!(infos[0].peer_addr, peer_addr(50001));
+        assert_eq!(infos[0].controller_id, Some(controller));
+        assert_eq!(infos[0].event_subscriptions, vec![(1, 10)]);
+        assert_eq!(infos[1].controller_id, None);
+
+        // the notifier and subscriptions of a removed connection are gone
+        let (id, event_subscriptions, notifier_stopped) = connections.remove(0);
+        registry.remove(id).await;
+        assert!(event_subscriptions.lock().await.is_empty());
+        assert!(notifier_stopped.await.is_err());
+        assert_eq!(registry.connections().await.len(), 1);
+        assert_eq!(events.next().await, Some(Event::ConnectionClosed {
+            peer_addr: peer_addr(50001),
+        }));
+
+        // the same goes for aborted connections
+        let (_, event_subscriptions, notifier_stopped) = connections.remove(0);
+        registry.close_all().await;
+        assert!(event_subscriptions.lock().await.is_empty());
+        assert!(notifier_stopped.await.is_err());
+        assert!(registry.connections().await.is_empty());
+        assert_eq!(events.next().await, Some(Event::ConnectionClosed {
+            peer_addr: peer_addr(50002),
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_make_room() {
+        let controller = Uuid::parse_str("bc158b86-cabf-432d-aee4-422ef0e3f1d5").unwrap();
+        let mut registry = ConnectionRegistry::new(Arc::new(EventEmitter::new()), pairing_coordinator().await);
+
+        // the session of a transient pair setup has no controller ID
+        let connections = [
+            (50001, Some(controller), true),
+            (50002, None, false),
+            (50003, None, true),
+            (50004, None, false),
+        ];
+        for (port, controller_id, session_established) in connections {
+            let state = connection_state(registry.next_id(), port, controller_id, session_established);
+            let notifier = tokio::spawn(futures::future::pending());
+            let task = tokio::spawn(futures::future::pending());
+            registry.add(state, notifier, task);
+        }
+
+        // the oldest connection without a session makes room
+        assert!(registry.make_room(4, peer_addr(50005)).await);
+        let peer_addrs = registry.connections().await.iter().map(|c| c.peer_addr).collect::<Vec<_>>();
+        assert_eq!(peer_addrs, vec![peer_addr(50001), peer_addr(50003), peer_addr(50004)]);
+
+        // lowering the limit evicts as many connections without a session as necessary, but never ones with a session
+        assert!(!registry.make_room(1, peer_addr(50005)).await);
+        let peer_addrs = registry.connections().await.iter().map(|c| c.peer_addr).collect::<Vec<_>>();
+        assert_eq!(peer_addrs, vec![peer_addr(50001), peer_addr(50003)]);
+
+        assert_eq!(registry.stats(), ConnectionStats {
+            accepted: 4,
+            refused: 1,
+            evicted: 2,
+            pair_verify_timeouts: 0,
+            idle_timeouts: 0,
+        });
+
+        registry.close_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_end_pair_setup_on_eviction() {
+        let pairing_coordinator = pairing_coordinator().await;
+        let mut registry = ConnectionRegistry::new(Arc::new(EventEmitter::new()), pairing_coordinator.clone());
+
+        for port in [50001, 50002] {
+            let state = connection_state(registry.next_id(), port, None, false);
+            let notifier = tokio::spawn(futures::future::pending());
+            let task = tokio::spawn(futures::future::pending());
+            registry.add(state, notifier, task);
+        }
+        pairing_coordinator.lock().await.start_pair_setup(1).await.unwrap();
+        assert!(pairing_coordinator.lock().await.start_pair_setup(2).await.is_err());
+
+        // evicting the pairing connection lets another one start a pair setup right away
+        assert!(registry.make_room(2, peer_addr(50003)).await);
+        assert!(!pairing_coordinator.lock().await.is_pairing(1));
+        pairing_coordinator.lock().await.start_pair_setup(2).await.unwrap();
+
+        // the same goes for connections closed on shutdown
+        registry.close_all().await;
+        assert!(!pairing_coordinator.lock().await.is_pairing(2));
+        pairing_coordinator.lock().await.start_pair_setup(3).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_watchdog() {
+        let timeout = Some(Duration::from_millis(10));
+        let last_activity: pointer::LastActivity = Arc::new(RwLock::new(Instant::now()));
+
+        let unestablished = Arc::new(AtomicBool::new(false));
+        let res = watchdog(timeout, None, unestablished, last_activity).await;
+        assert_eq!(res, Timeout::PairVerify);
+
+        // connections with a session, e.g. of a transient pair setup, are only closed once they're idle
+        let established = Arc::new(AtomicBool::new(true));
+        let started = Instant::now();
+        let last_activity: pointer::LastActivity = Arc::new(RwLock::new(started));
+        let last_activity_ = last_activity.clone();
+        tokio::spawn(async move {
+            sleep(Duration::from_millis(15)).await;
+            *last_activity_.write().unwrap() = Instant::now();
+        });
+        let res = watchdog(timeout, Some(Duration::from_millis(30)), established.clone(), last_activity).await;
+        assert_eq!(res, Timeout::Idle);
+        assert!(started.elapsed() >= Duration::from_millis(45));
+
+        let last_activity: pointer::LastActivity = Arc::new(RwLock::new(Instant::now()));
+        let never = tokio::time::timeout(Duration::from_millis(30), watchdog(None, None, established, last_activity));
+        assert!(never.await.is_err());
+    }
+}
@@ -20,7 +20,7 @@ impl JsonHandlerExt for Accessories {
         _: Uri,
         _: Body,
         _: pointer::ControllerId,
-        _: pointer::EventSubscriptions,
+        event_subscriptions: pointer::EventSubscriptions,
         _: pointer::Config,
         _: pointer::Storage,
         accessory_database: pointer::AccessoryDatabase,
@@ -28,7 +28,11 @@ impl JsonHandlerExt for Accessories {
     ) -> BoxFuture<Result<Response<Body>>> {
         info!("received list accessories request");
         async move {
-            let resp_body = accessory_database.lock().await.as_serialized_json().await?;
+            let resp_body = accessory_database
+                .lock()
+                .await
+                .as_serialized_json(&event_subscriptions)
+                .await?;
             // let resp_body = serde_json::to_vec(&accessory_database)?;
             json_response(resp_body, StatusCode::OK)
         }
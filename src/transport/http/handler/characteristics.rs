@@ -1,7 +1,11 @@
 use futures::future::{BoxFuture, FutureExt};
 use hyper::{body::Buf, Body, Response, StatusCode, Uri};
-use log::error;
-use std::collections::HashMap;
+use log::{debug, error};
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
 use url::form_urlencoded;
 
 use crate::{
@@ -11,9 +15,11 @@ use crate::{
         json_response,
         status_response,
         CharacteristicResponseBody,
+        PrepareObject,
+        PreparedWrite,
         ReadResponseObject,
         Status,
-        WriteObject,
+        WriteRequestBody,
         WriteResponseObject,
     },
     Error,
@@ -32,7 +38,7 @@ impl JsonHandlerExt for GetCharacteristics {
         uri: Uri,
         _: Body,
         _: pointer::ControllerId,
-        _: pointer::EventSubscriptions,
+        event_subscriptions: pointer::EventSubscriptions,
         _: pointer::Config,
         _: pointer::Storage,
         accessory_database: pointer::AccessoryDatabase,
@@ -63,7 +69,7 @@ impl JsonHandlerExt for GetCharacteristics {
                     let res_object = match accessory_database
                         .lock()
                         .await
-                        .read_characteristic(aid, iid, f_meta, f_perms, f_type, f_ev)
+                        .read_characteristic(aid, iid, f_meta, f_perms, f_type, f_ev.then_some(&event_subscriptions))
                         .await
                     {
                         Ok(mut res_object) => {
@@ -116,10 +122,12 @@ fn check_flags(flags: &HashMap<String, String>) -> (bool, bool, bool, bool) {
     )
 }
 
-pub struct UpdateCharacteristics;
+pub struct UpdateCharacteristics {
+    prepared_write: pointer::PreparedWrite,
+}
 
 impl UpdateCharacteristics {
-    pub fn new() -> Self { UpdateCharacteristics {} }
+    pub fn new(prepared_write: pointer::PreparedWrite) -> Self { UpdateCharacteristics { prepared_write } }
 }
 
 impl JsonHandlerExt for UpdateCharacteristics {
@@ -134,15 +142,44 @@ impl JsonHandlerExt for UpdateCharacteristics {
         accessories: pointer::AccessoryDatabase,
         _: pointer::EventEmitter,
     ) -> BoxFuture<Result<Response<Body>>> {
+        let prepared_write = self.prepared_write.clone();
+
         async move {
             let aggregated_body = hyper::body::aggregate(body).await?;
 
-            let write_body: CharacteristicResponseBody<WriteObject> = serde_json::from_slice(aggregated_body.chunk())?;
+            let write_body: WriteRequestBody = serde_json::from_slice(aggregated_body.chunk())?;
             let mut resp_body = CharacteristicResponseBody::<WriteResponseObject> {
                 characteristics: Vec::new(),
             };
+
+            // a write carrying a `pid` executes a timed write transaction and is only valid if it was announced via
+            // `PUT /prepare` and arrives before its TTL expires
+            let timed = match write_body.pid {
+                Some(pid) => {
+                    let prepared = prepared_write.lock().await.take();
+                    match prepared {
+                        Some(p) if p.pid == pid && Instant::now() <= p.deadline => true,
+                        _ => {
+                            debug!("rejecting timed write with expired or unknown pid {}", pid);
+                            for c in write_body.characteristics {
+                                resp_body.characteristics.push(WriteResponseObject {
+                                    iid: c.iid,
+                                    aid: c.aid,
+                                    status: Status::InvalidValueInRequest as i32,
+                                    value: None,
+                                });
+                            }
+                            let res = serde_json::to_vec(&resp_body)?;
+                            return json_response(res, StatusCode::BAD_REQUEST);
+                        },
+                    }
+                },
+                None => false,
+            };
+
             let mut some_err = false;
             let mut all_err = true;
+            let mut some_response = false;
 
             for c in write_body.characteristics {
                 let iid = c.iid;
@@ -150,7 +187,7 @@ impl JsonHandlerExt for UpdateCharacteristics {
                 let res_object = match accessories
                     .lock()
                     .await
-                    .write_characteristic(c, &event_subscriptions)
+                    .write_characteristic(c, &event_subscriptions, timed)
                     .await
                 {
                     Ok(res_object) => {
@@ -159,6 +196,9 @@ impl JsonHandlerExt for UpdateCharacteristics {
                         } else {
                             all_err = false;
                         }
+                        if res_object.value.is_some() {
+                            some_response = true;
+                        }
                         res_object
                     },
                     Err(e) => {
@@ -168,6 +208,7 @@ impl JsonHandlerExt for UpdateCharacteristics {
                             iid,
                             aid,
                             status: Status::ServiceCommunicationFailure as i32,
+                            value: None,
                         }
                     },
                 };
@@ -178,7 +219,7 @@ impl JsonHandlerExt for UpdateCharacteristics {
             if all_err {
                 let res = serde_json::to_vec(&resp_body)?;
                 json_response(res, StatusCode::BAD_REQUEST)
-            } else if some_err {
+            } else if some_err || some_response {
                 let res = serde_json::to_vec(&resp_body)?;
                 json_response(res, StatusCode::MULTI_STATUS)
             } else {
@@ -188,3 +229,209 @@ impl JsonHandlerExt for UpdateCharacteristics {
         .boxed()
     }
 }
+
+pub struct PrepareCharacteristics {
+    prepared_write: pointer::PreparedWrite,
+}
+
+impl PrepareCharacteristics {
+    pub fn new(prepared_write: pointer::PreparedWrite) -> Self { PrepareCharacteristics { prepared_write } }
+}
+
+impl JsonHandlerExt for PrepareCharacteristics {
+    fn handle(
+        &mut self,
+        _: Uri,
+        body: Body,
+        _: pointer::ControllerId,
+        _: pointer::EventSubscriptions,
+        _: pointer::Config,
+        _: pointer::Storage,
+        _: pointer::AccessoryDatabase,
+        _: pointer::EventEmitter,
+    ) -> BoxFuture<Result<Response<Body>>> {
+        let prepared_write = self.prepared_write.clone();
+
+        async move {
+            let aggregated_body = hyper::body::aggregate(body).await?;
+
+            let prepare: PrepareObject = match serde_json::from_slice(aggregated_body.chunk()) {
+                Ok(prepare) => prepare,
+                Err(_) => {
+                    let body = serde_json::to_vec(&json!({ "status": Status::InvalidValueInRequest as i32 }))?;
+                    return json_response(body, StatusCode::BAD_REQUEST);
+                },
+            };
+
+            debug!("preparing timed write with pid {} and TTL {} ms", prepare.pid, prepare.ttl);
+
+            *prepared_write.lock().await = Some(PreparedWrite {
+                pid: prepare.pid,
+                deadline: Instant::now() + Duration::from_millis(prepare.ttl),
+            });
+
+            let body = serde_json::to_vec(&json!({ "status": Status::Success as i32 }))?;
+            json_response(body, StatusCode::OK)
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::lock::Mutex;
+    use serde_json::Value;
+    use std::sync::{Arc, RwLock};
+
+    use crate::{
+        accessory::{lightbulb::LightbulbAccessory, AccessoryInformation},
+        characteristic::{HapCharacteristic, Perm},
+        event::EventEmitter,
+        storage::{accessory_database::AccessoryDatabase, FileStorage},
+        Config,
+    };
+
+    /// Returns an accessory database holding a lightbulb, whose power state characteristic has the given permissions,
+    /// and the instance ID of that characteristic.
+    fn lightbulb_database(perms: Vec<Perm>) -> (pointer::AccessoryDatabase, u64) {
+        let event_emitter = Arc::new(EventEmitter::new());
+        let mut lightbulb = LightbulbAccessory::new(1, AccessoryInformation::default()).unwrap();
+        lightbulb.lightbulb.power_state.set_perms(perms);
+        let iid = lightbulb.lightbulb.power_state.get_id();
+
+        let mut accessory_database = AccessoryDatabase::new(event_emitter);
+        accessory_database.add_accessory(Box::new(lightbulb)).unwrap();
+
+        (Arc::new(Mutex::new(accessory_database)), iid)
+    }
+
+    /// Sends a request with a JSON body to a handler and returns the status and JSON body of the response.
+    async fn request<H: JsonHandlerExt>(
+        handler: &mut H,
+        body: Value,
+        accessory_database: &pointer::AccessoryDatabase,
+    ) -> (StatusCode, Value) {
+        let mut temp_dir = std::env::temp_dir();
+        temp_dir.push("hap-characteristics");
+
+        let storage: pointer::Storage = Arc::new(Mutex::new(Box::new(FileStorage::new(&temp_dir).await.unwrap())));
+        let response = handler
+            .handle(
+                Uri::from_static("/characteristics"),
+                Body::from(serde_json::to_vec(&body).unwrap()),
+                Arc::new(RwLock::new(None)),
+                Arc::new(Mutex::new(vec![])),
+                Arc::new(Mutex::new(Config::default())),
+                storage,
+                accessory_database.clone(),
+                Arc::new(EventEmitter::new()),
+            )
+            .await
+            .unwrap();
+
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body = match body.is_empty() {
+            true => Value::Null,
+            false => serde_json::from_slice(&body).unwrap(),
+        };
+
+        (status, body)
+    }
+
+    #[tokio::test]
+    async fn test_write_response() {
+        let (accessory_database, iid) = lightbulb_database(vec![Perm::PairedRead, Perm::PairedWrite]);
+        let mut handler = UpdateCharacteristics::new(Arc::new(Mutex::new(None)));
+
+        // characteristics without the `WriteResponse` permission can't be asked for a write response
+        let body = json!({ "characteristics": [{ "aid": 1, "iid": iid, "value": true, "r": true }] });
+        let (status, body) = request(&mut handler, body, &accessory_database).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body,
+            json!({ "characteristics": [{ "aid": 1, "iid": iid, "status": Status::InvalidValueInRequest as i32 }] })
+        );
+
+        let (accessory_database, iid) =
+            lightbulb_database(vec![Perm::PairedRead, Perm::PairedWrite, Perm::WriteResponse]);
+        let body = json!({ "characteristics": [{ "aid": 1, "iid": iid, "value": true, "r": true }] });
+        let (status, body) = request(&mut handler, body, &accessory_database).await;
+        assert_eq!(status, StatusCode::MULTI_STATUS);
+        assert_eq!(
+            body,
+            json!({ "characteristics": [{ "aid": 1, "iid": iid, "status": 0, "value": true }] })
+        );
+
+        // without asking for a write response, there is no response body
+        let body = json!({ "characteristics": [{ "aid": 1, "iid": iid, "value": false }] });
+        let (status, body) = request(&mut handler, body, &accessory_database).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_eq!(body, Value::Null);
+    }
+
+    #[tokio::test]
+    async fn test_timed_write() {
+        let (accessory_database, iid) = lightbulb_database(vec![Perm::PairedRead, Perm::PairedWrite, Perm::TimedWrite]);
+        let rejected = json!({
+            "characteristics": [{ "aid": 1, "iid": iid, "status": Status::InvalidValueInRequest as i32 }]
+        });
+
+        let prepared_write = Arc::new(Mutex::new(None));
+        let mut prepare = PrepareCharacteristics::new(prepared_write.clone());
+        let mut update = UpdateCharacteristics::new(prepared_write);
+
+        // characteristics with the `TimedWrite` permission can't be written without a prepared write
+        let write = json!({ "characteristics": [{ "aid": 1, "iid": iid, "value": true }] });
+        let (status, body) = request(&mut update, write, &accessory_database).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, rejected);
+
+        let (status, body) = request(&mut prepare, json!({ "ttl": 5000, "pid": 1 }), &accessory_database).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({ "status": 0 }));
+        let write = json!({ "characteristics": [{ "aid": 1, "iid": iid, "value": true }], "pid": 1 });
+        let (status, _) = request(&mut update, write, &accessory_database).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        // a prepared write can only be executed once
+        let write = json!({ "characteristics": [{ "aid": 1, "iid": iid, "value": false }], "pid": 1 });
+        let (status, body) = request(&mut update, write, &accessory_database).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, rejected);
+
+        // unknown pid
+        request(&mut prepare, json!({ "ttl": 5000, "pid": 2 }), &accessory_database).await;
+        let write = json!({ "characteristics": [{ "aid": 1, "iid": iid, "value": false }], "pid": 3 });
+        let (status, body) = request(&mut update, write, &accessory_database).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, rejected);
+
+        // expired TTL
+        request(&mut prepare, json!({ "ttl": 0, "pid": 4 }), &accessory_database).await;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let write = json!({ "characteristics": [{ "aid": 1, "iid": iid, "value": false }], "pid": 4 });
+        let (status, body) = request(&mut update, write, &accessory_database).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, rejected);
+
+        // pid prepared on another connection
+        request(&mut prepare, json!({ "ttl": 5000, "pid": 5 }), &accessory_database).await;
+        let mut other_update = UpdateCharacteristics::new(Arc::new(Mutex::new(None)));
+        let write = json!({ "characteristics": [{ "aid": 1, "iid": iid, "value": false }], "pid": 5 });
+        let (status, body) = request(&mut other_update, write, &accessory_database).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, rejected);
+
+        // none of the rejected writes changed the value
+        let read = accessory_database
+            .lock()
+            .await
+            .read_characteristic(1, iid, false, false, false, None)
+            .await
+            .unwrap();
+        assert_eq!(read.value, Some(json!(true)));
+    }
+}
@@ -1,8 +1,9 @@
 use aead::{generic_array::GenericArray, AeadInPlace, NewAead};
+use bytes::Bytes;
 use chacha20poly1305::ChaCha20Poly1305;
 use futures::future::{BoxFuture, FutureExt};
-use hyper::{body::Buf, Body};
-use log::{debug, info};
+use hyper::Body;
+use log::{debug, error, info, warn};
 use num::BigUint;
 use rand::{rngs::OsRng, RngCore};
 use sha2::{digest::Digest, Sha512};
@@ -13,15 +14,16 @@ use srp::{
     server::{SrpServer, UserRecord},
     types::SrpGroup,
 };
-use std::{ops::BitXor, str};
+use std::{ops::BitXor, str, time::SystemTime};
 use uuid::Uuid;
 
 use crate::{
+    characteristic::tlv8::{Tlv8, Tlv8Value},
     event::Event,
-    pairing::{Pairing, Permissions},
+    pairing::{PairSetupAttempts, Pairing, Permissions},
     pointer,
-    tlv::{self, Encodable, Type, Value},
-    transport::{hkdf_extract_and_expand, http::handler::TlvHandlerExt},
+    tlv::{self, Encodable, Method, PairingFlag, Type, Value},
+    transport::{hkdf_extract_and_expand, http::handler::TlvHandlerExt, pairing_coordinator::SplitSetup, tcp},
 };
 
 struct Session {
@@ -30,22 +32,59 @@ struct Session {
     b: [u8; 64],
     b_pub: Vec<u8>,
     shared_secret: Option<Vec<u8>>,
+    flags: u32,
+    with_auth: bool,
+}
+
+impl Session {
+    fn has_flag(&self, flag: PairingFlag) -> bool { self.flags & flag as u32 != 0 }
 }
 
 pub struct PairSetup {
     session: Option<Session>,
-    unsuccessful_tries: u8,
+    connection_id: u64,
+    pairing_coordinator: pointer::PairingCoordinator,
+    authenticator: pointer::Authenticator,
+    session_sender: pointer::SessionSender,
 }
 
 impl PairSetup {
-    pub fn new() -> PairSetup {
+    pub fn new(
+        connection_id: u64,
+        pairing_coordinator: pointer::PairingCoordinator,
+        authenticator: pointer::Authenticator,
+        session_sender: pointer::SessionSender,
+    ) -> PairSetup {
         PairSetup {
             session: None,
-            unsuccessful_tries: 0,
+            connection_id,
+            pairing_coordinator,
+            authenticator,
+            session_sender,
         }
     }
 }
 
+/// Encrypted sub-TLV proving the authenticity of the accessory in the M4 step.
+#[derive(Debug, Tlv8)]
+struct AuthenticationSubTlv {
+    #[tlv(type = Type::Signature)]
+    signature: Vec<u8>,
+    #[tlv(type = Type::Certificate)]
+    certificate: Vec<u8>,
+}
+
+/// Encrypted sub-TLV exchanged in the M5 and M6 steps.
+#[derive(Debug, Tlv8)]
+struct ExchangeSubTlv {
+    #[tlv(type = Type::Identifier)]
+    identifier: Vec<u8>,
+    #[tlv(type = Type::PublicKey)]
+    ltpk: Vec<u8>,
+    #[tlv(type = Type::Signature)]
+    signature: Vec<u8>,
+}
+
 #[derive(Debug, Clone)]
 enum StepNumber {
     Unknown = 0,
@@ -59,9 +98,9 @@ enum StepNumber {
 
 #[derive(Debug, Clone)]
 pub enum Step {
-    Start,
-    Verify { a_pub: Vec<u8>, a_proof: Vec<u8> },
-    Exchange { data: Vec<u8> },
+    Start { method: u8, flags: u32 },
+    Verify { a_pub: Bytes, a_proof: Bytes },
+    Exchange { data: Bytes },
 }
 
 impl TlvHandlerExt for PairSetup {
@@ -70,41 +109,38 @@ impl TlvHandlerExt for PairSetup {
 
     fn parse(&self, body: Body) -> BoxFuture<Result<Step, tlv::ErrorContainer>> {
         async {
-            let aggregated_body = hyper::body::aggregate(body)
+            let body = hyper::body::to_bytes(body)
                 .await
                 .map_err(|_| tlv::ErrorContainer::new(StepNumber::Unknown as u8, tlv::Error::Unknown))?;
 
-            debug!("received body: {:?}", aggregated_body.chunk());
-
-            let mut decoded = tlv::decode(aggregated_body.chunk());
-            match decoded.get(&(Type::State as u8)) {
-                Some(method) => match method[0] {
-                    x if x == StepNumber::SrpStartRequest as u8 => Ok(Step::Start),
-                    x if x == StepNumber::SrpVerifyRequest as u8 => {
-                        let a_pub = decoded
-                            .remove(&(Type::PublicKey as u8))
-                            .ok_or(tlv::ErrorContainer::new(
-                                StepNumber::SrpVerifyResponse as u8,
-                                tlv::Error::Unknown,
-                            ))?;
-                        let a_proof = decoded.remove(&(Type::Proof as u8)).ok_or(tlv::ErrorContainer::new(
-                            StepNumber::SrpVerifyResponse as u8,
-                            tlv::Error::Unknown,
-                        ))?;
-                        Ok(Step::Verify { a_pub, a_proof })
-                    },
-                    x if x == StepNumber::ExchangeRequest as u8 => {
-                        let data = decoded
-                            .remove(&(Type::EncryptedData as u8))
-                            .ok_or(tlv::ErrorContainer::new(
-                                StepNumber::ExchangeResponse as u8,
-                                tlv::Error::Unknown,
-                            ))?;
-                        Ok(Step::Exchange { data })
-                    },
-                    _ => Err(tlv::ErrorContainer::new(StepNumber::Unknown as u8, tlv::Error::Unknown)),
+            debug!("received body: {:?}", &body);
+
+            let decoded = tlv::decode(body)
+                .map_err(|_| tlv::ErrorContainer::new(StepNumber::Unknown as u8, tlv::Error::Unknown))?;
+            match decoded.get_u8(Type::State as u8) {
+                Some(x) if x == StepNumber::SrpStartRequest as u8 => Ok(Step::Start {
+                    method: decoded.get_u8(Type::Method as u8).unwrap_or(Method::PairSetup as u8),
+                    flags: decoded.get_u32(Type::Flags as u8).unwrap_or(0),
+                }),
+                Some(x) if x == StepNumber::SrpVerifyRequest as u8 => {
+                    let a_pub = decoded.get(Type::PublicKey as u8).cloned().ok_or(tlv::ErrorContainer::new(
+                        StepNumber::SrpVerifyResponse as u8,
+                        tlv::Error::Unknown,
+                    ))?;
+                    let a_proof = decoded.get(Type::Proof as u8).cloned().ok_or(tlv::ErrorContainer::new(
+                        StepNumber::SrpVerifyResponse as u8,
+                        tlv::Error::Unknown,
+                    ))?;
+                    Ok(Step::Verify { a_pub, a_proof })
                 },
-                None => Err(tlv::ErrorContainer::new(StepNumber::Unknown as u8, tlv::Error::Unknown)),
+                Some(x) if x == StepNumber::ExchangeRequest as u8 => {
+                    let data = decoded.get(Type::EncryptedData as u8).cloned().ok_or(tlv::ErrorContainer::new(
+                        StepNumber::ExchangeResponse as u8,
+                        tlv::Error::Unknown,
+                    ))?;
+                    Ok(Step::Exchange { data })
+                },
+                _ => Err(tlv::ErrorContainer::new(StepNumber::Unknown as u8, tlv::Error::Unknown)),
             }
         }
         .boxed()
@@ -119,72 +155,139 @@ impl TlvHandlerExt for PairSetup {
         event_emitter: pointer::EventEmitter,
     ) -> BoxFuture<Result<tlv::Container, tlv::ErrorContainer>> {
         async move {
-            match step {
-                Step::Start => match handle_start(self, config).await {
-                    Ok(res) => {
-                        self.unsuccessful_tries = 0;
-                        Ok(res)
-                    },
-                    Err(err) => {
-                        self.unsuccessful_tries += 1;
-                        Err(tlv::ErrorContainer::new(StepNumber::SrpStartResponse as u8, err))
-                    },
+            let res = match step {
+                Step::Start { method, flags } => {
+                    self.pairing_coordinator
+                        .lock()
+                        .await
+                        .start_pair_setup(self.connection_id)
+                        .await
+                        .map_err(|err| tlv::ErrorContainer::new(StepNumber::SrpStartResponse as u8, err))?;
+                    match check_attempts(&storage).await {
+                        Ok(()) => match handle_start(self, config, method, flags).await {
+                            Ok(res) => Ok(res),
+                            Err(err) => Err(tlv::ErrorContainer::new(StepNumber::SrpStartResponse as u8, err)),
+                        },
+                        Err(err) => Err(err),
+                    }
                 },
                 Step::Verify { a_pub, a_proof } => match handle_verify(self, &a_pub, &a_proof).await {
-                    Ok(res) => {
-                        self.unsuccessful_tries = 0;
-                        Ok(res)
-                    },
+                    Ok(res) => Ok(res),
                     Err(err) => {
-                        self.unsuccessful_tries += 1;
+                        if let tlv::Error::Authentication = err {
+                            record_failed_attempt(&storage).await;
+                        }
                         Err(tlv::ErrorContainer::new(StepNumber::SrpVerifyResponse as u8, err))
                     },
                 },
                 Step::Exchange { data } => match handle_exchange(self, config, storage, event_emitter, &data).await {
                     Ok(res) => {
-                        self.unsuccessful_tries = 0;
+                        self.session = None;
+                        self.pairing_coordinator.lock().await.end_pair_setup(self.connection_id);
                         Ok(res)
                     },
-                    Err(err) => {
-                        self.unsuccessful_tries += 1;
-                        Err(tlv::ErrorContainer::new(StepNumber::ExchangeResponse as u8, err))
-                    },
+                    Err(err) => Err(tlv::ErrorContainer::new(StepNumber::ExchangeResponse as u8, err)),
                 },
+            };
+
+            // a failed pair setup has to be started over at M1, so every new attempt has to pass the backoff and
+            // other controllers can pair in the meantime
+            if res.is_err() {
+                self.session = None;
+                self.pairing_coordinator.lock().await.end_pair_setup(self.connection_id);
             }
+
+            res
         }
         .boxed()
     }
 }
 
-async fn handle_start(handler: &mut PairSetup, config: pointer::Config) -> Result<tlv::Container, tlv::Error> {
-    info!("pair setup M1: received SRP start request");
+/// Refuses to start a pair setup while the controller has to back off after an unsuccessful attempt or after the
+/// maximum number of unsuccessful attempts.
+async fn check_attempts(storage: &pointer::Storage) -> Result<(), tlv::ErrorContainer> {
+    let attempts = PairSetupAttempts::load(storage.lock().await.as_ref())
+        .await
+        .map_err(|_| tlv::ErrorContainer::new(StepNumber::SrpStartResponse as u8, tlv::Error::Unknown))?;
+
+    if attempts.is_exhausted() {
+        warn!("pair setup M2: refusing pair setup after {} unsuccessful attempts", attempts.count);
+        return Err(tlv::ErrorContainer::new(StepNumber::SrpStartResponse as u8, tlv::Error::MaxTries));
+    }
+
+    if let Some(retry_delay) = attempts.retry_delay(SystemTime::now()) {
+        info!("pair setup M2: backing off for {:?}", &retry_delay);
+        return Err(tlv::ErrorContainer::backoff(StepNumber::SrpStartResponse as u8, retry_delay));
+    }
 
-    // TODO
-    // If the accessory is already paired, it must respond with the following TLV items:
-    // kTLVType_State <M2>
-    // kTLVType_Error <kTLVError_Unavailable>
+    Ok(())
+}
+
+/// Counts an unsuccessful attempt towards the backoff and the maximum number of attempts.
+async fn record_failed_attempt(storage: &pointer::Storage) {
+    let mut storage = storage.lock().await;
+    let result = match PairSetupAttempts::load(storage.as_ref()).await {
+        Ok(mut attempts) => {
+            attempts.record_failure(SystemTime::now());
+            storage.save_pair_setup_attempts(&attempts).await
+        },
+        Err(err) => Err(err),
+    };
+
+    if let Err(err) = result {
+        error!("error saving the pair setup attempts: {:?}", err);
+    }
+}
+
+async fn handle_start(
+    handler: &mut PairSetup,
+    config: pointer::Config,
+    method: u8,
+    flags: u32,
+) -> Result<tlv::Container, tlv::Error> {
+    info!("pair setup M1: received SRP start request");
 
-    if handler.unsuccessful_tries > 100 {
-        return Err(tlv::Error::MaxTries);
+    let with_auth = method == Method::PairSetupWithAuth as u8;
+    if with_auth && handler.authenticator.lock().await.is_none() {
+        warn!("pair setup M2: refusing pair setup with authentication as no authenticator is set");
+        return Err(tlv::Error::Unknown);
     }
 
-    // TODO
-    // If the accessory is currently performing a PairSetup procedure with a different controller, it must respond with
-    // the following TLV items:
-    // kTLVType_State <M2>
-    // kTLVType_Error <kTLVError_Busy>
+    // unsupported flags are ignored
+    let mut flags = flags & (PairingFlag::Transient as u32 | PairingFlag::Split as u32);
+    let transient = flags & PairingFlag::Transient as u32 != 0;
+    let split = flags & PairingFlag::Split as u32 != 0;
 
     let mut csprng = OsRng {};
-    let mut salt = [0; 16]; // s
     let mut b = [0; 64];
-    csprng.fill_bytes(&mut salt);
     csprng.fill_bytes(&mut b);
 
-    // TODO - respect pairing flags (specification p. 35 - 7.) for split pair setup
+    // a split pair setup reuses the setup code verifier of the transient pair setup preceding it
+    let split_setup = match split && !transient {
+        true => handler.pairing_coordinator.lock().await.take_split_setup(),
+        false => None,
+    };
+    let (salt, verifier) = match split_setup {
+        Some(split_setup) => {
+            debug!("pair setup M1: resuming split pair setup");
+            (split_setup.salt, split_setup.verifier)
+        },
+        None => {
+            if split && !transient {
+                info!("pair setup M1: no transient pair setup to split from; using the setup code");
+                flags &= !(PairingFlag::Split as u32);
+            }
+
+            let mut salt = [0; 16]; // s
+            csprng.fill_bytes(&mut salt);
 
-    let private_key = srp_private_key::<Sha512>(b"Pair-Setup", &config.lock().await.pin.to_string().as_bytes(), &salt); // x = H(s | H(I | ":" | P))
-    let srp_client = SrpClient::<Sha512>::new(&private_key, &G_3072);
-    let verifier = srp_client.get_password_verifier(&private_key); // v = g^x
+            let private_key = srp_private_key::<Sha512>(b"Pair-Setup", &config.lock().await.pin.to_string().as_bytes(), &salt); // x = H(s | H(I | ":" | P))
+            let srp_client = SrpClient::<Sha512>::new(&private_key, &G_3072);
+            let verifier = srp_client.get_password_verifier(&private_key); // v = g^x
+
+            (salt, verifier)
+        },
+    };
 
     let user = UserRecord {
         username: b"Pair-Setup",
@@ -200,20 +303,31 @@ async fn handle_start(handler: &mut PairSetup, config: pointer::Config) -> Resul
         b,
         b_pub: b_pub.clone(),
         shared_secret: None,
+        flags,
+        with_auth,
     });
 
     info!("pair setup M2: sending SRP start response");
 
-    Ok(vec![
+    let mut res = vec![
         Value::State(StepNumber::SrpStartResponse as u8),
         Value::PublicKey(b_pub),
         Value::Salt(salt),
-    ])
+    ];
+    if flags != 0 {
+        res.push(Value::Flags(flags));
+    }
+
+    Ok(res)
 }
 
 async fn handle_verify(handler: &mut PairSetup, a_pub: &[u8], a_proof: &[u8]) -> Result<tlv::Container, tlv::Error> {
     info!("pair setup M3: received SRP verify request");
 
+    if !handler.pairing_coordinator.lock().await.is_pairing(handler.connection_id) {
+        return Err(tlv::Error::Unknown);
+    }
+
     match handler.session {
         None => Err(tlv::Error::Unknown),
         Some(ref mut session) => {
@@ -230,16 +344,71 @@ async fn handle_verify(handler: &mut PairSetup, a_pub: &[u8], a_proof: &[u8]) ->
             let b_proof =
                 verify_client_proof::<Sha512>(&session.b_pub, a_pub, a_proof, &session.salt, &shared_secret, &G_3072)?;
 
-            info!("pair setup M4: sending SRP verify response");
-
-            Ok(vec![
+            let mut res = vec![
                 Value::State(StepNumber::SrpVerifyResponse as u8),
                 Value::Proof(b_proof),
-            ])
+            ];
+            if session.with_auth {
+                res.push(Value::EncryptedData(authenticate(&handler.authenticator, &shared_secret).await?));
+            }
+
+            // a transient pair setup ends after M4 with a session derived from the SRP shared secret, without adding
+            // a pairing
+            if session.has_flag(PairingFlag::Transient) {
+                if session.has_flag(PairingFlag::Split) {
+                    handler.pairing_coordinator.lock().await.save_split_setup(SplitSetup {
+                        salt: session.salt,
+                        verifier: session.verifier.clone(),
+                    });
+                }
+
+                let sender = handler.session_sender.lock().await.take().ok_or(tlv::Error::Unknown)?;
+                let _session = sender.send(tcp::Session {
+                    controller_id: None,
+                    shared_secret: shared_secret.to_vec(),
+                });
+
+                handler.session = None;
+                handler.pairing_coordinator.lock().await.end_pair_setup(handler.connection_id);
+
+                info!("pair setup M4: sending SRP verify response, finishing transient pair setup");
+            } else {
+                info!("pair setup M4: sending SRP verify response");
+            }
+
+            Ok(res)
         },
     }
 }
 
+/// Proves the authenticity of the accessory to the controller. The authenticator signs a challenge derived from the
+/// SRP shared secret, and the signature is sent along with its certificate or token, encrypted with the session key.
+async fn authenticate(authenticator: &pointer::Authenticator, shared_secret: &[u8]) -> Result<Vec<u8>, tlv::Error> {
+    let mut authenticator = authenticator.lock().await;
+    let authenticator = authenticator.as_mut().ok_or(tlv::Error::Unknown)?;
+
+    let challenge = hkdf_extract_and_expand(b"MFi-Pair-Setup-Salt", shared_secret, b"MFi-Pair-Setup-Info")?;
+    let signature = authenticator.sign(&challenge).await?;
+    let certificate = authenticator.certificate().await?;
+
+    let encoded_sub_tlv = AuthenticationSubTlv { signature, certificate }.encode();
+
+    let encryption_key =
+        hkdf_extract_and_expand(b"Pair-Setup-Encrypt-Salt", shared_secret, b"Pair-Setup-Encrypt-Info")?;
+
+    let mut nonce = vec![0; 4];
+    nonce.extend(b"PS-Msg04");
+
+    let aead = ChaCha20Poly1305::new(GenericArray::from_slice(&encryption_key));
+
+    let mut encrypted_data = Vec::new();
+    encrypted_data.extend_from_slice(&encoded_sub_tlv);
+    let auth_tag = aead.encrypt_in_place_detached(GenericArray::from_slice(&nonce), &[], &mut encrypted_data)?;
+    encrypted_data.extend(&auth_tag);
+
+    Ok(encrypted_data)
+}
+
 async fn handle_exchange(
     handler: &mut PairSetup,
     config: pointer::Config,
@@ -249,6 +418,14 @@ async fn handle_exchange(
 ) -> Result<tlv::Container, tlv::Error> {
     info!("pair setup M5: received exchange request");
 
+    if !handler.pairing_coordinator.lock().await.is_pairing(handler.connection_id) {
+        return Err(tlv::Error::Unknown);
+    }
+
+    if data.len() < 16 {
+        return Err(tlv::Error::Authentication);
+    }
+
     match handler.session {
         None => Err(tlv::Error::Unknown),
         Some(ref mut session) => match session.shared_secret {
@@ -274,14 +451,10 @@ async fn handle_exchange(
                     GenericArray::from_slice(&auth_tag),
                 )?;
 
-                let sub_tlv = tlv::decode(&decrypted_data);
-                let device_pairing_id = sub_tlv.get(&(Type::Identifier as u8)).ok_or(tlv::Error::Unknown)?;
-                let device_ltpk = ed25519_dalek::PublicKey::from_bytes(
-                    sub_tlv.get(&(Type::PublicKey as u8)).ok_or(tlv::Error::Unknown)?,
-                )?;
-                let device_signature = ed25519_dalek::Signature::from_bytes(
-                    sub_tlv.get(&(Type::Signature as u8)).ok_or(tlv::Error::Unknown)?,
-                )?;
+                let sub_tlv = ExchangeSubTlv::decode(decrypted_data.into())?;
+                let device_pairing_id = &sub_tlv.identifier;
+                let device_ltpk = ed25519_dalek::PublicKey::from_bytes(&sub_tlv.ltpk)?;
+                let device_signature = ed25519_dalek::Signature::from_bytes(&sub_tlv.signature)?;
 
                 let device_x = hkdf_extract_and_expand(
                     b"Pair-Setup-Controller-Sign-Salt",
@@ -310,7 +483,10 @@ async fn handle_exchange(
                 }
 
                 let pairing = Pairing::new(pairing_uuid, Permissions::Admin, device_ltpk.to_bytes());
-                storage.lock().await.save_pairing(&pairing).await?;
+                let mut s = storage.lock().await;
+                s.save_pairing(&pairing).await?;
+                s.save_pair_setup_attempts(&PairSetupAttempts::default()).await?;
+                drop(s);
 
                 debug!("pairing: {:?}", &pairing);
 
@@ -329,11 +505,11 @@ async fn handle_exchange(
                 accessory_info.extend(config.device_ed25519_keypair.public.as_bytes());
                 let accessory_signature = config.device_ed25519_keypair.sign(&accessory_info);
 
-                let encoded_sub_tlv = vec![
-                    Value::Identifier(device_id),
-                    Value::PublicKey(config.device_ed25519_keypair.public.as_bytes().to_vec()),
-                    Value::Signature(accessory_signature.to_bytes().to_vec()),
-                ]
+                let encoded_sub_tlv = ExchangeSubTlv {
+                    identifier: device_id.into_bytes(),
+                    ltpk: config.device_ed25519_keypair.public.as_bytes().to_vec(),
+                    signature: accessory_signature.to_bytes().to_vec(),
+                }
                 .encode();
 
                 drop(config);
@@ -347,11 +523,7 @@ async fn handle_exchange(
                     aead.encrypt_in_place_detached(GenericArray::from_slice(&nonce), &[], &mut encrypted_data)?;
                 encrypted_data.extend(&auth_tag);
 
-                event_emitter
-                    .lock()
-                    .await
-                    .emit(&Event::ControllerPaired { id: pairing.id })
-                    .await;
+                event_emitter.emit(Event::ControllerPaired { id: pairing.id });
 
                 info!("pair setup M6: sending exchange response");
 
@@ -411,6 +583,54 @@ fn verify_client_proof<D: Digest>(
 mod tests {
     use super::*;
 
+    use futures::lock::Mutex;
+    use std::{fs, sync::Arc};
+
+    use crate::authenticator::FileAuthenticator;
+
+    #[tokio::test]
+    async fn test_authenticate() {
+        let mut temp_dir = std::env::temp_dir();
+        temp_dir.push("hap-pair-setup-authenticate");
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        let mut csprng = OsRng {};
+        let keypair = ed25519_dalek::Keypair::generate(&mut csprng);
+        fs::write(temp_dir.join("token"), b"token").unwrap();
+        fs::write(temp_dir.join("keypair"), keypair.to_bytes()).unwrap();
+
+        let authenticator: pointer::Authenticator = Arc::new(Mutex::new(Some(Box::new(FileAuthenticator::new(
+            temp_dir.join("token"),
+            temp_dir.join("keypair"),
+        )))));
+        let shared_secret = [1; 64];
+
+        let mut encrypted_data = authenticate(&authenticator, &shared_secret).await.unwrap();
+
+        // the controller decrypts the sub-TLV with the session key and verifies the signature of the challenge
+        let encryption_key =
+            hkdf_extract_and_expand(b"Pair-Setup-Encrypt-Salt", &shared_secret, b"Pair-Setup-Encrypt-Info").unwrap();
+        let mut nonce = vec![0; 4];
+        nonce.extend(b"PS-Msg04");
+        let auth_tag = encrypted_data.split_off(encrypted_data.len() - 16);
+        ChaCha20Poly1305::new(GenericArray::from_slice(&encryption_key))
+            .decrypt_in_place_detached(
+                GenericArray::from_slice(&nonce),
+                &[],
+                &mut encrypted_data,
+                GenericArray::from_slice(&auth_tag),
+            )
+            .unwrap();
+
+        let sub_tlv = AuthenticationSubTlv::decode(encrypted_data.into()).unwrap();
+        assert_eq!(sub_tlv.certificate, b"token".to_vec());
+
+        let challenge =
+            hkdf_extract_and_expand(b"MFi-Pair-Setup-Salt", &shared_secret, b"MFi-Pair-Setup-Info").unwrap();
+        let signature = ed25519_dalek::Signature::from_bytes(&sub_tlv.signature).unwrap();
+        assert!(keypair.public.verify(&challenge, &signature).is_ok());
+    }
+
     #[test]
     fn test_verify_client_proof() {
         let b_pub = [
@@ -1,21 +1,25 @@
 use aead::{generic_array::GenericArray, AeadInPlace, NewAead};
+use bytes::Bytes;
 use chacha20poly1305::ChaCha20Poly1305;
-use futures::{
-    channel::oneshot,
-    future::{BoxFuture, FutureExt},
-};
-use hyper::{body::Buf, Body};
+use futures::future::{BoxFuture, FutureExt};
+use hyper::Body;
 use log::{debug, info};
-use rand::rngs::OsRng;
+use rand::{rngs::OsRng, Rng};
 use signature::{Signer, Verifier};
 use std::str;
 use uuid::Uuid;
 use x25519_dalek::{EphemeralSecret, PublicKey};
 
 use crate::{
+    characteristic::tlv8::{Tlv8, Tlv8Value},
     pointer,
-    tlv::{self, Encodable, Type, Value},
-    transport::{hkdf_extract_and_expand, http::handler::TlvHandlerExt, tcp},
+    tlv::{self, Encodable, Method, Type, Value},
+    transport::{
+        hkdf_extract_and_expand,
+        http::handler::TlvHandlerExt,
+        session_cache::SESSION_ID_LEN,
+        tcp,
+    },
 };
 
 struct Session {
@@ -27,18 +31,29 @@ struct Session {
 
 pub struct PairVerify {
     session: Option<Session>,
-    session_sender: Option<oneshot::Sender<tcp::Session>>,
+    session_sender: pointer::SessionSender,
+    session_cache: pointer::SessionCache,
 }
 
 impl PairVerify {
-    pub fn new(session_sender: oneshot::Sender<tcp::Session>) -> PairVerify {
+    pub fn new(session_sender: pointer::SessionSender, session_cache: pointer::SessionCache) -> PairVerify {
         PairVerify {
             session: None,
-            session_sender: Some(session_sender),
+            session_sender,
+            session_cache,
         }
     }
 }
 
+/// Encrypted sub-TLV exchanged in the M2 and M3 steps.
+#[derive(Debug, Tlv8)]
+struct SubTlv {
+    #[tlv(type = Type::Identifier)]
+    identifier: Vec<u8>,
+    #[tlv(type = Type::Signature)]
+    signature: Vec<u8>,
+}
+
 #[derive(Debug, Clone)]
 enum StepNumber {
     Unknown = 0,
@@ -50,8 +65,9 @@ enum StepNumber {
 
 #[derive(Debug, Clone)]
 pub enum Step {
-    Start { a_pub: Vec<u8> },
-    Finish { data: Vec<u8> },
+    Start { a_pub: Bytes },
+    Resume { a_pub: Bytes, session_id: Bytes, data: Bytes },
+    Finish { data: Bytes },
 }
 
 impl TlvHandlerExt for PairVerify {
@@ -60,36 +76,42 @@ impl TlvHandlerExt for PairVerify {
 
     fn parse(&self, body: Body) -> BoxFuture<Result<Step, tlv::ErrorContainer>> {
         async {
-            let aggregated_body = hyper::body::aggregate(body)
+            let body = hyper::body::to_bytes(body)
                 .await
                 .map_err(|_| tlv::ErrorContainer::new(StepNumber::Unknown as u8, tlv::Error::Unknown))?;
 
-            debug!("received body: {:?}", aggregated_body.chunk());
-
-            let mut decoded = tlv::decode(aggregated_body.chunk());
-            match decoded.get(&(Type::State as u8)) {
-                Some(method) => match method[0] {
-                    x if x == StepNumber::StartReq as u8 => {
-                        let a_pub = decoded
-                            .remove(&(Type::PublicKey as u8))
-                            .ok_or(tlv::ErrorContainer::new(
-                                StepNumber::StartRes as u8,
-                                tlv::Error::Unknown,
-                            ))?;
-                        Ok(Step::Start { a_pub })
-                    },
-                    x if x == StepNumber::FinishReq as u8 => {
-                        let data = decoded
-                            .remove(&(Type::EncryptedData as u8))
-                            .ok_or(tlv::ErrorContainer::new(
-                                StepNumber::FinishRes as u8,
-                                tlv::Error::Unknown,
-                            ))?;
-                        Ok(Step::Finish { data })
-                    },
-                    _ => Err(tlv::ErrorContainer::new(StepNumber::Unknown as u8, tlv::Error::Unknown)),
+            debug!("received body: {:?}", &body);
+
+            let decoded = tlv::decode(body)
+                .map_err(|_| tlv::ErrorContainer::new(StepNumber::Unknown as u8, tlv::Error::Unknown))?;
+            match decoded.get_u8(Type::State as u8) {
+                Some(x) if x == StepNumber::StartReq as u8 => {
+                    let a_pub = decoded
+                        .get(Type::PublicKey as u8)
+                        .cloned()
+                        .ok_or(tlv::ErrorContainer::new(StepNumber::StartRes as u8, tlv::Error::Unknown))?;
+                    match (
+                        decoded.get_u8(Type::Method as u8),
+                        decoded.get(Type::SessionId as u8),
+                        decoded.get(Type::EncryptedData as u8),
+                    ) {
+                        (Some(method), Some(session_id), Some(data)) if method == Method::PairResume as u8 =>
+                            Ok(Step::Resume {
+                                a_pub,
+                                session_id: session_id.clone(),
+                                data: data.clone(),
+                            }),
+                        _ => Ok(Step::Start { a_pub }),
+                    }
+                },
+                Some(x) if x == StepNumber::FinishReq as u8 => {
+                    let data = decoded
+                        .get(Type::EncryptedData as u8)
+                        .cloned()
+                        .ok_or(tlv::ErrorContainer::new(StepNumber::FinishRes as u8, tlv::Error::Unknown))?;
+                    Ok(Step::Finish { data })
                 },
-                None => Err(tlv::ErrorContainer::new(StepNumber::Unknown as u8, tlv::Error::Unknown)),
+                _ => Err(tlv::ErrorContainer::new(StepNumber::Unknown as u8, tlv::Error::Unknown)),
             }
         }
         .boxed()
@@ -109,6 +131,11 @@ impl TlvHandlerExt for PairVerify {
                     Ok(res) => Ok(res),
                     Err(err) => Err(tlv::ErrorContainer::new(StepNumber::StartRes as u8, err)),
                 },
+                Step::Resume { a_pub, session_id, data } =>
+                    match handle_resume(self, config, storage, a_pub, &session_id, &data).await {
+                        Ok(res) => Ok(res),
+                        Err(err) => Err(tlv::ErrorContainer::new(StepNumber::StartRes as u8, err)),
+                    },
                 Step::Finish { data } => match handle_finish(self, storage, &data).await {
                     Ok(res) => Ok(res),
                     Err(err) => Err(tlv::ErrorContainer::new(StepNumber::FinishRes as u8, err)),
@@ -122,13 +149,15 @@ impl TlvHandlerExt for PairVerify {
 async fn handle_start(
     handler: &mut PairVerify,
     config: pointer::Config,
-    a_pub_bytes: Vec<u8>,
+    a_pub_bytes: Bytes,
 ) -> Result<tlv::Container, tlv::Error> {
     info!("pair verify M1: received verify start request");
 
     let mut a_pub = [0; 32];
-    let bytes = &a_pub_bytes[..a_pub.len()]; // panics if not enough data
-    a_pub.copy_from_slice(bytes);
+    if a_pub_bytes.len() != a_pub.len() {
+        return Err(tlv::Error::Unknown);
+    }
+    a_pub.copy_from_slice(&a_pub_bytes);
     let a_pub = PublicKey::from(a_pub);
 
     let mut csprng = OsRng {};
@@ -147,10 +176,10 @@ async fn handle_start(
 
     drop(config);
 
-    let encoded_sub_tlv = vec![
-        Value::Identifier(device_id),
-        Value::Signature(accessory_signature.to_bytes().to_vec()),
-    ]
+    let encoded_sub_tlv = SubTlv {
+        identifier: device_id.into_bytes(),
+        signature: accessory_signature.to_bytes().to_vec(),
+    }
     .encode();
 
     let session_key = hkdf_extract_and_expand(
@@ -192,6 +221,10 @@ async fn handle_finish(
 ) -> Result<tlv::Container, tlv::Error> {
     info!("pair verify M3: received verify finish request");
 
+    if data.len() < 16 {
+        return Err(tlv::Error::Authentication);
+    }
+
     match handler.session {
         None => Err(tlv::Error::Unknown),
         Some(ref mut session) => {
@@ -212,13 +245,11 @@ async fn handle_finish(
                 GenericArray::from_slice(&auth_tag),
             )?;
 
-            let sub_tlv = tlv::decode(&decrypted_data);
+            let sub_tlv = SubTlv::decode(decrypted_data.into())?;
             debug!("received sub-TLV: {:?}", &sub_tlv);
-            let device_pairing_id = sub_tlv.get(&(Type::Identifier as u8)).ok_or(tlv::Error::Unknown)?;
+            let device_pairing_id = &sub_tlv.identifier;
             debug!("raw device pairing ID: {:?}", &device_pairing_id);
-            let device_signature = ed25519_dalek::Signature::from_bytes(
-                sub_tlv.get(&(Type::Signature as u8)).ok_or(tlv::Error::Unknown)?,
-            )?;
+            let device_signature = ed25519_dalek::Signature::from_bytes(&sub_tlv.signature)?;
             debug!("device signature: {:?}", &device_signature);
 
             let uuid_str = str::from_utf8(device_pairing_id)?;
@@ -239,15 +270,9 @@ async fn handle_finish(
                 return Err(tlv::Error::Authentication);
             }
 
-            if let Some(sender) = handler.session_sender.take() {
-                let encrypted_session = tcp::Session {
-                    controller_id: pairing_uuid,
-                    shared_secret: session.shared_secret,
-                };
-                let _session = sender.send(encrypted_session);
-            } else {
-                return Err(tlv::Error::Unknown);
-            }
+            let shared_secret = session.shared_secret;
+            let session_id = resume_session_id(&shared_secret)?;
+            establish_session(handler, pairing_uuid, session_id, shared_secret).await?;
 
             info!("pair verify M4: sending verify finish response");
 
@@ -255,3 +280,230 @@ async fn handle_finish(
         },
     }
 }
+
+async fn handle_resume(
+    handler: &mut PairVerify,
+    config: pointer::Config,
+    storage: pointer::Storage,
+    a_pub: Bytes,
+    session_id: &[u8],
+    auth_tag: &[u8],
+) -> Result<tlv::Container, tlv::Error> {
+    info!("pair resume M1: received resume request");
+
+    // the session is only removed from the cache once the request is authenticated, so forged requests can't burn it
+    let cached_session = handler.session_cache.lock().await.get(session_id);
+    let (controller_id, shared_secret) = match cached_session {
+        // the controller might have been unpaired since the session was established
+        Some((controller_id, shared_secret))
+            if storage.lock().await.load_pairing(&controller_id).await.is_ok()
+                && verify_resume_request(&a_pub, session_id, &shared_secret, auth_tag) =>
+            (controller_id, shared_secret),
+        _ => {
+            info!("pair resume: session can't be resumed; falling back to pair verify");
+            return handle_start(handler, config, a_pub).await;
+        },
+    };
+    // another connection might have resumed the session in the meantime
+    if handler.session_cache.lock().await.take(session_id).is_none() {
+        info!("pair resume: session was resumed already; falling back to pair verify");
+        return handle_start(handler, config, a_pub).await;
+    }
+
+    let mut csprng = OsRng {};
+    let new_session_id = csprng.gen::<[u8; SESSION_ID_LEN]>();
+
+    let mut salt = a_pub.to_vec();
+    salt.extend(&new_session_id);
+    let response_key = hkdf_extract_and_expand(&salt, &shared_secret, b"Pair-Resume-Response-Info")?;
+    let new_shared_secret = hkdf_extract_and_expand(&salt, &shared_secret, b"Pair-Resume-Shared-Secret-Info")?;
+
+    let mut nonce = vec![0; 4];
+    nonce.extend(b"PR-Msg02");
+
+    let aead = ChaCha20Poly1305::new(GenericArray::from_slice(&response_key));
+    let auth_tag = aead.encrypt_in_place_detached(GenericArray::from_slice(&nonce), &[], &mut [])?;
+
+    establish_session(handler, controller_id, new_session_id, new_shared_secret).await?;
+
+    info!("pair resume M2: sending resume response");
+
+    Ok(vec![
+        Value::State(StepNumber::StartRes as u8),
+        Value::Method(Method::PairResume),
+        Value::SessionId(new_session_id.to_vec()),
+        Value::EncryptedData(auth_tag.to_vec()),
+    ])
+}
+
+/// Checks the auth tag of a resume request, which the controller derives from the shared secret of the session to
+/// resume.
+fn verify_resume_request(a_pub: &[u8], session_id: &[u8], shared_secret: &[u8; 32], auth_tag: &[u8]) -> bool {
+    if auth_tag.len() != 16 {
+        return false;
+    }
+
+    let mut salt = a_pub.to_vec();
+    salt.extend(session_id);
+    let request_key = match hkdf_extract_and_expand(&salt, shared_secret, b"Pair-Resume-Request-Info") {
+        Ok(request_key) => request_key,
+        Err(_) => return false,
+    };
+
+    let mut nonce = vec![0; 4];
+    nonce.extend(b"PR-Msg01");
+
+    let aead = ChaCha20Poly1305::new(GenericArray::from_slice(&request_key));
+    aead.decrypt_in_place_detached(GenericArray::from_slice(&nonce), &[], &mut [], GenericArray::from_slice(auth_tag))
+        .is_ok()
+}
+
+/// Derives the ID under which a session established by a full pair verify can be resumed.
+fn resume_session_id(shared_secret: &[u8; 32]) -> Result<[u8; SESSION_ID_LEN], tlv::Error> {
+    let okm = hkdf_extract_and_expand(
+        b"Pair-Verify-ResumeSessionID-Salt",
+        shared_secret,
+        b"Pair-Verify-ResumeSessionID-Info",
+    )?;
+
+    let mut session_id = [0; SESSION_ID_LEN];
+    session_id.copy_from_slice(&okm[..SESSION_ID_LEN]);
+
+    Ok(session_id)
+}
+
+/// Hands the verified session over to the connection and caches it for later resumption.
+async fn establish_session(
+    handler: &mut PairVerify,
+    controller_id: Uuid,
+    session_id: [u8; SESSION_ID_LEN],
+    shared_secret: [u8; 32],
+) -> Result<(), tlv::Error> {
+    let sender = handler.session_sender.lock().await.take().ok_or(tlv::Error::Unknown)?;
+    let _session = sender.send(tcp::Session {
+        controller_id: Some(controller_id),
+        shared_secret: shared_secret.to_vec(),
+    });
+
+    handler.session_cache.lock().await.insert(session_id, controller_id, shared_secret);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::{channel::oneshot, lock::Mutex};
+    use std::{sync::Arc, time::Duration};
+
+    use crate::{
+        pairing::{Pairing, Permissions},
+        storage::FileStorage,
+        transport::session_cache::SessionCache,
+        Config,
+    };
+
+    #[tokio::test]
+    async fn test_pair_resume() {
+        let mut temp_dir = std::env::temp_dir();
+        temp_dir.push("hap-pair-resume");
+
+        let storage: pointer::Storage = Arc::new(Mutex::new(Box::new(FileStorage::new(&temp_dir).await.unwrap())));
+        let config: pointer::Config = Arc::new(Mutex::new(Config::default()));
+        let session_cache: pointer::SessionCache = Arc::new(Mutex::new(SessionCache::new(8, Duration::from_secs(60))));
+
+        let pairing = Pairing::new(
+            Uuid::parse_str("bc158b86-cabf-432d-aee4-422ef0e3f1d5").unwrap(),
+            Permissions::Admin,
+            [0; 32],
+        );
+        storage.lock().await.save_pairing(&pairing).await.unwrap();
+
+        let session_id = [1; SESSION_ID_LEN];
+        let shared_secret = [2; 32];
+        session_cache.lock().await.insert(session_id, pairing.id, shared_secret);
+
+        // the controller authenticates the resume request with a key derived from the previous shared secret
+        let a_pub = [3; 32];
+        let mut salt = a_pub.to_vec();
+        salt.extend(&session_id);
+        let request_key = hkdf_extract_and_expand(&salt, &shared_secret, b"Pair-Resume-Request-Info").unwrap();
+        let mut nonce = vec![0; 4];
+        nonce.extend(b"PR-Msg01");
+        let auth_tag = ChaCha20Poly1305::new(GenericArray::from_slice(&request_key))
+            .encrypt_in_place_detached(GenericArray::from_slice(&nonce), &[], &mut [])
+            .unwrap();
+
+        // a forged or corrupted request falls back to a full pair verify and leaves the session resumable
+        for forged_auth_tag in [vec![0; 16], auth_tag[..8].to_vec()] {
+            let (session_sender, _session_receiver) = oneshot::channel();
+            let mut handler = PairVerify::new(Arc::new(Mutex::new(Some(session_sender))), session_cache.clone());
+            let res = handle_resume(
+                &mut handler,
+                config.clone(),
+                storage.clone(),
+                Bytes::copy_from_slice(&a_pub),
+                &session_id,
+                &forged_auth_tag,
+            )
+            .await
+            .unwrap();
+            assert!(res.iter().any(|v| matches!(v, Value::PublicKey(_))));
+            assert!(session_cache.lock().await.get(&session_id).is_some());
+        }
+
+        let (session_sender, mut session_receiver) = oneshot::channel();
+        let mut handler = PairVerify::new(Arc::new(Mutex::new(Some(session_sender))), session_cache.clone());
+        let res = handle_resume(
+            &mut handler,
+            config.clone(),
+            storage.clone(),
+            Bytes::copy_from_slice(&a_pub),
+            &session_id,
+            &auth_tag,
+        )
+        .await
+        .unwrap();
+
+        let new_session_id = res
+            .iter()
+            .find_map(|v| match v {
+                Value::SessionId(session_id) => Some(session_id.clone()),
+                _ => None,
+            })
+            .unwrap();
+        let mut salt = a_pub.to_vec();
+        salt.extend(&new_session_id);
+        let new_shared_secret =
+            hkdf_extract_and_expand(&salt, &shared_secret, b"Pair-Resume-Shared-Secret-Info").unwrap();
+
+        let session = session_receiver.try_recv().unwrap().unwrap();
+        assert_eq!(session.controller_id, Some(pairing.id));
+        assert_eq!(session.shared_secret, new_shared_secret.to_vec());
+
+        // the resumed session was replaced by the new one
+        let mut cache = session_cache.lock().await;
+        assert_eq!(cache.take(&session_id), None);
+        assert_eq!(cache.take(&new_session_id), Some((pairing.id, new_shared_secret)));
+        drop(cache);
+
+        // an unknown session falls back to a full pair verify
+        let (session_sender, _session_receiver) = oneshot::channel();
+        let mut handler = PairVerify::new(Arc::new(Mutex::new(Some(session_sender))), session_cache.clone());
+        let res = handle_resume(
+            &mut handler,
+            config,
+            storage.clone(),
+            Bytes::copy_from_slice(&a_pub),
+            &session_id,
+            &auth_tag,
+        )
+        .await
+        .unwrap();
+        assert!(res.iter().any(|v| matches!(v, Value::PublicKey(_))));
+        assert!(handler.session.is_some());
+
+        storage.lock().await.delete_pairing(&pairing.id).await.unwrap();
+    }
+}
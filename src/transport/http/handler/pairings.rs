@@ -1,21 +1,25 @@
+use bytes::Bytes;
 use futures::future::{BoxFuture, FutureExt};
-use hyper::{body::Buf, Body};
+use hyper::Body;
 use log::{debug, info};
 use std::{ops::Deref, str};
 use uuid::Uuid;
 
 use crate::{
+    characteristic::tlv8::Tlv8,
     event::Event,
     pairing::{Pairing, Permissions},
     pointer,
-    tlv::{self, Type, Value},
+    tlv::{self, Type},
     transport::http::handler::TlvHandlerExt,
 };
 
-pub struct Pairings;
+pub struct Pairings {
+    connections: pointer::ConnectionRegistry,
+}
 
 impl Pairings {
-    pub fn new() -> Pairings { Pairings }
+    pub fn new(connections: pointer::ConnectionRegistry) -> Pairings { Pairings { connections } }
 }
 
 #[derive(Debug, Clone)]
@@ -33,62 +37,93 @@ enum HandlerNumber {
 
 pub enum HandlerType {
     Add {
-        pairing_id: Vec<u8>,
-        ltpk: Vec<u8>,
+        pairing_id: Bytes,
+        ltpk: Bytes,
         permissions: Permissions,
     },
     Remove {
-        pairing_id: Vec<u8>,
+        pairing_id: Bytes,
     },
     List,
 }
 
+/// Response of the pairings endpoint. Only the list pairings response carries pairings, separated by `Separator`
+/// items.
+#[derive(Debug, Tlv8)]
+pub struct PairingsResponse {
+    #[tlv(type = Type::State)]
+    state: u8,
+    #[tlv(type = Type::Identifier, entries, separator = Type::Separator)]
+    pairings: Vec<PairingEntry>,
+}
+
+impl PairingsResponse {
+    fn new(pairings: Vec<PairingEntry>) -> PairingsResponse {
+        PairingsResponse {
+            state: StepNumber::Res as u8,
+            pairings,
+        }
+    }
+}
+
+/// A single pairing of a list pairings response.
+#[derive(Debug, PartialEq, Eq, Tlv8)]
+struct PairingEntry {
+    #[tlv(type = Type::Identifier)]
+    identifier: String,
+    #[tlv(type = Type::PublicKey)]
+    public_key: Vec<u8>,
+    #[tlv(type = Type::Permissions)]
+    permissions: u8,
+}
+
 impl TlvHandlerExt for Pairings {
     type ParseResult = HandlerType;
-    type Result = tlv::Container;
+    type Result = PairingsResponse;
 
     fn parse(&self, body: Body) -> BoxFuture<Result<HandlerType, tlv::ErrorContainer>> {
         async {
-            let aggregated_body = hyper::body::aggregate(body)
+            let body = hyper::body::to_bytes(body)
                 .await
                 .map_err(|_| tlv::ErrorContainer::new(StepNumber::Unknown as u8, tlv::Error::Unknown))?;
 
-            debug!("received body: {:?}", aggregated_body.chunk());
+            debug!("received body: {:?}", &body);
 
-            let mut decoded = tlv::decode(aggregated_body.chunk());
-            if decoded.get(&(Type::State as u8)) != Some(&vec![1]) {
+            let decoded = tlv::decode(body)
+                .map_err(|_| tlv::ErrorContainer::new(StepNumber::Unknown as u8, tlv::Error::Unknown))?;
+            if decoded.get_u8(Type::State as u8) != Some(1) {
                 return Err(tlv::ErrorContainer::new(0, tlv::Error::Unknown));
             }
-            match decoded.get(&(Type::Method as u8)) {
-                Some(handler) => match handler[0] {
-                    x if x == HandlerNumber::Add as u8 => {
-                        let pairing_id = decoded
-                            .remove(&(Type::Identifier as u8))
-                            .ok_or(tlv::ErrorContainer::new(StepNumber::Res as u8, tlv::Error::Unknown))?;
-                        let ltpk = decoded
-                            .remove(&(Type::PublicKey as u8))
-                            .ok_or(tlv::ErrorContainer::new(StepNumber::Res as u8, tlv::Error::Unknown))?;
-                        let perms = decoded
-                            .remove(&(Type::Permissions as u8))
-                            .ok_or(tlv::ErrorContainer::new(StepNumber::Res as u8, tlv::Error::Unknown))?;
-                        let permissions = Permissions::from_byte(perms[0])
-                            .map_err(|_| tlv::ErrorContainer::new(StepNumber::Res as u8, tlv::Error::Unknown))?;
-                        Ok(HandlerType::Add {
-                            pairing_id,
-                            ltpk,
-                            permissions,
-                        })
-                    },
-                    x if x == HandlerNumber::Remove as u8 => {
-                        let pairing_id = decoded
-                            .remove(&(Type::Identifier as u8))
-                            .ok_or(tlv::ErrorContainer::new(StepNumber::Res as u8, tlv::Error::Unknown))?;
-                        Ok(HandlerType::Remove { pairing_id })
-                    },
-                    x if x == HandlerNumber::List as u8 => Ok(HandlerType::List),
-                    _ => Err(tlv::ErrorContainer::new(StepNumber::Unknown as u8, tlv::Error::Unknown)),
+            match decoded.get_u8(Type::Method as u8) {
+                Some(x) if x == HandlerNumber::Add as u8 => {
+                    let pairing_id = decoded
+                        .get(Type::Identifier as u8)
+                        .cloned()
+                        .ok_or(tlv::ErrorContainer::new(StepNumber::Res as u8, tlv::Error::Unknown))?;
+                    let ltpk = decoded
+                        .get(Type::PublicKey as u8)
+                        .cloned()
+                        .ok_or(tlv::ErrorContainer::new(StepNumber::Res as u8, tlv::Error::Unknown))?;
+                    let perms = decoded
+                        .get_u8(Type::Permissions as u8)
+                        .ok_or(tlv::ErrorContainer::new(StepNumber::Res as u8, tlv::Error::Unknown))?;
+                    let permissions = Permissions::from_byte(perms)
+                        .map_err(|_| tlv::ErrorContainer::new(StepNumber::Res as u8, tlv::Error::Unknown))?;
+                    Ok(HandlerType::Add {
+                        pairing_id,
+                        ltpk,
+                        permissions,
+                    })
                 },
-                None => Err(tlv::ErrorContainer::new(StepNumber::Unknown as u8, tlv::Error::Unknown)),
+                Some(x) if x == HandlerNumber::Remove as u8 => {
+                    let pairing_id = decoded
+                        .get(Type::Identifier as u8)
+                        .cloned()
+                        .ok_or(tlv::ErrorContainer::new(StepNumber::Res as u8, tlv::Error::Unknown))?;
+                    Ok(HandlerType::Remove { pairing_id })
+                },
+                Some(x) if x == HandlerNumber::List as u8 => Ok(HandlerType::List),
+                _ => Err(tlv::ErrorContainer::new(StepNumber::Unknown as u8, tlv::Error::Unknown)),
             }
         }
         .boxed()
@@ -101,7 +136,7 @@ impl TlvHandlerExt for Pairings {
         config: pointer::Config,
         storage: pointer::Storage,
         event_emitter: pointer::EventEmitter,
-    ) -> BoxFuture<Result<tlv::Container, tlv::ErrorContainer>> {
+    ) -> BoxFuture<Result<PairingsResponse, tlv::ErrorContainer>> {
         async move {
             match handler {
                 HandlerType::Add {
@@ -123,7 +158,7 @@ impl TlvHandlerExt for Pairings {
                     Err(err) => Err(tlv::ErrorContainer::new(StepNumber::Res as u8, err)),
                 },
                 HandlerType::Remove { pairing_id } => {
-                    match handle_remove(controller_id, storage, event_emitter, pairing_id).await {
+                    match handle_remove(controller_id, storage, event_emitter, &self.connections, pairing_id).await {
                         Ok(res) => Ok(res),
                         Err(err) => Err(tlv::ErrorContainer::new(StepNumber::Res as u8, err)),
                     }
@@ -143,10 +178,10 @@ async fn handle_add(
     config: pointer::Config,
     storage: pointer::Storage,
     event_emitter: pointer::EventEmitter,
-    pairing_id: Vec<u8>,
-    ltpk: Vec<u8>,
+    pairing_id: Bytes,
+    ltpk: Bytes,
     permissions: Permissions,
-) -> Result<tlv::Container, tlv::Error> {
+) -> Result<PairingsResponse, tlv::Error> {
     info!("pairings M1: received add pairing request");
 
     check_admin(&controller_id, &storage).await?;
@@ -167,11 +202,7 @@ async fn handle_add(
 
             drop(s);
 
-            event_emitter
-                .lock()
-                .await
-                .emit(&Event::ControllerPaired { id: pairing.id })
-                .await;
+            event_emitter.emit(Event::ControllerPaired { id: pairing.id });
         },
         Err(_) => {
             if let Some(max_peers) = config.lock().await.max_peers {
@@ -191,66 +222,93 @@ async fn handle_add(
 
             drop(s);
 
-            event_emitter
-                .lock()
-                .await
-                .emit(&Event::ControllerPaired { id: pairing.id })
-                .await;
+            event_emitter.emit(Event::ControllerPaired { id: pairing.id });
         },
     }
 
     info!("pairings M2: sending add pairing response");
 
-    Ok(vec![Value::State(StepNumber::Res as u8)])
+    Ok(PairingsResponse::new(Vec::new()))
 }
 
 async fn handle_remove(
     controller_id: pointer::ControllerId,
     storage: pointer::Storage,
     event_emitter: pointer::EventEmitter,
-    pairing_id: Vec<u8>,
-) -> Result<tlv::Container, tlv::Error> {
+    connections: &pointer::ConnectionRegistry,
+    pairing_id: Bytes,
+) -> Result<PairingsResponse, tlv::Error> {
     info!("pairings M1: received remove pairing request");
 
     check_admin(&controller_id, &storage).await?;
 
     let uuid_str = str::from_utf8(&pairing_id)?;
     let pairing_uuid = Uuid::parse_str(uuid_str)?;
-    storage.lock().await.delete_pairing(&pairing_uuid).await?;
-
-    event_emitter
-        .lock()
-        .await
-        .emit(&Event::ControllerUnpaired { id: pairing_uuid })
-        .await;
+    remove_pairing(&pairing_uuid, &storage, &event_emitter, connections, Some(&controller_id)).await?;
 
     info!("pairings M2: sending remove pairing response");
 
-    Ok(vec![Value::State(StepNumber::Res as u8)])
+    Ok(PairingsResponse::new(Vec::new()))
+}
+
+/// Removes the pairing of a controller and tears down its connections. If the removed pairing was the last admin
+/// pairing, all remaining pairings are removed as well, as no controller could manage them anymore.
+///
+/// The `current` connection is the one the removal was requested on. It's left open until the response is sent.
+pub async fn remove_pairing(
+    id: &Uuid,
+    storage: &pointer::Storage,
+    event_emitter: &pointer::EventEmitter,
+    connections: &pointer::ConnectionRegistry,
+    current: Option<&pointer::ControllerId>,
+) -> crate::Result<()> {
+    let mut s = storage.lock().await;
+    s.delete_pairing(id).await?;
+    let mut removed_ids = vec![*id];
+
+    let remaining_pairings = s.list_pairings().await?;
+    if !remaining_pairings.iter().any(|p| p.permissions == Permissions::Admin) {
+        for pairing in remaining_pairings {
+            info!("removing pairing {} after the last admin pairing was removed", pairing.id);
+            s.delete_pairing(&pairing.id).await?;
+            removed_ids.push(pairing.id);
+        }
+    }
+
+    drop(s);
+
+    for id in removed_ids {
+        connections.lock().await.close_controller(&id, current).await;
+        event_emitter.emit(Event::ControllerUnpaired { id });
+    }
+
+    Ok(())
 }
 
 async fn handle_list(
     controller_id: pointer::ControllerId,
     storage: pointer::Storage,
-) -> Result<tlv::Container, tlv::Error> {
+) -> Result<PairingsResponse, tlv::Error> {
     info!("pairings M1: received list pairings request");
 
     check_admin(&controller_id, &storage).await?;
 
-    let pairings = storage.lock().await.list_pairings().await?;
-    let mut list = vec![Value::State(StepNumber::Res as u8)];
-    for (i, pairing) in pairings.iter().enumerate() {
-        list.push(Value::Identifier(pairing.id.to_hyphenated().to_string()));
-        list.push(Value::PublicKey(pairing.public_key.to_vec()));
-        list.push(Value::Permissions(pairing.permissions.clone()));
-        if i < pairings.len() {
-            list.push(Value::Separator);
-        }
-    }
+    let pairings = storage
+        .lock()
+        .await
+        .list_pairings()
+        .await?
+        .into_iter()
+        .map(|pairing| PairingEntry {
+            identifier: pairing.id.to_hyphenated().to_string(),
+            public_key: pairing.public_key.to_vec(),
+            permissions: pairing.permissions.as_byte(),
+        })
+        .collect();
 
     info!("pairings M2: sending list pairings response");
 
-    Ok(list)
+    Ok(PairingsResponse::new(pairings))
 }
 
 async fn check_admin(controller_id: &pointer::ControllerId, storage: &pointer::Storage) -> Result<(), tlv::Error> {
@@ -267,3 +325,50 @@ async fn check_admin(controller_id: &pointer::ControllerId, storage: &pointer::S
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::characteristic::tlv8::Tlv8Value;
+
+    fn entry(id: u8, permissions: Permissions) -> PairingEntry {
+        PairingEntry {
+            identifier: Uuid::from_bytes([id; 16]).to_hyphenated().to_string(),
+            public_key: vec![id; 32],
+            permissions: permissions.as_byte(),
+        }
+    }
+
+    #[test]
+    fn test_list_pairings_response() {
+        let response = PairingsResponse::new(vec![
+            entry(1, Permissions::Admin),
+            entry(2, Permissions::User),
+            entry(3, Permissions::User),
+        ]);
+        let bytes = Tlv8Value::encode(&response);
+
+        let payload = tlv::decode(bytes.clone().into()).unwrap();
+        assert_eq!(payload.get_u8(Type::State as u8), Some(StepNumber::Res as u8));
+        // the pairings are separated by empty `Separator` items, without a trailing one
+        let separators = payload
+            .items()
+            .iter()
+            .filter(|item| item.tlv_type == Type::Separator as u8)
+            .collect::<Vec<_>>();
+        assert_eq!(separators.len(), 2);
+        assert!(separators.iter().all(|item| item.value.is_empty()));
+        assert_ne!(payload.items().last().unwrap().tlv_type, Type::Separator as u8);
+
+        let decoded = PairingsResponse::decode(bytes.into()).unwrap();
+        assert_eq!(decoded.state, StepNumber::Res as u8);
+        assert_eq!(decoded.pairings, response.pairings);
+    }
+
+    #[test]
+    fn test_empty_pairings_response() {
+        let bytes = Tlv8Value::encode(&PairingsResponse::new(Vec::new()));
+
+        assert_eq!(bytes, vec![Type::State as u8, 1, StepNumber::Res as u8]);
+    }
+}
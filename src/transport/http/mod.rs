@@ -5,6 +5,7 @@ use hyper::{
     StatusCode,
 };
 use serde::{Deserialize, Serialize};
+use std::time::Instant;
 
 use crate::{
     characteristic::{Format, Perm, Unit},
@@ -14,6 +15,7 @@ use crate::{
 };
 
 mod handler;
+mod notification_queue;
 
 pub(crate) mod server;
 
@@ -90,6 +92,27 @@ pub struct WriteObject {
     #[serde(rename = "authData")]
     pub auth_data: Option<String>,
     pub remote: Option<bool>,
+    #[serde(rename = "r")]
+    pub response: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WriteRequestBody {
+    pub characteristics: Vec<WriteObject>,
+    pub pid: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PrepareObject {
+    pub ttl: u64,
+    pub pid: u64,
+}
+
+/// A timed write transaction announced by a controller via `PUT /prepare`.
+#[derive(Debug)]
+pub struct PreparedWrite {
+    pub pid: u64,
+    pub deadline: Instant,
 }
 
 #[derive(Debug, Serialize)]
@@ -97,6 +120,8 @@ pub struct WriteResponseObject {
     pub iid: u64,
     pub aid: u64,
     pub status: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Serialize)]
@@ -0,0 +1,203 @@
+use futures::{
+    channel::mpsc::UnboundedSender,
+    future::{self, Either},
+    stream::{self, BoxStream},
+    FutureExt,
+    Stream,
+    StreamExt,
+};
+use log::{error, warn};
+use std::time::Duration;
+use tokio::{
+    sync::broadcast::{error::RecvError, Receiver},
+    time::sleep,
+};
+
+use crate::{
+    event::Event,
+    pointer,
+    transport::http::{event_response, EventObject},
+};
+
+/// Returns the event notifications of a connection, i.e. the value changes of the characteristics it's subscribed to.
+/// If the connection falls behind on the events of the server, the current values of all of its subscribed
+/// characteristics are sent in place of the missed changes.
+pub fn notifications(
+    events: Receiver<Event>,
+    event_subscriptions: pointer::EventSubscriptions,
+    accessory_database: pointer::AccessoryDatabase,
+) -> BoxStream<'static, EventObject> {
+    stream::unfold(events, move |mut events| {
+        let event_subscriptions = event_subscriptions.clone();
+        let accessory_database = accessory_database.clone();
+        async move {
+            loop {
+                match events.recv().await {
+                    Ok(Event::CharacteristicValueChanged { aid, iid, value })
+                        if event_subscriptions.lock().await.contains(&(aid, iid)) =>
+                        return Some((vec![EventObject { aid, iid, value }], events)),
+                    Ok(_) => {},
+                    Err(RecvError::Lagged(count)) => {
+                        warn!("connection fell behind by {} events; resending its subscribed values", count);
+                        let current = current_values(&event_subscriptions, &accessory_database).await;
+                        return Some((current, events));
+                    },
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        }
+    })
+    .flat_map(stream::iter)
+    .boxed()
+}
+
+/// Reads the current values of the subscribed characteristics of a connection.
+async fn current_values(
+    event_subscriptions: &pointer::EventSubscriptions,
+    accessory_database: &pointer::AccessoryDatabase,
+) -> Vec<EventObject> {
+    let subscriptions = event_subscriptions.lock().await.clone();
+
+    let mut events = Vec::with_capacity(subscriptions.len());
+    for (aid, iid) in subscriptions {
+        let read = accessory_database
+            .lock()
+            .await
+            .read_characteristic(aid, iid, false, false, false, None)
+            .await;
+        match read {
+            Ok(read) =>
+                if let Some(value) = read.value {
+                    events.push(EventObject { aid, iid, value });
+                },
+            Err(e) => error!("error reading characteristic {}.{}: {:?}", aid, iid, e),
+        }
+    }
+
+    events
+}
+
+/// Sends the event notifications of a connection to its outgoing stream. Value changes arriving within the `window` of
+/// the first one are coalesced into a single event message, keeping only the latest value of every characteristic.
+/// Runs until the queue of notifications ends or the connection is gone.
+pub async fn run<S: Stream<Item = EventObject> + Unpin>(
+    mut queue: S,
+    outgoing: UnboundedSender<Vec<u8>>,
+    window: Option<Duration>,
+) {
+    while let Some(event) = queue.next().await {
+        let mut events = vec![event];
+
+        if let Some(window) = window {
+            let mut deadline = Box::pin(sleep(window));
+            while let Either::Left((Some(event), _)) = future::select(queue.next(), &mut deadline).await {
+                coalesce(&mut events, event);
+            }
+        }
+        while let Some(Some(event)) = queue.next().now_or_never() {
+            coalesce(&mut events, event);
+        }
+
+        match event_response(events) {
+            Ok(event_response) =>
+                if outgoing.unbounded_send(event_response).is_err() {
+                    break;
+                },
+            Err(e) => error!("couldn't create event response: {:?}", e),
+        }
+    }
+}
+
+/// Adds an event to the events of a message, replacing the value of an event of the same characteristic.
+fn coalesce(events: &mut Vec<EventObject>, event: EventObject) {
+    match events.iter_mut().find(|e| e.aid == event.aid && e.iid == event.iid) {
+        Some(e) => e.value = event.value,
+        None => events.push(event),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::{channel::mpsc, lock::Mutex};
+    use serde_json::json;
+    use std::sync::Arc;
+
+    use crate::{
+        accessory::{lightbulb::LightbulbAccessory, AccessoryInformation},
+        characteristic::HapCharacteristic,
+        event::{EventEmitter, EVENT_CAPACITY},
+        storage::accessory_database::AccessoryDatabase,
+    };
+
+    fn event(aid: u64, iid: u64, value: serde_json::Value) -> EventObject { EventObject { iid, aid, value } }
+
+    #[tokio::test]
+    async fn test_coalescing() {
+        let (queue_sender, queue) = mpsc::unbounded();
+        let (outgoing, mut outgoing_receiver) = mpsc::unbounded();
+        let task = tokio::spawn(run(queue, outgoing, Some(Duration::from_millis(20))));
+
+        queue_sender.unbounded_send(event(1, 10, json!(1))).unwrap();
+        queue_sender.unbounded_send(event(1, 11, json!(true))).unwrap();
+        queue_sender.unbounded_send(event(1, 10, json!(2))).unwrap();
+
+        let message = String::from_utf8(outgoing_receiver.next().await.unwrap()).unwrap();
+        let body = message.split("\n\n").nth(1).unwrap();
+        assert_eq!(
+            body,
+            "{\"characteristics\":[{\"iid\":10,\"aid\":1,\"value\":2},{\"iid\":11,\"aid\":1,\"value\":true}]}"
+        );
+
+        // changes after the window are sent in a message of their own
+        queue_sender.unbounded_send(event(1, 10, json!(3))).unwrap();
+        let message = String::from_utf8(outgoing_receiver.next().await.unwrap()).unwrap();
+        assert!(message.ends_with("{\"characteristics\":[{\"iid\":10,\"aid\":1,\"value\":3}]}"));
+
+        drop(queue_sender);
+        task.await.unwrap();
+        assert!(outgoing_receiver.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_notifications() {
+        let event_emitter = Arc::new(EventEmitter::new());
+        let mut lightbulb = LightbulbAccessory::new(1, AccessoryInformation::default()).unwrap();
+        let iid = lightbulb.lightbulb.power_state.get_id();
+        lightbulb.lightbulb.power_state.set_value(json!(true)).await.unwrap();
+        let mut accessory_database = AccessoryDatabase::new(event_emitter.clone());
+        accessory_database.add_accessory(Box::new(lightbulb)).unwrap();
+
+        let event_subscriptions = Arc::new(Mutex::new(vec![(1, iid)]));
+        let mut notifications = notifications(
+            event_emitter.receiver(),
+            event_subscriptions,
+            Arc::new(Mutex::new(accessory_database)),
+        );
+
+        // only changes of subscribed characteristics are notified
+        event_emitter.emit(Event::CharacteristicValueChanged {
+            aid: 1,
+            iid: iid + 1,
+            value: json!(1),
+        });
+        event_emitter.emit(Event::CharacteristicValueChanged {
+            aid: 1,
+            iid,
+            value: json!(false),
+        });
+        assert_eq!(notifications.next().await.unwrap().value, json!(false));
+
+        // a connection falling behind gets the current values of its subscriptions instead of the missed changes
+        for _ in 0..EVENT_CAPACITY + 1 {
+            event_emitter.emit(Event::CharacteristicValueChanged {
+                aid: 1,
+                iid,
+                value: json!(false),
+            });
+        }
+        let event = notifications.next().await.unwrap();
+        assert_eq!((event.aid, event.iid, event.value), (1, iid, json!(true)));
+    }
+}
@@ -1,38 +1,51 @@
 use futures::{
-    channel::oneshot,
+    channel::{mpsc, oneshot},
     future::{self, BoxFuture, Future, FutureExt, TryFutureExt},
     lock::Mutex,
+    StreamExt,
+};
+use hyper::{
+    header::{self, HeaderValue},
+    server::conn::Http,
+    service::Service,
+    Body,
+    Method,
+    Request,
+    Response,
+    StatusCode,
 };
-use hyper::{server::conn::Http, service::Service, Body, Method, Request, Response, StatusCode};
 use log::{debug, error, info};
 use std::{
-    net::SocketAddr,
+    net::{IpAddr, Ipv6Addr, SocketAddr},
     pin::Pin,
-    sync::Arc,
+    sync::{Arc, RwLock},
     task::{Context, Poll},
+    time::{Duration, Instant},
 };
-use tokio::net::TcpListener;
+use tokio::{net::TcpListener, time::sleep};
+use uuid::Uuid;
 
 use crate::{
     event::Event,
     pointer,
     transport::{
         http::{
-            event_response,
             handler::{
                 accessories::Accessories,
-                characteristics::{GetCharacteristics, UpdateCharacteristics},
+                characteristics::{GetCharacteristics, PrepareCharacteristics, UpdateCharacteristics},
                 identify::Identify,
                 pair_setup::PairSetup,
                 pair_verify::PairVerify,
-                pairings::Pairings,
+                pairings::{self, Pairings},
                 HandlerExt,
                 JsonHandler,
                 TlvHandler,
             },
+            notification_queue,
             status_response,
-            EventObject,
         },
+        connection::{self, ConnectionRegistry, ConnectionState},
+        pairing_coordinator::PairingCoordinator,
         tcp::{EncryptedStream, Session, StreamWrapper},
     },
     Error,
@@ -45,12 +58,14 @@ struct Handlers {
     pub accessories: Arc<Mutex<Box<dyn HandlerExt + Send + Sync>>>,
     pub get_characteristics: Arc<Mutex<Box<dyn HandlerExt + Send + Sync>>>,
     pub put_characteristics: Arc<Mutex<Box<dyn HandlerExt + Send + Sync>>>,
+    pub prepare: Arc<Mutex<Box<dyn HandlerExt + Send + Sync>>>,
     pub pairings: Arc<Mutex<Box<dyn HandlerExt + Send + Sync>>>,
     pub identify: Arc<Mutex<Box<dyn HandlerExt + Send + Sync>>>,
 }
 
 struct Api {
     controller_id: pointer::ControllerId,
+    last_activity: pointer::LastActivity,
     event_subscriptions: pointer::EventSubscriptions,
     config: pointer::Config,
     storage: pointer::Storage,
@@ -60,29 +75,40 @@ struct Api {
 }
 
 impl Api {
-    fn new(
-        controller_id: pointer::ControllerId,
-        event_subscriptions: pointer::EventSubscriptions,
-        config: pointer::Config,
-        storage: pointer::Storage,
-        accessory_database: pointer::AccessoryDatabase,
-        event_emitter: pointer::EventEmitter,
-        session_sender: oneshot::Sender<Session>,
-    ) -> Self {
+    fn new(server: &Server, connection: ConnectionState, session_sender: oneshot::Sender<Session>) -> Self {
+        let prepared_write = Arc::new(Mutex::new(None));
+        let session_sender = Arc::new(Mutex::new(Some(session_sender)));
+
         Api {
-            controller_id,
-            event_subscriptions,
-            config,
-            storage,
-            accessory_database,
-            event_emitter,
+            controller_id: connection.controller_id,
+            last_activity: connection.last_activity,
+            event_subscriptions: connection.event_subscriptions,
+            config: server.config.clone(),
+            storage: server.storage.clone(),
+            accessory_database: server.accessory_database.clone(),
+            event_emitter: server.event_emitter.clone(),
             handlers: Handlers {
-                pair_setup: Arc::new(Mutex::new(Box::new(TlvHandler::from(PairSetup::new())))),
-                pair_verify: Arc::new(Mutex::new(Box::new(TlvHandler::from(PairVerify::new(session_sender))))),
+                pair_setup: Arc::new(Mutex::new(Box::new(TlvHandler::from(PairSetup::new(
+                    connection.id,
+                    server.pairing_coordinator.clone(),
+                    server.authenticator.clone(),
+                    session_sender.clone(),
+                ))))),
+                pair_verify: Arc::new(Mutex::new(Box::new(TlvHandler::from(PairVerify::new(
+                    session_sender,
+                    server.session_cache.clone(),
+                ))))),
                 accessories: Arc::new(Mutex::new(Box::new(JsonHandler::from(Accessories::new())))),
                 get_characteristics: Arc::new(Mutex::new(Box::new(JsonHandler::from(GetCharacteristics::new())))),
-                put_characteristics: Arc::new(Mutex::new(Box::new(JsonHandler::from(UpdateCharacteristics::new())))),
-                pairings: Arc::new(Mutex::new(Box::new(TlvHandler::from(Pairings::new())))),
+                put_characteristics: Arc::new(Mutex::new(Box::new(JsonHandler::from(UpdateCharacteristics::new(
+                    prepared_write.clone(),
+                ))))),
+                prepare: Arc::new(Mutex::new(Box::new(JsonHandler::from(PrepareCharacteristics::new(
+                    prepared_write,
+                ))))),
+                pairings: Arc::new(Mutex::new(Box::new(TlvHandler::from(Pairings::new(
+                    server.connections.clone(),
+                ))))),
                 identify: Arc::new(Mutex::new(Box::new(JsonHandler::from(Identify::new())))),
             },
         }
@@ -99,6 +125,8 @@ impl Service<Request<Body>> for Api {
     }
 
     fn call(&mut self, req: Request<Body>) -> Self::Future {
+        *self.last_activity.write().expect("setting last_activity") = Instant::now();
+
         let (parts, body) = req.into_parts();
         let method = parts.method;
         let uri = parts.uri;
@@ -109,6 +137,7 @@ impl Service<Request<Body>> for Api {
             (Method::GET, "/accessories") => Some(self.handlers.accessories.clone()),
             (Method::GET, "/characteristics") => Some(self.handlers.get_characteristics.clone()),
             (Method::PUT, "/characteristics") => Some(self.handlers.put_characteristics.clone()),
+            (Method::PUT, "/prepare") => Some(self.handlers.prepare.clone()),
             (Method::POST, "/pairings") => Some(self.handlers.pairings.clone()),
             (Method::POST, "/identify") => Some(self.handlers.identify.clone()),
             _ => None,
@@ -122,7 +151,9 @@ impl Service<Request<Body>> for Api {
         let event_emitter = self.event_emitter.clone();
 
         let fut = async move {
-            match handler.take() {
+            let was_verified = controller_id.read().expect("reading controller_id").is_some();
+
+            let res = match handler.take() {
                 Some(handler) =>
                     handler
                         .lock()
@@ -130,7 +161,7 @@ impl Service<Request<Body>> for Api {
                         .handle(
                             uri,
                             body,
-                            controller_id,
+                            controller_id.clone(),
                             event_subscriptions,
                             config,
                             storage,
@@ -139,6 +170,17 @@ impl Service<Request<Body>> for Api {
                         )
                         .await,
                 None => future::ready(status_response(StatusCode::NOT_FOUND)).await,
+            };
+
+            // if the pairing of the controller was removed by the request, the connection is closed once the response
+            // is sent
+            let is_verified = controller_id.read().expect("reading controller_id").is_some();
+            match res {
+                Ok(mut response) if was_verified && !is_verified => {
+                    response.headers_mut().insert(header::CONNECTION, HeaderValue::from_static("close"));
+                    Ok(response)
+                },
+                res => res,
             }
         }
         .boxed();
@@ -154,8 +196,18 @@ pub struct Server {
     accessory_database: pointer::AccessoryDatabase,
     event_emitter: pointer::EventEmitter,
     mdns_responder: pointer::MdnsResponder,
+    pairing_coordinator: pointer::PairingCoordinator,
+    session_cache: pointer::SessionCache,
+    authenticator: pointer::Authenticator,
+    connections: pointer::ConnectionRegistry,
+    shutdown_sender: Arc<std::sync::Mutex<Option<oneshot::Sender<()>>>>,
+    rebind_sender: Arc<std::sync::Mutex<Option<mpsc::UnboundedSender<()>>>>,
 }
 
+/// Time to wait before accepting again after accepting a TCP connection failed, e.g. because the process ran out of
+/// file descriptors.
+const ACCEPT_ERROR_DELAY: Duration = Duration::from_millis(100);
+
 impl Server {
     pub fn new(
         config: pointer::Config,
@@ -163,109 +215,370 @@ impl Server {
         accessory_database: pointer::AccessoryDatabase,
         event_emitter: pointer::EventEmitter,
         mdns_responder: pointer::MdnsResponder,
+        session_cache: pointer::SessionCache,
+        authenticator: pointer::Authenticator,
     ) -> Self {
+        let pairing_coordinator = Arc::new(Mutex::new(PairingCoordinator::new(storage.clone())));
+        let connections = Arc::new(Mutex::new(ConnectionRegistry::new(
+            event_emitter.clone(),
+            pairing_coordinator.clone(),
+        )));
+
         Server {
             config,
             storage,
             accessory_database,
             event_emitter,
             mdns_responder,
+            pairing_coordinator,
+            session_cache,
+            authenticator,
+            connections,
+            shutdown_sender: Arc::new(std::sync::Mutex::new(None)),
+            rebind_sender: Arc::new(std::sync::Mutex::new(None)),
+        }
+    }
+
+    /// Signals a running server to shut down. The run handle stops accepting connections, closes all live
+    /// connections, shuts the mDNS responder down and finishes afterwards. A run handle that wasn't polled yet
+    /// finishes right away.
+    pub async fn shutdown(&self) {
+        if let Some(shutdown_sender) = self.shutdown_sender.lock().expect("taking shutdown_sender").take() {
+            shutdown_sender.send(()).ok();
+        }
+    }
+
+    /// Signals a running server to rebind its TCP listeners to the current `hosts` of the `Config`. Listeners on
+    /// unchanged addresses and live connections are kept open.
+    pub async fn rebind(&self) {
+        if let Some(rebind_sender) = self.rebind_sender.lock().expect("reading rebind_sender").as_ref() {
+            rebind_sender.unbounded_send(()).ok();
         }
     }
 
+    /// Returns the registry of the live connections of the server.
+    pub fn connections(&self) -> pointer::ConnectionRegistry { self.connections.clone() }
+
+    /// Closes every live connection, dropping the encrypted sessions of all controllers.
+    pub async fn close_connections(&self) { self.connections.lock().await.close_all().await; }
+
+    /// Removes the pairing of a controller and closes its connections.
+    pub async fn remove_pairing(&self, id: &Uuid) -> Result<()> {
+        pairings::remove_pairing(id, &self.storage, &self.event_emitter, &self.connections, None).await
+    }
+
     pub fn run_handle(&self) -> BoxFuture<Result<()>> {
+        let server = self.clone();
         let config = self.config.clone();
-        let storage = self.storage.clone();
         let accessory_database = self.accessory_database.clone();
         let event_emitter = self.event_emitter.clone();
         let mdns_responder = self.mdns_responder.clone();
+        let connections = self.connections.clone();
+
+        // the signal channels are set up before the run handle is polled, so signals sent in between aren't lost
+        let (sender, mut shutdown_receiver) = oneshot::channel();
+        *self.shutdown_sender.lock().expect("setting shutdown_sender") = Some(sender);
+        let (sender, mut rebind_receiver) = mpsc::unbounded();
+        *self.rebind_sender.lock().expect("setting rebind_sender") = Some(sender);
 
         async move {
-            let config_lock = config.lock().await;
-            let socket_addr = SocketAddr::new(config_lock.host, config_lock.port);
-            drop(config_lock);
+            let mut listeners = bind_listeners(&config).await?;
 
-            info!("binding TCP listener on {}", &socket_addr);
-            let listener = TcpListener::bind(socket_addr).await?;
+            let served: Result<()> = async {
+                mdns_responder.lock().await.update_records().await?;
+                event_emitter.emit(Event::ServerStarted);
 
-            mdns_responder.lock().await.update_records().await;
+                loop {
+                    let accept = future::select_all(listeners.iter().map(|listener| Box::pin(listener.accept())));
+                    let signal = future::select(&mut shutdown_receiver, rebind_receiver.next());
+                    let incoming = match future::select(accept, signal).await {
+                        future::Either::Left(((accepted, ..), _)) => Some(accepted),
+                        future::Either::Right((future::Either::Left(_), _)) => return Ok(()),
+                        future::Either::Right((future::Either::Right(_), _)) => None,
+                    };
+                    let (stream, peer_addr) = match incoming {
+                        Some(Ok(incoming)) => incoming,
+                        Some(Err(e)) => {
+                            error!("error accepting TCP connection: {:?}", e);
+                            sleep(ACCEPT_ERROR_DELAY).await;
+                            continue;
+                        },
+                        None => {
+                            rebind_listeners(&config, &mut listeners).await;
+                            continue;
+                        },
+                    };
 
-            loop {
-                let (stream, _socket_addr) = listener.accept().await?;
+                    debug!("incoming TCP stream from {}", peer_addr);
 
-                debug!("incoming TCP stream from {}", stream.peer_addr()?);
+                    let config_lock = config.lock().await;
+                    let max_connections = config_lock.max_connections;
+                    let pair_verify_timeout = config_lock.pair_verify_timeout;
+                    let idle_timeout = config_lock.idle_timeout;
+                    let event_coalescing_window = config_lock.event_coalescing_window;
+                    drop(config_lock);
 
-                let (
-                    encrypted_stream,
-                    stream_incoming,
-                    stream_outgoing,
-                    session_sender,
-                    incoming_waker,
-                    outgoing_waker,
-                ) = EncryptedStream::new(stream);
-                let stream_wrapper =
-                    StreamWrapper::new(stream_incoming, stream_outgoing.clone(), incoming_waker, outgoing_waker);
-                let event_subscriptions = Arc::new(Mutex::new(vec![]));
-
-                let api = Api::new(
-                    encrypted_stream.controller_id.clone(),
-                    event_subscriptions.clone(),
-                    config.clone(),
-                    storage.clone(),
-                    accessory_database.clone(),
-                    event_emitter.clone(),
-                    session_sender,
-                );
-
-                event_emitter.lock().await.add_listener(Box::new(move |event| {
-                    let event_subscriptions_ = event_subscriptions.clone();
-                    let stream_outgoing_ = stream_outgoing.clone();
-                    async move {
-                        match *event {
-                            Event::CharacteristicValueChanged { aid, iid, ref value } => {
-                                let mut dropped_subscriptions = vec![];
-                                for (i, &(s_aid, s_iid)) in event_subscriptions_.lock().await.iter().enumerate() {
-                                    if s_aid == aid && s_iid == iid {
-                                        let event = EventObject {
-                                            aid,
-                                            iid,
-                                            value: value.clone(),
-                                        };
-                                        let event_res =
-                                            event_response(vec![event]).expect("couldn't create event response");
-                                        if stream_outgoing_.unbounded_send(event_res).is_err() {
-                                            dropped_subscriptions.push(i);
-                                        }
-                                    }
-                                }
-                                let mut ev = event_subscriptions_.lock().await;
-                                for s in dropped_subscriptions {
-                                    ev.remove(s);
-                                }
+                    if !connections.lock().await.make_room(max_connections, peer_addr).await {
+                        continue;
+                    }
+
+                    let (
+                        encrypted_stream,
+                        stream_incoming,
+                        stream_outgoing,
+                        session_sender,
+                        incoming_waker,
+                        outgoing_waker,
+                    ) = EncryptedStream::new(stream);
+                    let stream_wrapper =
+                        StreamWrapper::new(stream_incoming, stream_outgoing.clone(), incoming_waker, outgoing_waker);
+                    let state = ConnectionState {
+                        id: connections.lock().await.next_id(),
+                        peer_addr,
+                        controller_id: encrypted_stream.controller_id.clone(),
+                        session_established: encrypted_stream.session_established.clone(),
+                        last_activity: Arc::new(RwLock::new(Instant::now())),
+                        event_subscriptions: Arc::new(Mutex::new(vec![])),
+                    };
+                    let connection_id = state.id;
+
+                    let api = Api::new(&server, state.clone(), session_sender);
+
+                    let notifications = notification_queue::notifications(
+                        event_emitter.receiver(),
+                        state.event_subscriptions.clone(),
+                        accessory_database.clone(),
+                    );
+                    let notifier =
+                        tokio::spawn(notification_queue::run(notifications, stream_outgoing, event_coalescing_window));
+
+                    let mut http = Http::new();
+                    http.http1_only(true);
+                    http.http1_half_close(true);
+                    http.http1_keep_alive(true);
+                    http.http1_preserve_header_case(true);
+
+                    let connection = future::select(
+                        encrypted_stream.map_err(|e| error!("{:?}", e)).boxed(),
+                        http.serve_connection(stream_wrapper, api)
+                            .map_err(|e| error!("{:?}", e))
+                            .boxed(),
+                    );
+                    let watchdog = connection::watchdog(
+                        pair_verify_timeout,
+                        idle_timeout,
+                        state.session_established.clone(),
+                        state.last_activity.clone(),
+                    );
+
+                    let mut connections_lock = connections.lock().await;
+                    let connections_ = connections.clone();
+                    let task = tokio::spawn(async move {
+                        match future::select(connection, watchdog.boxed()).await {
+                            future::Either::Left(_) => {
+                                debug!("connection {} closed", connection_id);
+                                connections_.lock().await.remove(connection_id).await;
                             },
-                            _ => {},
+                            future::Either::Right((timeout, _)) =>
+                                connections_.lock().await.time_out(connection_id, timeout).await,
                         }
-                    }
-                    .boxed()
-                }));
-
-                let mut http = Http::new();
-                http.http1_only(true);
-                http.http1_half_close(true);
-                http.http1_keep_alive(true);
-                http.http1_preserve_header_case(true);
-
-                tokio::spawn(encrypted_stream.map_err(|e| error!("{:?}", e)).map(|_| ()));
-                tokio::spawn(
-                    http.serve_connection(stream_wrapper, api)
-                        .map_err(|e| error!("{:?}", e))
-                        .map(|_| ()),
-                );
+                    });
+                    connections_lock.add(state, notifier, task);
+                    drop(connections_lock);
+                }
+            }
+            .await;
+
+            for listener in &listeners {
+                if let Ok(local_addr) = listener.local_addr() {
+                    info!("shutting down TCP listener on {}", local_addr);
+                }
             }
 
-            #[allow(unreachable_code)]
-            Ok(())
+            connections.lock().await.close_all().await;
+            let shut_down = mdns_responder.lock().await.shutdown().await;
+            event_emitter.emit(Event::ServerStopped);
+
+            served.and(shut_down)
         }
         .boxed()
     }
 }
+
+/// Returns the socket addresses to serve on according to the `Config`. Serves on `::` if there are no `hosts`.
+async fn socket_addrs(config: &pointer::Config) -> Vec<SocketAddr> {
+    let config = config.lock().await;
+    let mut hosts = config.hosts.clone();
+    if hosts.is_empty() {
+        hosts.push(IpAddr::V6(Ipv6Addr::UNSPECIFIED));
+    }
+
+    hosts.into_iter().map(|host| SocketAddr::new(host, config.port)).collect()
+}
+
+/// Binds TCP listeners to the `hosts` of the `Config`. Addresses that can't be bound are skipped; binding only fails
+/// if none of them can be bound.
+async fn bind_listeners(config: &pointer::Config) -> Result<Vec<TcpListener>> {
+    let mut listeners = Vec::new();
+    let mut last_error = None;
+    for socket_addr in socket_addrs(config).await {
+        info!("binding TCP listener on {}", &socket_addr);
+        match TcpListener::bind(socket_addr).await {
+            Ok(listener) => listeners.push(listener),
+            Err(e) => {
+                error!("error binding TCP listener on {}: {:?}", &socket_addr, e);
+                last_error = Some(e);
+            },
+        }
+    }
+
+    match last_error {
+        Some(e) if listeners.is_empty() => Err(e.into()),
+        _ => Ok(listeners),
+    }
+}
+
+/// Rebinds the TCP listeners to the current `hosts` of the `Config`. Listeners on unchanged addresses are kept. If none
+/// of the new addresses can be bound, the previous listeners are kept as well.
+async fn rebind_listeners(config: &pointer::Config, listeners: &mut Vec<TcpListener>) {
+    let mut rebound = Vec::new();
+    for socket_addr in socket_addrs(config).await {
+        if let Some(i) = listeners
+            .iter()
+            .position(|listener| listener.local_addr().ok() == Some(socket_addr))
+        {
+            rebound.push(listeners.remove(i));
+            continue;
+        }
+
+        info!("binding TCP listener on {}", &socket_addr);
+        match TcpListener::bind(socket_addr).await {
+            Ok(listener) => rebound.push(listener),
+            Err(e) => error!("error binding TCP listener on {}: {:?}", &socket_addr, e),
+        }
+    }
+
+    if rebound.is_empty() {
+        error!("couldn't bind any TCP listener; keeping the previous ones");
+        return;
+    }
+
+    for listener in listeners.iter() {
+        if let Ok(local_addr) = listener.local_addr() {
+            info!("shutting down TCP listener on {}", local_addr);
+        }
+    }
+
+    *listeners = rebound;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::{Ipv4Addr, TcpStream};
+
+    use crate::{
+        advertiser::MemoryAdvertiser,
+        event::EventEmitter,
+        storage::{accessory_database::AccessoryDatabase, FileStorage},
+        transport::{mdns::MdnsResponder, session_cache::SessionCache},
+        Config,
+    };
+
+    fn config(hosts: Vec<IpAddr>) -> pointer::Config {
+        Arc::new(Mutex::new(Config {
+            hosts,
+            port: 0,
+            ..Default::default()
+        }))
+    }
+
+    async fn server(config: pointer::Config, event_emitter: pointer::EventEmitter) -> Server {
+        let mut temp_dir = std::env::temp_dir();
+        temp_dir.push("hap-http-server");
+
+        let storage: pointer::Storage = Arc::new(Mutex::new(Box::new(FileStorage::new(&temp_dir).await.unwrap())));
+        let c = config.lock().await;
+        let session_cache = SessionCache::new(c.session_cache_size, c.session_cache_ttl);
+        drop(c);
+        let authenticator: pointer::Authenticator = Arc::new(Mutex::new(None));
+
+        Server::new(
+            config.clone(),
+            storage,
+            Arc::new(Mutex::new(AccessoryDatabase::new(event_emitter.clone()))),
+            event_emitter.clone(),
+            Arc::new(Mutex::new(MdnsResponder::new(
+                config,
+                authenticator.clone(),
+                Box::new(MemoryAdvertiser::new()),
+            ))),
+            Arc::new(Mutex::new(session_cache)),
+            authenticator,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_before_run() {
+        let event_emitter = Arc::new(EventEmitter::new());
+        let mut events = event_emitter.subscribe();
+        let server = server(config(vec![IpAddr::V4(Ipv4Addr::LOCALHOST)]), event_emitter).await;
+
+        // shutting down a server that isn't running has no effect on later runs
+        server.shutdown().await;
+
+        let handle = server.run_handle();
+        server.shutdown().await;
+        handle.await.unwrap();
+
+        assert_eq!(events.next().await, Some(Event::ServerStarted));
+        assert_eq!(events.next().await, Some(Event::ServerStopped));
+    }
+
+    #[tokio::test]
+    async fn test_restart() {
+        let port = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let config = config(vec![IpAddr::V4(Ipv4Addr::LOCALHOST)]);
+        config.lock().await.port = port;
+
+        let event_emitter = Arc::new(EventEmitter::new());
+        let mut events = event_emitter.subscribe();
+        let server = server(config, event_emitter).await;
+
+        for _ in 0..2 {
+            let server_ = server.clone();
+            let handle = tokio::spawn(async move { server_.run_handle().await });
+            assert_eq!(events.next().await, Some(Event::ServerStarted));
+
+            let stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+            let peer_addr = stream.local_addr().unwrap();
+            assert_eq!(events.next().await, Some(Event::ConnectionOpened { peer_addr }));
+
+            // shutting down closes the live connections and releases the listeners
+            server.shutdown().await;
+            handle.await.unwrap().unwrap();
+            assert_eq!(events.next().await, Some(Event::ConnectionClosed { peer_addr }));
+            assert_eq!(events.next().await, Some(Event::ServerStopped));
+            assert!(server.connections.lock().await.connections().await.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bind_listeners() {
+        // 192.0.2.1 is reserved for documentation and never assigned to a local interface
+        let unassigned = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+
+        let listeners = bind_listeners(&config(vec![IpAddr::V4(Ipv4Addr::LOCALHOST), unassigned]))
+            .await
+            .unwrap();
+        assert_eq!(listeners.len(), 1);
+        assert_eq!(listeners[0].local_addr().unwrap().ip(), IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+        assert!(bind_listeners(&config(vec![unassigned])).await.is_err());
+    }
+}
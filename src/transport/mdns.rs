@@ -1,61 +1,81 @@
-use libmdns::{Responder, Service};
+use futures::future::BoxFuture;
 use log::debug;
 
-use crate::pointer;
+use crate::{
+    advertiser::{Advertiser, ServiceRecord},
+    pointer,
+    Result,
+};
 
-/// An mDNS Responder. Used to announce the Accessory's name and HAP TXT records to potential controllers.
+/// An mDNS Responder. Used to announce the Accessory's name and HAP TXT records to potential controllers via an
+/// [`Advertiser`](Advertiser).
 pub struct MdnsResponder {
     config: pointer::Config,
-    responder: Responder,
-    service: Option<Service>,
-    task: Option<Box<dyn futures::Future<Output = ()> + Unpin + std::marker::Send>>,
+    authenticator: pointer::Authenticator,
+    advertiser: Box<dyn Advertiser>,
 }
 
 impl MdnsResponder {
     /// Creates a new mDNS Responder.
-    pub async fn new(config: pointer::Config) -> Self {
-        let (responder, task) = libmdns::Responder::with_default_handle().expect("creating mDNS responder");
-
+    pub fn new(
+        config: pointer::Config,
+        authenticator: pointer::Authenticator,
+        advertiser: Box<dyn Advertiser>,
+    ) -> Self {
         MdnsResponder {
             config,
-            responder,
-            service: None,
-            task: Some(task),
+            authenticator,
+            advertiser,
         }
     }
 
-    /// Derives new mDNS TXT records from the server's `Config`.
-    pub async fn update_records(&mut self) {
+    /// Derives new mDNS TXT records from the server's `Config`. The Bonjour Feature Flag is taken from the server's
+    /// [`Authenticator`](crate::authenticator::Authenticator) if one is set.
+    pub async fn update_records(&mut self) -> Result<()> {
         debug!("attempting to set mDNS records");
 
-        self.service = None;
-
+        let authenticator_flag = self.authenticator.lock().await.as_ref().map(|a| a.feature_flag());
         let c = self.config.lock().await;
 
-        let name = c.name.clone();
-        let port = c.port;
-        let tr = c.txt_records();
+        let record = ServiceRecord {
+            name: c.name.clone(),
+            service_type: "_hap._tcp".into(),
+            port: c.port,
+            txt_records: c.txt_records(authenticator_flag.unwrap_or(c.feature_flag)).to_vec(),
+        };
 
         drop(c);
 
-        self.service = Some(self.responder.register("_hap._tcp".into(), name, port, &[
-            &tr[0], &tr[1], &tr[2], &tr[3], &tr[4], &tr[5], &tr[6], &tr[7],
-        ]));
+        self.advertiser.register(&record).await?;
+
+        debug!("setting mDNS records: {:?}", &record.txt_records);
 
-        debug!("setting mDNS records: {:?}", &tr);
+        Ok(())
     }
 
-    /// Returns the mDNS task to throw on a scheduler.
-    pub fn run_handle(&mut self) -> Box<dyn futures::Future<Output = ()> + Unpin + std::marker::Send> {
-        match self.task.take() {
-            Some(task) => task,
-            // if the task handle is gone, recreate the whole responder
-            None => {
-                let (responder, task) = libmdns::Responder::with_default_handle().expect("creating mDNS responder");
-                self.responder = responder;
-
-                task
-            },
-        }
+    /// Unregisters the service, which sends goodbye packets to the network, and stops the advertiser. The mDNS task
+    /// returned by `run_handle` finishes afterwards. Calling `run_handle` again restarts the advertiser.
+    pub async fn shutdown(&mut self) -> Result<()> {
+        debug!("shutting down mDNS responder");
+
+        self.advertiser.stop().await
+    }
+
+    /// Restarts the advertiser for the current `hosts` of the server's `Config` and registers the service again.
+    /// Returns the task of the restarted advertiser to throw on a scheduler. The previous task finishes.
+    pub async fn rebuild(&mut self) -> Result<BoxFuture<'static, ()>> {
+        debug!("rebuilding mDNS responder");
+
+        let task = self.run_handle().await?;
+        self.update_records().await?;
+
+        Ok(task)
+    }
+
+    /// Starts the advertiser on the `hosts` of the server's `Config` and returns the mDNS task to throw on a scheduler.
+    pub async fn run_handle(&mut self) -> Result<BoxFuture<'static, ()>> {
+        let hosts = self.config.lock().await.hosts.clone();
+
+        self.advertiser.start(&hosts).await
     }
 }
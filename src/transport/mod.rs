@@ -4,8 +4,11 @@ use sha2::Sha512;
 use crate::{Error, Result};
 
 pub(crate) mod bonjour;
+pub(crate) mod connection;
 pub(crate) mod http;
 pub(crate) mod mdns;
+pub(crate) mod pairing_coordinator;
+pub(crate) mod session_cache;
 pub(crate) mod tcp;
 
 pub(crate) fn hkdf_extract_and_expand(salt: &[u8], ikm: &[u8], info: &[u8]) -> Result<[u8; 32]> {
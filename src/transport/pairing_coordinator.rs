@@ -0,0 +1,142 @@
+use log::{debug, info};
+use std::time::{Duration, Instant};
+
+use crate::{pointer, tlv};
+
+/// Time after which a pair setup that wasn't finished no longer blocks other controllers.
+const PAIR_SETUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A pair setup in progress, identified by the connection performing it.
+#[derive(Debug)]
+struct PairSetup {
+    connection_id: u64,
+    expires: Instant,
+}
+
+impl PairSetup {
+    fn is_expired(&self, now: Instant) -> bool { now >= self.expires }
+}
+
+/// SRP salt and verifier of a transient pair setup, kept for the split pair setup following it.
+#[derive(Debug, Clone)]
+pub struct SplitSetup {
+    pub salt: [u8; 16],
+    pub verifier: Vec<u8>,
+}
+
+/// Coordinates the pair setups of all connections of the server. Only one controller at a time can perform a pair
+/// setup, and only as long as the accessory isn't paired yet.
+pub struct PairingCoordinator {
+    storage: pointer::Storage,
+    pair_setup: Option<PairSetup>,
+    split_setup: Option<SplitSetup>,
+}
+
+impl PairingCoordinator {
+    /// Creates a new `PairingCoordinator`.
+    pub fn new(storage: pointer::Storage) -> Self {
+        PairingCoordinator {
+            storage,
+            pair_setup: None,
+            split_setup: None,
+        }
+    }
+
+    /// Starts a pair setup for a connection. Fails with `Unavailable` if the accessory is already paired and with
+    /// `Busy` if another connection is performing a pair setup that hasn't timed out yet.
+    pub async fn start_pair_setup(&mut self, connection_id: u64) -> Result<(), tlv::Error> {
+        if self.storage.lock().await.count_pairings().await? > 0 {
+            info!("refusing pair setup on connection {}: already paired", connection_id);
+            return Err(tlv::Error::Unavailable);
+        }
+
+        let now = Instant::now();
+        if let Some(ref pair_setup) = self.pair_setup {
+            if pair_setup.connection_id != connection_id && !pair_setup.is_expired(now) {
+                info!(
+                    "refusing pair setup on connection {}: connection {} is pairing",
+                    connection_id, pair_setup.connection_id
+                );
+                return Err(tlv::Error::Busy);
+            }
+        }
+
+        debug!("starting pair setup on connection {}", connection_id);
+        self.pair_setup = Some(PairSetup {
+            connection_id,
+            expires: now + PAIR_SETUP_TIMEOUT,
+        });
+
+        Ok(())
+    }
+
+    /// Returns whether the connection is performing the current pair setup and it hasn't timed out yet.
+    pub fn is_pairing(&self, connection_id: u64) -> bool {
+        match self.pair_setup {
+            Some(ref pair_setup) => pair_setup.connection_id == connection_id && !pair_setup.is_expired(Instant::now()),
+            None => false,
+        }
+    }
+
+    /// Ends the pair setup of a connection, if it's performing one. Called once the pair setup finished or failed and
+    /// when the connection is closed.
+    pub fn end_pair_setup(&mut self, connection_id: u64) {
+        if self.pair_setup.as_ref().map(|p| p.connection_id) == Some(connection_id) {
+            debug!("ending pair setup on connection {}", connection_id);
+            self.pair_setup = None;
+        }
+    }
+
+    /// Keeps the SRP salt and verifier of a transient pair setup that requested a split pair setup to follow.
+    pub fn save_split_setup(&mut self, split_setup: SplitSetup) { self.split_setup = Some(split_setup); }
+
+    /// Takes the SRP salt and verifier saved by a preceding transient pair setup. They can only be used once.
+    pub fn take_split_setup(&mut self) -> Option<SplitSetup> { self.split_setup.take() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::lock::Mutex;
+    use std::sync::Arc;
+
+    use crate::{
+        pairing::{Pairing, Permissions},
+        storage::FileStorage,
+    };
+
+    #[tokio::test]
+    async fn test_single_pair_setup() {
+        let mut temp_dir = std::env::temp_dir();
+        temp_dir.push("hap-pairing-coordinator");
+
+        let storage: pointer::Storage = Arc::new(Mutex::new(Box::new(FileStorage::new(&temp_dir).await.unwrap())));
+        let mut coordinator = PairingCoordinator::new(storage.clone());
+
+        // a second connection is refused while the first one is pairing
+        assert!(coordinator.start_pair_setup(1).await.is_ok());
+        assert!(matches!(coordinator.start_pair_setup(2).await, Err(tlv::Error::Busy)));
+        assert!(coordinator.is_pairing(1));
+        assert!(!coordinator.is_pairing(2));
+
+        // abandoned pair setups don't block other connections
+        coordinator.pair_setup.as_mut().unwrap().expires = Instant::now();
+        assert!(!coordinator.is_pairing(1));
+        assert!(coordinator.start_pair_setup(2).await.is_ok());
+
+        coordinator.end_pair_setup(2);
+        assert!(coordinator.pair_setup.is_none());
+
+        // once paired, pair setup is unavailable
+        let pairing = Pairing::new(
+            uuid::Uuid::parse_str("bc158b86-cabf-432d-aee4-422ef0e3f1d5").unwrap(),
+            Permissions::Admin,
+            [0; 32],
+        );
+        storage.lock().await.save_pairing(&pairing).await.unwrap();
+        assert!(matches!(coordinator.start_pair_setup(1).await, Err(tlv::Error::Unavailable)));
+
+        storage.lock().await.delete_pairing(&pairing.id).await.unwrap();
+    }
+}
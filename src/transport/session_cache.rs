@@ -0,0 +1,122 @@
+use log::debug;
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+/// Length of the session IDs used for pair resume.
+pub const SESSION_ID_LEN: usize = 8;
+
+/// A session established by a pair verify, which a controller can resume on a new connection.
+#[derive(Debug)]
+struct CachedSession {
+    session_id: [u8; SESSION_ID_LEN],
+    controller_id: Uuid,
+    shared_secret: [u8; 32],
+    expires: Instant,
+}
+
+/// Caches the shared secrets of verified sessions, so controllers can resume them with a pair resume instead of
+/// running a full pair verify on every reconnect. The oldest sessions are evicted once the cache is full.
+#[derive(Debug)]
+pub struct SessionCache {
+    capacity: usize,
+    ttl: Duration,
+    sessions: VecDeque<CachedSession>,
+}
+
+impl SessionCache {
+    /// Creates a new `SessionCache` holding up to `capacity` sessions for `ttl` each. A capacity of `0` disables pair
+    /// resume.
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        SessionCache {
+            capacity,
+            ttl,
+            sessions: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Caches a session under its session ID.
+    pub fn insert(&mut self, session_id: [u8; SESSION_ID_LEN], controller_id: Uuid, shared_secret: [u8; 32]) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.remove_expired(Instant::now());
+        self.sessions.retain(|s| s.session_id != session_id);
+        while self.sessions.len() >= self.capacity {
+            if let Some(evicted) = self.sessions.pop_front() {
+                debug!("evicting cached session of controller {}", evicted.controller_id);
+            }
+        }
+
+        self.sessions.push_back(CachedSession {
+            session_id,
+            controller_id,
+            shared_secret,
+            expires: Instant::now() + self.ttl,
+        });
+    }
+
+    /// Returns the controller ID and shared secret of a session without removing it from the cache, if the session is
+    /// cached and hasn't expired yet.
+    pub fn get(&mut self, session_id: &[u8]) -> Option<(Uuid, [u8; 32])> {
+        self.remove_expired(Instant::now());
+
+        self.sessions
+            .iter()
+            .find(|s| s.session_id == session_id)
+            .map(|s| (s.controller_id, s.shared_secret))
+    }
+
+    /// Removes a session from the cache and returns its controller ID and shared secret, if the session is cached and
+    /// hasn't expired yet. A session can only be resumed once, as resuming it establishes a new one.
+    pub fn take(&mut self, session_id: &[u8]) -> Option<(Uuid, [u8; 32])> {
+        self.remove_expired(Instant::now());
+
+        let position = self.sessions.iter().position(|s| s.session_id == session_id)?;
+        self.sessions.remove(position).map(|s| (s.controller_id, s.shared_secret))
+    }
+
+    /// Removes all sessions from the cache, so none of them can be resumed anymore.
+    pub fn clear(&mut self) { self.sessions.clear(); }
+
+    fn remove_expired(&mut self, now: Instant) { self.sessions.retain(|s| s.expires > now); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_cache() {
+        let controller_id = Uuid::parse_str("bc158b86-cabf-432d-aee4-422ef0e3f1d5").unwrap();
+        let mut cache = SessionCache::new(2, Duration::from_secs(60));
+
+        cache.insert([1; SESSION_ID_LEN], controller_id, [1; 32]);
+        cache.insert([2; SESSION_ID_LEN], controller_id, [2; 32]);
+        cache.insert([3; SESSION_ID_LEN], controller_id, [3; 32]);
+
+        // the oldest session was evicted
+        assert_eq!(cache.take(&[1; SESSION_ID_LEN]), None);
+        assert_eq!(cache.get(&[2; SESSION_ID_LEN]), Some((controller_id, [2; 32])));
+        assert_eq!(cache.take(&[2; SESSION_ID_LEN]), Some((controller_id, [2; 32])));
+
+        // sessions can only be resumed once
+        assert_eq!(cache.take(&[2; SESSION_ID_LEN]), None);
+
+        // expired sessions can't be resumed
+        cache.sessions[0].expires = Instant::now();
+        assert_eq!(cache.take(&[3; SESSION_ID_LEN]), None);
+
+        // cleared sessions can't be resumed
+        cache.insert([4; SESSION_ID_LEN], controller_id, [4; 32]);
+        cache.clear();
+        assert_eq!(cache.take(&[4; SESSION_ID_LEN]), None);
+
+        let mut disabled = SessionCache::new(0, Duration::from_secs(60));
+        disabled.insert([1; SESSION_ID_LEN], controller_id, [1; 32]);
+        assert_eq!(disabled.take(&[1; SESSION_ID_LEN]), None);
+    }
+}
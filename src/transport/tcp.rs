@@ -16,7 +16,12 @@ use std::{
     future::Future,
     io::{self, ErrorKind},
     pin::Pin,
-    sync::{Arc, Mutex, RwLock},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+        Mutex,
+        RwLock,
+    },
     task::{Context, Poll, Waker},
 };
 use tokio::{
@@ -157,10 +162,12 @@ impl AsyncWrite for StreamWrapper {
     }
 }
 
+/// An encrypted session established by a pair verify, a pair resume or a transient pair setup. Sessions of transient
+/// pair setups don't belong to a paired controller and therefore have no controller ID.
 #[derive(Debug)]
 pub struct Session {
-    pub controller_id: Uuid,
-    pub shared_secret: [u8; 32],
+    pub controller_id: Option<Uuid>,
+    pub shared_secret: Vec<u8>,
 }
 
 #[derive(Debug)]
@@ -172,7 +179,10 @@ pub struct EncryptedStream {
     outgoing_waker: Arc<Mutex<Option<Waker>>>,
     session_receiver: oneshot::Receiver<Session>,
     pub controller_id: Arc<RwLock<Option<Uuid>>>,
-    shared_secret: Option<[u8; 32]>,
+    /// Whether an encrypted session was established on the stream, including sessions of transient pair setups,
+    /// which have no controller ID.
+    pub session_established: Arc<AtomicBool>,
+    shared_secret: Option<Vec<u8>>,
     decrypt_count: u64,
     encrypt_count: u64,
     encrypted_buf: BytesMut,
@@ -212,6 +222,7 @@ impl EncryptedStream {
                 outgoing_waker: outgoing_waker.clone(),
                 session_receiver: receiver,
                 controller_id: Arc::new(RwLock::new(None)),
+                session_established: Arc::new(AtomicBool::new(false)),
                 shared_secret: None,
                 decrypt_count: 0,
                 encrypt_count: 0,
@@ -257,7 +268,7 @@ impl EncryptedStream {
 
         if self.missing_data_for_decrypted_buf {
             let decrypted = decrypt_chunk(
-                &self.shared_secret.expect("missing shared secret"),
+                self.shared_secret.as_ref().expect("missing shared secret"),
                 &self.encrypted_buf[..2],
                 &self.encrypted_buf[2..(self.packet_len - 14)],
                 &self.encrypted_buf[(self.packet_len - 14)..(self.packet_len + 2)],
@@ -424,8 +435,8 @@ impl AsyncRead for EncryptedStream {
         if encrypted_stream.shared_secret.is_none() {
             match encrypted_stream.session_receiver.try_recv() {
                 Ok(Some(session)) => {
-                    *encrypted_stream.controller_id.write().expect("setting controller_id") =
-                        Some(session.controller_id);
+                    *encrypted_stream.controller_id.write().expect("setting controller_id") = session.controller_id;
+                    encrypted_stream.session_established.store(true, Ordering::SeqCst);
                     encrypted_stream.shared_secret = Some(session.shared_secret);
                 },
                 _ => {
@@ -451,12 +462,12 @@ impl AsyncWrite for EncryptedStream {
     fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<std::result::Result<usize, Error>> {
         let encrypted_stream = Pin::into_inner(self);
 
-        if let Some(shared_secret) = encrypted_stream.shared_secret {
+        if let Some(ref shared_secret) = encrypted_stream.shared_secret {
             let mut write_buf = BytesMut::from(buf);
 
             while write_buf.len() > 1024 {
                 let (aad, chunk, auth_tag) =
-                    encrypt_chunk(&shared_secret, &write_buf[..1024], &mut encrypted_stream.encrypt_count)
+                    encrypt_chunk(shared_secret, &write_buf[..1024], &mut encrypted_stream.encrypt_count)
                         .map_err(|_| io::Error::new(io::ErrorKind::Other, "encryption failed"))?;
 
                 let data = [&aad[..], &chunk[..], &auth_tag[..]].concat();
@@ -465,7 +476,7 @@ impl AsyncWrite for EncryptedStream {
                 write_buf.advance(1024);
             }
 
-            let (aad, chunk, auth_tag) = encrypt_chunk(&shared_secret, &write_buf, &mut encrypted_stream.encrypt_count)
+            let (aad, chunk, auth_tag) = encrypt_chunk(shared_secret, &write_buf, &mut encrypted_stream.encrypt_count)
                 .map_err(|_| io::Error::new(io::ErrorKind::Other, "encryption failed"))?;
 
             let data = [&aad[..], &chunk[..], &auth_tag[..]].concat();
@@ -488,7 +499,7 @@ impl AsyncWrite for EncryptedStream {
 }
 
 fn decrypt_chunk(
-    shared_secret: &[u8; 32],
+    shared_secret: &[u8],
     aad: &[u8],
     data: &[u8],
     auth_tag: &[u8],
@@ -510,7 +521,7 @@ fn decrypt_chunk(
     Ok(buffer)
 }
 
-fn encrypt_chunk(shared_secret: &[u8; 32], data: &[u8], count: &mut u64) -> Result<([u8; 2], Vec<u8>, [u8; 16])> {
+fn encrypt_chunk(shared_secret: &[u8], data: &[u8], count: &mut u64) -> Result<([u8; 2], Vec<u8>, [u8; 16])> {
     let write_key = compute_write_key(shared_secret)?;
     let aead = ChaCha20Poly1305::new(GenericArray::from_slice(&write_key));
 
@@ -530,14 +541,14 @@ fn encrypt_chunk(shared_secret: &[u8; 32], data: &[u8], count: &mut u64) -> Resu
     Ok((aad, buffer, auth_tag.into()))
 }
 
-fn compute_read_key(shared_secret: &[u8; 32]) -> Result<[u8; 32]> {
+fn compute_read_key(shared_secret: &[u8]) -> Result<[u8; 32]> {
     compute_key(shared_secret, b"Control-Write-Encryption-Key")
 }
 
-fn compute_write_key(shared_secret: &[u8; 32]) -> Result<[u8; 32]> {
+fn compute_write_key(shared_secret: &[u8]) -> Result<[u8; 32]> {
     compute_key(shared_secret, b"Control-Read-Encryption-Key")
 }
 
-fn compute_key(shared_secret: &[u8; 32], info: &[u8]) -> Result<[u8; 32]> {
+fn compute_key(shared_secret: &[u8], info: &[u8]) -> Result<[u8; 32]> {
     super::hkdf_extract_and_expand(b"Control-Salt", shared_secret, info)
 }